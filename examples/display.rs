@@ -0,0 +1,13 @@
+use dir_meta::DirMetadata;
+
+fn main() {
+    smol::block_on(async {
+        let outcome = DirMetadata::new("src")
+            .unwrap()
+            .dir_metadata()
+            .await
+            .unwrap();
+
+        print!("{outcome}");
+    });
+}
@@ -0,0 +1,26 @@
+use dir_meta::DirMetadata;
+
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or_else(|| ".".to_owned());
+
+    let scan = match DirMetadata::new(&path) {
+        Ok(scan) => scan,
+        Err(error) => {
+            eprintln!("Unable to scan `{path}`: {error}");
+            std::process::exit(1);
+        }
+    };
+
+    smol::block_on(async {
+        let outcome = scan.dir_metadata().await.unwrap();
+
+        for image in outcome.images() {
+            println!(
+                "{} ({}, {})",
+                image.path().display(),
+                image.file_format().name(),
+                image.formatted_size()
+            );
+        }
+    });
+}
@@ -0,0 +1,42 @@
+use dir_meta::{DirMetadata, FsUtils};
+use std::io::Write;
+
+fn main() {
+    smol::block_on(async {
+        let outcome = DirMetadata::new(".")
+            .unwrap()
+            .on_progress(100, |progress| {
+                print!(
+                    "\rScanned {} files, {} directories, {}",
+                    progress.files_seen,
+                    progress.directories_seen,
+                    FsUtils::size_to_bytes(progress.bytes)
+                );
+                std::io::stdout().flush().unwrap();
+            })
+            .dir_metadata()
+            .await
+            .unwrap();
+
+        println!(
+            "\rScanned {} files, {} directories, {}",
+            outcome.files().len(),
+            outcome.directories().len(),
+            outcome.size_formatted()
+        );
+
+        for stats in outcome.extension_summary().iter().take(10) {
+            let extension = match &stats.extension {
+                Some(extension) => format!(".{extension}"),
+                None => "no extension".to_owned(),
+            };
+
+            println!(
+                "{} {} files ({})",
+                stats.count,
+                extension,
+                stats.size_formatted()
+            );
+        }
+    });
+}
@@ -0,0 +1,22 @@
+use dir_meta::{tokio, DirMetadata};
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let outcome = DirMetadata::new(".")
+            .unwrap()
+            .tokio_dir_metadata()
+            .await
+            .unwrap();
+
+        println!(
+            "{} files, {} directories, {}",
+            outcome.files().len(),
+            outcome.directories().len(),
+            outcome.size_formatted()
+        );
+    });
+}
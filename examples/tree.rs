@@ -0,0 +1,36 @@
+use dir_meta::{DirMetadata, DirNode, FsUtils};
+
+fn print_node(node: &DirNode, indent: usize) {
+    println!(
+        "{:indent$}{}/ ({})",
+        "",
+        node.name(),
+        FsUtils::size_to_bytes(node.size())
+    );
+
+    node.children()
+        .iter()
+        .for_each(|child| print_node(child, indent + 2));
+
+    node.files().iter().for_each(|file| {
+        println!(
+            "{:indent$}{} ({})",
+            "",
+            file.name(),
+            file.formatted_size(),
+            indent = indent + 2
+        )
+    });
+}
+
+fn main() {
+    smol::block_on(async {
+        let outcome = DirMetadata::new("src")
+            .unwrap()
+            .dir_metadata()
+            .await
+            .unwrap();
+
+        print_node(&outcome.as_tree(), 0);
+    });
+}
@@ -1,11 +1,14 @@
-use dir_meta::{inotify::WatchMask, smol::channel, FsWatcher, WatcherOutcome};
-
+#[cfg(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+))]
 fn main() {
+    use dir_meta::{smol::channel, FsWatcher, WatchFor, WatcherOutcome};
+
     smol::block_on(async {
         let (sender, receiver) = channel::unbounded::<WatcherOutcome>();
 
-        let watch_options =
-            WatchMask::MODIFY | WatchMask::CREATE | WatchMask::DELETE | WatchMask::DELETE_SELF;
+        let watch_options = WatchFor::FILE_CHANGES | WatchFor::DELETE_SELF;
 
         smol::spawn(async move {
             FsWatcher::new(sender)
@@ -21,3 +24,11 @@ fn main() {
         }
     });
 }
+
+#[cfg(not(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+)))]
+fn main() {
+    eprintln!("Watching needs the `watcher` feature on Linux or the `watcher-portable` feature");
+}
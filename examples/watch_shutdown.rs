@@ -0,0 +1,39 @@
+#[cfg(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+))]
+fn main() {
+    use dir_meta::{smol::channel, FsWatcher, WatchFor, WatcherOutcome};
+    use std::time::Duration;
+
+    smol::block_on(async {
+        let (sender, receiver) = channel::unbounded::<WatcherOutcome>();
+
+        let watch_options = WatchFor::FILE_CHANGES;
+        let (handle, watch) = FsWatcher::new(sender)
+            .path(".")
+            .watch_with_handle(watch_options);
+        let watch = smol::spawn(watch);
+
+        smol::spawn(async move {
+            while let Ok(data) = receiver.recv().await {
+                dbg!(data);
+            }
+        })
+        .detach();
+
+        smol::Timer::after(Duration::from_secs(5)).await;
+        handle.stop();
+
+        watch.await.unwrap();
+        println!("Stopped watching");
+    });
+}
+
+#[cfg(not(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+)))]
+fn main() {
+    eprintln!("Watching needs the `watcher` feature on Linux or the `watcher-portable` feature");
+}
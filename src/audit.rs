@@ -0,0 +1,139 @@
+use crate::{DirMetadata, FileMetadata};
+
+/// The files flagged by a hardening audit, created using [DirMetadata::security_report].
+/// Always empty without the `unix` feature or on platforms other than unix
+#[derive(Debug, PartialEq, Eq, Default, Clone)]
+pub struct SecurityReport<'s, 'a> {
+    /// The files anyone can write to, see [DirMetadata::world_writable]
+    pub world_writable: Vec<&'s FileMetadata<'a>>,
+    /// The files running as their owner, see [DirMetadata::setuid_files]
+    pub setuid: Vec<&'s FileMetadata<'a>>,
+    /// The files running as their group, see [DirMetadata::setgid_files]
+    pub setgid: Vec<&'s FileMetadata<'a>>,
+}
+
+impl SecurityReport<'_, '_> {
+    /// The number of files flagged, a file flagged more than once is counted once
+    pub fn flagged_count(&self) -> usize {
+        let mut paths = self
+            .world_writable
+            .iter()
+            .chain(&self.setuid)
+            .chain(&self.setgid)
+            .map(|file| file.path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+
+        paths.len()
+    }
+
+    /// Check if no file was flagged
+    pub fn is_empty(&self) -> bool {
+        self.world_writable.is_empty() && self.setuid.is_empty() && self.setgid.is_empty()
+    }
+}
+
+impl<'a> DirMetadata<'a> {
+    /// Get the files with the write permission for others, symbolic links are skipped
+    /// since their permissions are not used
+    pub fn world_writable(&self) -> Vec<&FileMetadata<'a>> {
+        self.files_with_mode(0o002)
+    }
+
+    /// Get the files with the setuid bit
+    pub fn setuid_files(&self) -> Vec<&FileMetadata<'a>> {
+        self.files_with_mode(0o4000)
+    }
+
+    /// Get the files with the setgid bit
+    pub fn setgid_files(&self) -> Vec<&FileMetadata<'a>> {
+        self.files_with_mode(0o2000)
+    }
+
+    /// Get the world writable, setuid and setgid files at once
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///     let report = outcome.security_report();
+    ///
+    ///     println!(
+    ///         "{} flagged: {} world writable, {} setuid, {} setgid",
+    ///         report.flagged_count(),
+    ///         report.world_writable.len(),
+    ///         report.setuid.len(),
+    ///         report.setgid.len()
+    ///     );
+    /// });
+    /// ```
+    pub fn security_report(&self) -> SecurityReport<'_, 'a> {
+        SecurityReport {
+            world_writable: self.world_writable(),
+            setuid: self.setuid_files(),
+            setgid: self.setgid_files(),
+        }
+    }
+
+    /// The files that are not symbolic links with any of `bits` set in their mode
+    #[allow(unused_variables)]
+    fn files_with_mode(&self, bits: u32) -> Vec<&FileMetadata<'a>> {
+        #[cfg(feature = "unix")]
+        {
+            self.files()
+                .iter()
+                .filter(|file| !file.symlink() && file.mode().is_some_and(|mode| mode & bits != 0))
+                .collect()
+        }
+
+        #[cfg(not(feature = "unix"))]
+        {
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod sanity_checks {
+    use crate::test_utils::{nested_fixture, scan};
+    use std::fs;
+
+    #[test]
+    fn security_report() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = nested_fixture();
+        let setuid = root.path().join("one").join("setuid");
+        let shared = root.path().join("shared.txt");
+        fs::write(&setuid, b"").unwrap();
+        fs::write(&shared, b"").unwrap();
+        fs::set_permissions(&setuid, fs::Permissions::from_mode(0o4755)).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o666)).unwrap();
+        std::os::unix::fs::symlink(&shared, root.path().join("link")).unwrap();
+
+        let outcome = scan(root.path(), None);
+        let report = outcome.security_report();
+
+        #[cfg(feature = "unix")]
+        {
+            let names = |files: &[&crate::FileMetadata]| {
+                files
+                    .iter()
+                    .map(|file| file.name().to_owned())
+                    .collect::<Vec<String>>()
+            };
+            assert_eq!(vec!["shared.txt"], names(&report.world_writable));
+            assert_eq!(vec!["setuid"], names(&report.setuid));
+            assert!(report.setgid.is_empty());
+            assert_eq!(2, report.flagged_count());
+        }
+        #[cfg(not(feature = "unix"))]
+        assert!(report.is_empty());
+    }
+}
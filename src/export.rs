@@ -0,0 +1,158 @@
+use crate::{DirMetadata, FileMetadata};
+use std::io::{self, Write};
+
+/// The value of a column written by [DirMetadata::write_csv] and [DirMetadata::write_ndjson]
+enum Column {
+    Text(String),
+    Size(u64),
+    Flag(bool),
+    Empty,
+}
+
+/// The columns exported for each file, in order
+fn columns(file: &FileMetadata) -> Vec<(&'static str, Column)> {
+    #[cfg(feature = "time")]
+    let timestamp = |time: Option<String>| time.map(Column::Text).unwrap_or(Column::Empty);
+
+    vec![
+        (
+            "path",
+            Column::Text(file.path().to_string_lossy().into_owned()),
+        ),
+        ("name", Column::Text(file.name().to_owned())),
+        ("size", Column::Size(file.size())),
+        #[cfg(feature = "time")]
+        ("created", timestamp(file.created_rfc3339())),
+        #[cfg(feature = "time")]
+        ("modified", timestamp(file.modified_rfc3339())),
+        #[cfg(feature = "time")]
+        ("accessed", timestamp(file.accessed_rfc3339())),
+        (
+            "format",
+            file.detected_format()
+                .map(|format| Column::Text(format.media_type().to_owned()))
+                .unwrap_or(Column::Empty),
+        ),
+        ("read_only", Column::Flag(file.read_only())),
+    ]
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break
+fn csv_field(column: &Column) -> String {
+    match column {
+        Column::Text(text) if text.contains([',', '"', '\n', '\r']) => {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        }
+        Column::Text(text) => text.clone(),
+        Column::Size(size) => size.to_string(),
+        Column::Flag(flag) => flag.to_string(),
+        Column::Empty => String::new(),
+    }
+}
+
+impl DirMetadata<'_> {
+    /// Write one CSV row for each file after a header row. The columns are
+    /// `path,name,size,created,modified,accessed,format,read_only`, the `created`,
+    /// `modified` and `accessed` columns are RFC 3339 timestamps in UTC and are only
+    /// written with the `time` feature. `format` is the media type of the file format
+    /// if it was detected and is empty otherwise. Paths and names that are not valid UTF-8
+    /// have the invalid characters replaced with `U+FFFD REPLACEMENT CHARACTER`
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     outcome.write_csv(std::io::stdout().lock()).unwrap();
+    /// });
+    /// ```
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        let header = columns(&FileMetadata::default())
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(",");
+        writeln!(writer, "{header}")?;
+
+        for file in self.files() {
+            let row = columns(file)
+                .iter()
+                .map(|(_, column)| csv_field(column))
+                .collect::<Vec<String>>()
+                .join(",");
+            writeln!(writer, "{row}")?;
+        }
+
+        writer.flush()
+    }
+
+    /// Write one JSON object for each file on its own line, with the same fields as
+    /// the columns of [Self::write_csv]. Empty columns are written as `null`
+    #[cfg(feature = "serde")]
+    pub fn write_ndjson(&self, mut writer: impl Write) -> io::Result<()> {
+        use serde_json::{Map, Value};
+
+        for file in self.files() {
+            let object = columns(file)
+                .into_iter()
+                .map(|(name, column)| {
+                    let value = match column {
+                        Column::Text(text) => Value::from(text),
+                        Column::Size(size) => Value::from(size),
+                        Column::Flag(flag) => Value::from(flag),
+                        Column::Empty => Value::Null,
+                    };
+
+                    (name.to_owned(), value)
+                })
+                .collect::<Map<String, Value>>();
+
+            serde_json::to_writer(&mut writer, &object)?;
+            writeln!(writer)?;
+        }
+
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+    use crate::test_utils::scan;
+    use std::fs;
+
+    #[test]
+    fn csv_and_ndjson_export() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("a, \"quoted\"\nname.txt"), b"abc").unwrap();
+        let outcome = scan(root.path(), None);
+
+        let mut csv = Vec::<u8>::new();
+        outcome.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        #[cfg(feature = "time")]
+        assert!(csv.starts_with("path,name,size,created,modified,accessed,format,read_only\n"));
+        #[cfg(not(feature = "time"))]
+        assert!(csv.starts_with("path,name,size,format,read_only\n"));
+        assert!(csv.contains(",\"a, \"\"quoted\"\"\nname.txt\",3,"));
+        assert!(csv.ends_with(",text/plain,false\n"));
+
+        #[cfg(feature = "serde")]
+        {
+            let mut ndjson = Vec::<u8>::new();
+            outcome.write_ndjson(&mut ndjson).unwrap();
+            let ndjson = String::from_utf8(ndjson).unwrap();
+
+            assert_eq!(1, ndjson.lines().count());
+            let file = serde_json::from_str::<serde_json::Value>(&ndjson).unwrap();
+            assert_eq!("a, \"quoted\"\nname.txt", file["name"]);
+            assert_eq!(3, file["size"]);
+            assert_eq!(false, file["read_only"]);
+        }
+    }
+}
@@ -1,328 +1,6804 @@
-use crate::{CowStr, FsUtils};
-use async_recursion::async_recursion;
-use file_format::FileFormat;
+use crate::{CowStr, FsUtils, SizeStyle, SENDER_CHANNEL_ERROR};
+use file_format::{FileFormat, Kind};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
 use smol::{
+    channel::{self, Receiver, Sender},
     fs::{read_dir, ReadDir},
-    io::{self, ErrorKind},
-    stream::StreamExt,
-    unblock,
+    future::Future,
+    io::{self, AsyncReadExt, ErrorKind},
+    stream::{Stream, StreamExt},
+    unblock, Task,
 };
 use std::{
-    borrow::Cow,
-    path::{Path, PathBuf},
+    borrow::{Borrow, Cow},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    ffi::{OsStr, OsString},
+    fmt,
+    hash::{Hash, Hasher},
+    io::Read,
+    iter::FusedIterator,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
 };
 use tai64::Tai64N;
 
 #[cfg(feature = "time")]
 use crate::DateTimeString;
+#[cfg(feature = "hash")]
+use crate::HashAlgo;
+#[cfg(feature = "gitignore")]
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "regex")]
+use regex::{Regex, RegexBuilder};
+#[cfg(feature = "unicode")]
+use unicode_normalization::UnicodeNormalization;
+
+/// Uniquely identifies a directory on disk, used to detect symlink cycles
+#[cfg(unix)]
+type DirIdentity = (u64, u64);
+#[cfg(not(unix))]
+type DirIdentity = PathBuf;
+
+/// The closure passed to [DirMetadata::filter_with]
+type EntryFilter = dyn FnMut(&Path, bool) -> bool + Send;
+
+/// A file waiting to be recorded, with the detection of its file format and the hashing
+/// of its content running on the blocking thread pool unless both are skipped or deferred
+type PendingFile<'a> = (FileMetadata<'a>, Option<Task<FileProbe>>);
+
+/// The result of the blocking work done for a file while scanning
+struct FileProbe {
+    file_format: Option<io::Result<FileFormat>>,
+    #[cfg(feature = "hash")]
+    digest: Option<io::Result<String>>,
+}
+
+/// The metadata of a directory entry, looked up on the blocking thread pool so that
+/// up to [DirMetadata::concurrency] entries of a directory are looked up at the same time
+struct EntryLookup {
+    path: PathBuf,
+    /// The metadata of the entry itself rather than of the target of a symlink
+    metadata: io::Result<std::fs::Metadata>,
+    /// The metadata of the target of a symlink, or of a directory for its times
+    target: Option<io::Result<std::fs::Metadata>>,
+    link_target: Option<io::Result<PathBuf>>,
+}
+
+impl EntryLookup {
+    fn new(path: PathBuf) -> Self {
+        #[cfg(test)]
+        let _probe = crate::test_utils::LookupProbe::enter(&path);
+
+        let metadata = std::fs::symlink_metadata(&path);
+        let (is_dir, is_symlink) = metadata
+            .as_ref()
+            .map(|meta| (meta.is_dir(), meta.is_symlink()))
+            .unwrap_or_default();
+
+        Self {
+            target: (is_symlink || (is_dir && cfg!(feature = "time")))
+                .then(|| std::fs::metadata(&path)),
+            link_target: is_symlink.then(|| std::fs::read_link(&path)),
+            metadata,
+            path,
+        }
+    }
+}
+
+/// What the threads of [DirMetadata::par_dir_metadata] share while reading the directories
+#[cfg(feature = "rayon")]
+#[derive(Debug, Default)]
+struct ParallelWalk {
+    /// The directories entered by any thread, see [DirMetadata::follow_symlinks]
+    visited: HashSet<DirIdentity>,
+    /// The files with several hard links that any thread already counted
+    #[cfg(feature = "unix")]
+    hard_links: HashSet<(u64, u64)>,
+}
+
+/// The item yielded by [FileStream]
+type StreamItem = Result<FileMetadata<'static>, DirError<'static>>;
+
+/// The size, modification time and file format of each file of the snapshot
+/// passed to [DirMetadata::rescan], keyed by path
+type PreviousFormats = HashMap<PathBuf, (u64, Tai64N, FileFormat)>;
+
+/// The closure passed to [DirMetadata::on_progress]
+type ProgressHook = dyn FnMut(&ScanProgress) + Send;
+
+/// The closure passed to [DirMetadata::on_error]
+type ErrorHook = dyn FnMut(&DirError) -> ErrorAction + Send;
 
 /// The Metadata of all directories and files in the current directory
 /// #### Example
 /// ```rust
 /// use dir_meta::DirMetadata;
 ///
-/// let dir = DirMetadata::new("/path/to/directory").dir_metadata();
+/// match DirMetadata::new("/path/to/directory") {
+///     Ok(dir) => {
+///         dbg!(smol::block_on(dir.dir_metadata()).unwrap().size());
+///     }
+///     Err(error) => eprintln!("Unable to scan the directory: {error}"),
+/// }
 /// ```
 #[derive(Debug, PartialEq, Eq, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DirMetadata<'a> {
     name: CowStr<'a>,
     path: PathBuf,
     directories: Vec<PathBuf>,
     files: Vec<FileMetadata<'a>>,
-    size: usize,
+    size: u64,
     errors: Vec<DirError<'a>>,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    visited: HashSet<DirIdentity>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    include_globs: GlobFilter,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    exclude_globs: GlobFilter,
+    extensions: Vec<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    skip_dir_names: Vec<String>,
+    skipped: Vec<PathBuf>,
+    #[cfg(feature = "time")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::tai64n_option"))]
+    modified_after: Option<Tai64N>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    filter_hook: Option<SharedHook<EntryFilter>>,
+    #[cfg(feature = "gitignore")]
+    respect_gitignore: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cancel_handle: Option<CancelHandle>,
+    cancelled: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    progress_hook: Option<(usize, SharedHook<ProgressHook>)>,
+    entries_seen: usize,
+    directories_seen: usize,
+    roots: Vec<PathBuf>,
+    same_filesystem: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    stream_sender: Option<StreamSender>,
+    concurrency: usize,
+    skip_format_detection: bool,
+    format_detection_limit: Option<u64>,
+    defer_format_detection: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    previous_formats: PreviousFormats,
+    disk_usage: u64,
+    #[cfg(feature = "unix")]
+    size_deduped: u64,
+    #[cfg(feature = "unix")]
+    hard_links: HashSet<(u64, u64)>,
+    max_depth_seen: usize,
+    dir_sizes: HashMap<PathBuf, u64>,
+    dir_file_counts: HashMap<PathBuf, usize>,
+    #[cfg(feature = "hash")]
+    hash_algo: Option<HashAlgo>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    path_index: PathIndex,
+    sorted: bool,
+    traversal_order: TraversalOrder,
+    strict: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    error_hook: Option<SharedHook<ErrorHook>>,
+    max_errors: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    errors_stored: usize,
+    dropped_errors: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    skip_subtree: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::tai64n_option"))]
+    scanned_at: Option<Tai64N>,
+    elapsed: Duration,
+    root_meta: Option<FileMetadata<'a>>,
+    dir_entries: Vec<DirEntryMetadata>,
+    /// The number of files and sub-directories directly inside each directory read
+    dir_children: HashMap<PathBuf, (usize, usize)>,
+    #[cfg(feature = "rayon")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    parallel_walk: Option<SharedHook<ParallelWalk>>,
 }
 
 impl<'a> DirMetadata<'a> {
-    /// Create a new instance of [Self]
-    pub fn new(path: &'a str) -> Self {
-        let dir_name: PathBuf = path.into();
-        let dir_name = dir_name.file_name();
-
-        let name = match dir_name {
-            Some(name) => CowStr::Owned(name.to_string_lossy().to_string()),
-            None => path.into(),
-        };
+    /// Create a new instance of [Self] to scan the directory at `path`.
+    /// The name of the directory is borrowed from `path` when it ends with one,
+    /// use [Self::into_owned] to keep the scan longer than `path`.
+    /// Returns an error if `path` does not exist or is not a directory
+    pub fn new(path: &'a str) -> io::Result<Self> {
+        if !std::fs::metadata(path)?.is_dir() {
+            return Err(io::Error::new(
+                ErrorKind::NotADirectory,
+                format!("`{path}` is not a directory"),
+            ));
+        }
 
-        DirMetadata {
+        Ok(DirMetadata {
             path: path.into(),
-            name,
+            name: Path::new(path)
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map_or_else(|| Self::name_of(Path::new(path)), CowStr::Borrowed),
+            roots: vec![path.into()],
             ..Default::default()
-        }
+        })
     }
 
-    /// Returns an error if the directory cannot be accessed
-    /// Read all the directories and files in the given path
-    pub async fn dir_metadata(mut self) -> Result<DirMetadata<'a>, io::Error> {
-        let mut dir = read_dir(&self.path).await?;
+    /// Create a new instance of [Self] that scans multiple directories into one result.
+    /// Duplicate directories and directories nested inside another one of the `paths`
+    /// are removed so that their files are not counted twice.
+    /// [Self::dir_name] and [Self::dir_path] refer to the first of the remaining directories
+    /// which are sorted by path and can be listed using [Self::roots]
+    pub fn new_multi(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut candidates = paths
+            .into_iter()
+            .map(|path| std::fs::canonicalize(&path).unwrap_or(path))
+            .collect::<Vec<PathBuf>>();
+        candidates.sort();
+
+        let mut roots = Vec::<PathBuf>::new();
+        candidates.into_iter().for_each(|candidate| {
+            if !roots.iter().any(|root| candidate.starts_with(root)) {
+                roots.push(candidate);
+            }
+        });
 
-        self.iter_dir(&mut dir).await;
+        let path = roots.first().cloned().unwrap_or_default();
 
-        Ok(self)
+        DirMetadata {
+            name: Self::name_of(&path),
+            path,
+            roots,
+            ..Default::default()
+        }
     }
 
-    /// Recursively iterate over directories inside directories
-    #[async_recursion]
-    pub async fn iter_dir(&mut self, prepared_dir: &mut ReadDir) -> &mut Self {
-        let mut directories = Vec::<PathBuf>::new();
+    /// Convert the scan to one that does not borrow the path it was created from,
+    /// eg to move it to another thread or keep it after the path is dropped
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// let path = String::from("src");
+    /// let outcome = smol::block_on(DirMetadata::new(&path).unwrap().dir_metadata()).unwrap();
+    /// let outcome = outcome.into_owned();
+    /// drop(path);
+    ///
+    /// std::thread::spawn(move || dbg!(outcome.size()))
+    ///     .join()
+    ///     .unwrap();
+    /// ```
+    pub fn into_owned(self) -> DirMetadata<'static> {
+        DirMetadata {
+            name: Cow::Owned(self.name.into_owned()),
+            path: self.path,
+            directories: self.directories,
+            files: self
+                .files
+                .into_iter()
+                .map(FileMetadata::into_owned)
+                .collect(),
+            size: self.size,
+            errors: self.errors.into_iter().map(DirError::into_owned).collect(),
+            max_depth: self.max_depth,
+            follow_symlinks: self.follow_symlinks,
+            visited: self.visited,
+            include_globs: self.include_globs,
+            exclude_globs: self.exclude_globs,
+            extensions: self.extensions,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            skip_dir_names: self.skip_dir_names,
+            skipped: self.skipped,
+            #[cfg(feature = "time")]
+            modified_after: self.modified_after,
+            filter_hook: self.filter_hook,
+            #[cfg(feature = "gitignore")]
+            respect_gitignore: self.respect_gitignore,
+            cancel_handle: self.cancel_handle,
+            cancelled: self.cancelled,
+            progress_hook: self.progress_hook,
+            entries_seen: self.entries_seen,
+            directories_seen: self.directories_seen,
+            roots: self.roots,
+            same_filesystem: self.same_filesystem,
+            stream_sender: self.stream_sender,
+            concurrency: self.concurrency,
+            skip_format_detection: self.skip_format_detection,
+            format_detection_limit: self.format_detection_limit,
+            defer_format_detection: self.defer_format_detection,
+            previous_formats: self.previous_formats,
+            disk_usage: self.disk_usage,
+            #[cfg(feature = "unix")]
+            size_deduped: self.size_deduped,
+            #[cfg(feature = "unix")]
+            hard_links: self.hard_links,
+            max_depth_seen: self.max_depth_seen,
+            dir_sizes: self.dir_sizes,
+            dir_file_counts: self.dir_file_counts,
+            #[cfg(feature = "hash")]
+            hash_algo: self.hash_algo,
+            path_index: self.path_index,
+            sorted: self.sorted,
+            traversal_order: self.traversal_order,
+            strict: self.strict,
+            error_hook: self.error_hook,
+            max_errors: self.max_errors,
+            errors_stored: self.errors_stored,
+            dropped_errors: self.dropped_errors,
+            skip_subtree: self.skip_subtree,
+            scanned_at: self.scanned_at,
+            elapsed: self.elapsed,
+            root_meta: self.root_meta.map(FileMetadata::into_owned),
+            dir_entries: self.dir_entries,
+            dir_children: self.dir_children,
+            #[cfg(feature = "rayon")]
+            parallel_walk: self.parallel_walk,
+        }
+    }
 
-        while let Some(entry_result) = prepared_dir.next().await {
-            match entry_result {
-                Err(error) => {
-                    self.errors.push(DirError {
-                        path: self.path.clone(),
-                        error: error.kind(),
-                        display: error.to_string().into(),
-                    });
-                }
-                Ok(entry) => {
-                    let mut is_dir = false;
+    /// The name of the directory at `path`. Paths that do not end with a name like `.`
+    /// or `..` are resolved first, the root of the filesystem is named after its path
+    fn name_of(path: &Path) -> CowStr<'a> {
+        let canonical = path
+            .file_name()
+            .is_none()
+            .then(|| std::fs::canonicalize(path).ok())
+            .flatten();
+        let path = canonical.as_deref().unwrap_or(path);
 
-                    match entry.file_type().await {
-                        Ok(file_type) => is_dir = file_type.is_dir(),
-                        Err(error) => {
-                            let inner_path = entry.path();
+        match path.file_name() {
+            Some(name) => CowStr::Owned(name.to_string_lossy().into_owned()),
+            None => CowStr::Owned(path.to_string_lossy().into_owned()),
+        }
+    }
 
-                            self.errors.push(DirError {
-                                path: inner_path.clone(),
-                                error: error.kind(),
-                                display: Cow::Owned(format!(
-                                    "Unable to check if `{}` is a directory",
-                                    inner_path.display()
-                                )),
-                            });
-                        }
-                    }
+    /// Limit how deep the traversal descends into sub-directories.
+    /// A depth of `0` only reads the files in the root directory.
+    /// Directories beyond the limit are still listed in [Self::directories]
+    /// but their contents are not read.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth.replace(depth);
 
-                    if is_dir {
-                        directories.push(entry.path())
-                    } else {
-                        let mut file_meta = FileMetadata::default();
+        self
+    }
 
-                        let cloned_path = entry.path().clone();
-                        let get_file_format = unblock(move || FileFormat::from_file(cloned_path));
-                        let format = match get_file_format.await {
-                            Ok(format_detected) => format_detected,
-                            Err(_) => FileFormat::default(),
-                        };
-                        file_meta.file_format = format;
+    /// Only read the immediate contents of the directory, like `ls`.
+    /// Sub-directories are listed in [Self::directories] but are not descended into.
+    /// This is the same as calling [Self::max_depth] with a depth of `0`
+    pub fn shallow(self) -> Self {
+        self.max_depth(0)
+    }
 
-                        file_meta.name =
-                            CowStr::Owned(entry.file_name().to_string_lossy().to_string());
-                        file_meta.path = entry.path();
-                        match entry.metadata().await {
-                            Ok(meta) => {
-                                let current_file_size = meta.len() as usize;
-                                self.size += current_file_size;
-                                file_meta.size = current_file_size;
-                                file_meta.accessed = FsUtils::maybe_time(meta.accessed().ok());
-                                file_meta.modified = FsUtils::maybe_time(meta.modified().ok());
-                                file_meta.created = FsUtils::maybe_time(meta.created().ok());
-                            }
-                            Err(error) => {
-                                self.errors.push(DirError {
-                                    path: entry.path(),
-                                    error: error.kind(),
-                                    display: Cow::Owned(format!(
-                                        "Unable to access metadata of file `{}`",
-                                        entry.path().display()
-                                    )),
-                                });
-                            }
-                        }
+    /// Do not descend into directories on a different filesystem than the directory being scanned,
+    /// like `du -x`. Such directories are recorded in [Self::skipped].
+    ///
+    /// This relies on device ids which are only available on unix platforms,
+    /// on other platforms this option has no effect
+    pub fn same_filesystem(mut self, same_filesystem: bool) -> Self {
+        self.same_filesystem = same_filesystem;
 
-                        self.files.push(file_meta);
-                    }
-                }
-            }
-        }
+        self
+    }
 
-        let mut dir_iter = smol::stream::iter(&directories);
+    /// Look up the metadata of up to `limit` entries of a directory and detect the file format
+    /// of up to `limit` files at the same time on the blocking thread pool instead of one
+    /// after the other. Entries are still recorded in the order they are read from the directory
+    /// so the result does not depend on the limit. Defaults to `1`
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
 
-        while let Some(path) = dir_iter.next().await {
-            match read_dir(path.clone()).await {
-                Ok(mut prepared_dir) => {
-                    self.iter_dir(&mut prepared_dir).await;
-                }
-                Err(error) => self.errors.push(DirError {
-                    path: path.to_owned(),
-                    error: error.kind(),
-                    display: Cow::Owned(format!(
-                        "Unable to access metadata of file `{}`",
-                        path.display()
-                    )),
-                }),
-            }
-        }
+        self
+    }
 
-        self.directories.extend_from_slice(&directories);
+    /// Enable or disable detecting the format of files, when disabled files are never opened
+    /// and [FileMetadata::file_format] returns the default [FileFormat]. Defaults to `true`
+    pub fn detect_formats(mut self, detect: bool) -> Self {
+        self.skip_format_detection = !detect;
 
         self
     }
 
-    /// Get the name of the current directory
-    pub fn dir_name(&self) -> &str {
-        self.name.as_ref()
-    }
+    /// Only detect the format of files whose size in bytes is at most `bytes`,
+    /// larger files are not opened and get the default [FileFormat]
+    pub fn format_detection_limit(mut self, bytes: u64) -> Self {
+        self.format_detection_limit.replace(bytes);
 
-    /// Get the path of the current directory
-    pub fn dir_path(&self) -> &Path {
-        self.path.as_ref()
+        self
     }
 
-    /// Get all the sub-directories of the current directory
-    pub fn directories(&self) -> &[PathBuf] {
-        self.directories.as_ref()
-    }
+    /// Do not detect the format of files while scanning, instead the format is detected
+    /// the first time [FileMetadata::file_format] or [FileMetadata::file_format_async] is called
+    /// and remembered afterwards. Useful to scan quickly and only probe the files that are needed
+    pub fn defer_format_detection(mut self) -> Self {
+        self.defer_format_detection = true;
 
-    /// Get all the files in the current directory and all the files in it's sub-directory
-    pub fn files(&self) -> &[FileMetadata<'a>] {
-        self.files.as_ref()
+        self
     }
 
-    /// Get the size of the directory including the  size of all files in the sub-directories
-    pub fn size(&self) -> usize {
-        self.size
-    }
+    /// Compute the digest of the content of every regular file while scanning using `hash_algo`,
+    /// the content is read in chunks on the blocking thread pool and at most
+    /// [Self::concurrency] files are read at the same time
+    #[cfg(feature = "hash")]
+    pub fn hash_files(mut self, hash_algo: HashAlgo) -> Self {
+        self.hash_algo.replace(hash_algo);
 
-    /// Get the size of the directory including the  size of all files in the sub-directories in human readable format
-    pub fn size_formatted(&self) -> String {
-        FsUtils::size_to_bytes(self.size)
+        self
     }
 
-    /// Get all the errors encountered while opening the sub-directories and files
-    pub fn errors(&self) -> &[DirError<'a>] {
-        self.errors.as_ref()
+    /// Sort the files, directories and errors by path once the scan completes so that
+    /// scanning the same tree twice gives the same order. Without sorting the order
+    /// depends on the order the operating system lists the entries of a directory in.
+    /// Sorting does not apply to [Self::stream_files], [Self::walk] and [Self::scan_to]
+    /// which yield the files as they are found
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+
+        self
     }
-}
 
-/// The file metadata like file name, file type, file size, file path etc
-#[derive(Debug, PartialEq, Eq, Default, Clone)]
-pub struct FileMetadata<'a> {
-    name: CowStr<'a>,
-    path: PathBuf,
-    size: usize,
-    read_only: bool,
-    created: Option<Tai64N>,
-    accessed: Option<Tai64N>,
-    modified: Option<Tai64N>,
-    symlink: bool,
-    file_format: FileFormat,
-}
+    /// Choose the order the directories are read in, see [TraversalOrder].
+    /// The order of [Self::files] and of the files yielded by [Self::stream_files],
+    /// [Self::walk] and [Self::scan_to] follows it unless [Self::sorted] is enabled.
+    /// Defaults to [TraversalOrder::DepthFirst]
+    pub fn traversal_order(mut self, order: TraversalOrder) -> Self {
+        self.traversal_order = order;
 
-impl<'a> FileMetadata<'a> {
-    /// Get the name of the file
-    pub fn name(&self) -> &str {
-        self.name.as_ref()
+        self
     }
 
-    /// Get the path of the file
-    pub fn path(&self) -> &Path {
-        self.path.as_ref()
-    }
+    /// Stop the scan on the first file or directory that cannot be read and return
+    /// the error instead of the partial scan. The returned [io::Error] has the kind of
+    /// the [DirError] which can be read back with [io::Error::get_ref]
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{DirError, DirMetadata};
+    ///
+    /// match smol::block_on(DirMetadata::new("src").unwrap().strict().dir_metadata()) {
+    ///     Ok(outcome) => assert!(outcome.errors().is_empty()),
+    ///     Err(error) => {
+    ///         let dir_error = error
+    ///             .get_ref()
+    ///             .and_then(|error| error.downcast_ref::<DirError>());
+    ///         dbg!(dir_error);
+    ///     }
+    /// }
+    /// ```
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
 
-    /// Get the size of the file
-    pub fn size(&self) -> usize {
-        self.size
+        self
     }
 
-    /// Get the size of the file in human readable format
-    pub fn formatted_size(&self) -> String {
-        FsUtils::size_to_bytes(self.size)
+    /// Reuse the file formats detected by a `previous` scan of the same tree.
+    /// A file whose path, size and modification time are unchanged is not opened again,
+    /// every other file is detected as in a fresh scan
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let previous = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///     let current = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .rescan(&previous)
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     assert_eq!(previous.files().len(), current.files().len());
+    /// });
+    /// ```
+    pub fn rescan(mut self, previous: &DirMetadata) -> Self {
+        self.previous_formats = previous
+            .files
+            .iter()
+            .filter_map(|file| {
+                let modified = file.modified?;
+                let file_format = file.file_format.get()?;
+
+                Some((
+                    file.path().into_owned(),
+                    (file.size, modified, *file_format),
+                ))
+            })
+            .collect();
+
+        self
     }
 
-    /// Get the TAI64N timestamp when the file was last accessed
-    pub fn accessed(&self) -> Option<Tai64N> {
-        self.accessed
+    /// Descend into symlinked directories instead of treating them as files.
+    /// Every directory visited is recorded by its device and inode so that a symlink
+    /// pointing back to an already visited directory is reported as a [DirError]
+    /// instead of being followed forever. Defaults to `false`
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+
+        self
     }
 
-    /// Get the TAI64N timestamp when the file was last modified
-    pub fn modified(&self) -> Option<Tai64N> {
-        self.modified
+    /// Only record files whose path relative to the directory being read matches the glob pattern.
+    /// Can be called multiple times, a file is recorded if it matches any of the patterns.
+    /// Directories are still descended into so that nested files can be matched.
+    /// Returns an error if the pattern is invalid
+    pub fn include_glob(mut self, pattern: &str) -> Result<Self, globset::Error> {
+        self.include_globs.add(pattern)?;
+
+        Ok(self)
     }
 
-    /// Get the TAI64N timestamp when the file was last created
-    pub fn created(&self) -> Option<Tai64N> {
-        self.created
+    /// Skip files and directories whose path relative to the directory being read matches the glob pattern.
+    /// Can be called multiple times. Excluded directories are not descended into.
+    /// Returns an error if the pattern is invalid
+    pub fn exclude_glob(mut self, pattern: &str) -> Result<Self, globset::Error> {
+        self.exclude_globs.add(pattern)?;
+
+        Ok(self)
     }
 
-    /// Get the timestamp in local time in 24 hour format when the file was last accessed
-    #[cfg(feature = "time")]
-    pub fn accessed_24hr(&self) -> Option<DateTimeString<'a>> {
-        Some(FsUtils::tai64_to_local_hrs(&self.accessed?))
+    /// Only record files whose extension matches one of the `extensions`, ignoring ASCII case.
+    /// Other files are skipped before their file format is detected and files without
+    /// an extension are skipped
+    pub fn filter_extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions.extend(
+            extensions
+                .iter()
+                .map(|extension| extension.strip_prefix('.').unwrap_or(extension).to_owned()),
+        );
+
+        self
     }
 
-    /// Get the timestamp in local time in 12 hour format when the file was last accessed
-    #[cfg(feature = "time")]
-    pub fn accessed_am_pm(&self) -> Option<DateTimeString<'a>> {
-        Some(FsUtils::tai64_to_local_am_pm(&self.accessed?))
+    /// Only record files whose size in bytes is at least `bytes`.
+    /// Skipped files still count towards the total [Self::size] of the directory.
+    /// Files whose metadata cannot be read are always recorded
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size.replace(bytes);
+
+        self
     }
 
-    /// Get the time passed since access of a file eg `3 sec ago`
-    #[cfg(feature = "time")]
-    pub fn accessed_humatime(&self) -> Option<String> {
-        FsUtils::tai64_now_duration_to_humantime(&self.accessed?)
+    /// Only record files whose size in bytes is at most `bytes`.
+    /// Skipped files still count towards the total [Self::size] of the directory.
+    /// Files whose metadata cannot be read are always recorded
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size.replace(bytes);
+
+        self
     }
 
-    /// Get the timestamp in local time in 24 hour format when the file was last modified
-    #[cfg(feature = "time")]
-    pub fn modified_24hr(&self) -> Option<DateTimeString<'a>> {
-        Some(FsUtils::tai64_to_local_hrs(&self.modified?))
+    /// Do not descend into directories whose name matches any of the `names` at any depth,
+    /// like `target`, `node_modules` or `.git`.
+    /// The skipped directories are recorded in [Self::skipped] instead of [Self::directories]
+    pub fn skip_dir_names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.skip_dir_names
+            .extend(names.into_iter().map(Into::into));
+
+        self
     }
 
-    /// Get the timestamp in local time in 12 hour format when the file was last modified
+    /// Only record files last modified after the TAI64N timestamp `time`.
+    /// Like with [Self::min_size], skipped files still count towards [Self::size],
+    /// [Self::dir_size] and the number of files of each directory so those describe the
+    /// whole directory rather than the sum of [Self::files].
+    /// Files whose modification time cannot be read are recorded along with a [DirError]
     #[cfg(feature = "time")]
-    pub fn modified_am_pm(&self) -> Option<DateTimeString<'a>> {
-        Some(FsUtils::tai64_to_local_am_pm(&self.modified?))
+    pub fn modified_after(mut self, time: Tai64N) -> Self {
+        self.modified_after.replace(time);
+
+        self
     }
 
-    /// Get the time passed since modification of a file eg `3 sec ago`
-    #[cfg(feature = "time")]
-    pub fn modified_humatime(&self) -> Option<String> {
-        FsUtils::tai64_now_duration_to_humantime(&self.modified?)
+    /// Decide whether an entry is recorded using a closure that receives the path
+    /// of the entry and whether it is a directory. Returning `false` for a file skips it
+    /// and returning `false` for a directory skips it and its whole subtree.
+    ///
+    /// The closure is only called for entries that passed [Self::exclude_glob],
+    /// [Self::include_glob], [Self::filter_extensions] and the ignore files,
+    /// and is called before [Self::skip_dir_names], the size filters
+    /// and the modification time filter are applied
+    pub fn filter_with<F>(mut self, filter: F) -> Self
+    where
+        F: FnMut(&Path, bool) -> bool + Send + 'static,
+    {
+        self.filter_hook
+            .replace(SharedHook(Arc::new(Mutex::new(filter))));
+
+        self
     }
 
-    /// Get the timestamp in local time in 24 hour format when the file was created
-    #[cfg(feature = "time")]
-    pub fn created_24hr(&self) -> Option<DateTimeString<'a>> {
-        Some(FsUtils::tai64_to_local_hrs(&self.created?))
+    /// Skip files and directories matched by the `.gitignore` file of each directory
+    /// and by `.git/info/exclude` of a repository root, the same way `git` does.
+    /// Ignore files in sub-directories apply to their subtree and take precedence
+    /// over the ignore files of parent directories
+    #[cfg(feature = "gitignore")]
+    pub fn respect_gitignore(mut self, respect: bool) -> Self {
+        self.respect_gitignore = respect;
+
+        self
     }
 
-    /// Get the timestamp in local time in 12 hour format when the file was created
-    #[cfg(feature = "time")]
-    pub fn created_am_pm(&self) -> Option<DateTimeString<'a>> {
-        Some(FsUtils::tai64_to_local_am_pm(&self.created?))
+    /// Call `progress` every `every_n_entries` entries read with the progress of the scan so far,
+    /// useful for showing a live counter while a large directory is scanned.
+    /// An interval of `0` is treated as `1`
+    pub fn on_progress<F>(mut self, every_n_entries: usize, progress: F) -> Self
+    where
+        F: FnMut(&ScanProgress) + Send + 'static,
+    {
+        self.progress_hook.replace((
+            every_n_entries.max(1),
+            SharedHook(Arc::new(Mutex::new(progress))),
+        ));
+
+        self
     }
 
-    /// Get the time passed since file was created of a file eg `3 sec ago`
-    #[cfg(feature = "time")]
-    pub fn created_humatime(&self) -> Option<String> {
-        FsUtils::tai64_now_duration_to_humantime(&self.created?)
+    /// Call `on_error` with each error found while scanning, the returned [ErrorAction]
+    /// decides if the scan continues, skips the directory the error was found in or stops.
+    /// The error is recorded like any other error whatever the action
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{DirMetadata, ErrorAction};
+    ///
+    /// let outcome = smol::block_on(
+    ///     DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .on_error(|error| {
+    ///             eprintln!("{error}");
+    ///
+    ///             ErrorAction::Abort
+    ///         })
+    ///         .dir_metadata(),
+    /// )
+    /// .unwrap();
+    /// assert!(outcome.errors().len() <= 1);
+    /// ```
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: FnMut(&DirError) -> ErrorAction + Send + 'static,
+    {
+        self.error_hook
+            .replace(SharedHook(Arc::new(Mutex::new(on_error))));
+
+        self
     }
 
-    /// Is the file read only
-    pub fn read_only(&self) -> bool {
-        self.read_only
+    /// Keep at most `max_errors` errors, the errors found after that are still passed
+    /// to [Self::on_error] and counted by [Self::dropped_error_count] but not stored.
+    /// Useful to bound the memory used when scanning a failing mount.
+    /// A [Self::strict] scan always keeps the error it returns
+    pub fn max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors.replace(max_errors);
+
+        self
     }
 
-    /// Is the file a symbolic link
-    pub fn symlink(&self) -> bool {
-        self.symlink
+    /// Make the scan cancellable, returning the scan and a [CancelHandle].
+    /// Calling [CancelHandle::cancel] stops the scan before the next entry or directory is read
+    /// and the scan returns whatever was collected so far with [Self::was_cancelled] set to `true`
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// let (scan, cancel) = DirMetadata::new("src").unwrap().cancellable();
+    /// cancel.cancel();
+    ///
+    /// let outcome = smol::block_on(scan.dir_metadata()).unwrap();
+    /// assert!(outcome.was_cancelled());
+    /// ```
+    pub fn cancellable(mut self) -> (Self, CancelHandle) {
+        let cancel_handle = CancelHandle::default();
+        self.cancel_handle.replace(cancel_handle.clone());
+
+        (self, cancel_handle)
     }
 
-    /// Get the format of the current file
-    pub fn file_format(&self) -> &FileFormat {
-        &self.file_format
+    /// Returns an error if the directory cannot be accessed
+    /// Read all the directories and files in the given path.
+    /// A root that is a symlink to a directory is always read, [Self::follow_symlinks]
+    /// only applies to the symlinks found inside the roots
+    pub async fn dir_metadata(mut self) -> Result<DirMetadata<'a>, io::Error> {
+        let started = Instant::now();
+        self.scanned_at.replace(Tai64N::now());
+        self.root_meta = self.read_root_meta().await;
+
+        for root in self.roots.clone() {
+            let mut dir = read_dir(&root).await?;
+
+            if self.follow_symlinks {
+                let root_identity = Self::dir_identity(&root).await?;
+                self.visited.insert(root_identity);
+            }
+
+            self.iter_root(&mut dir, root).await;
+
+            if let Some(error) = self.errors.first().filter(|_| self.strict) {
+                return Err(io::Error::new(error.error, error.clone().into_owned()));
+            }
+        }
+
+        self.fill_dir_entries();
+        self.sort_entries();
+        self.elapsed = started.elapsed();
+
+        Ok(self)
     }
-}
 
-/// An error encountered while accessing a file or sub-directory
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct DirError<'a> {
-    /// The path to the sub-directory or file where the error occurred
-    pub path: PathBuf,
-    /// The kind of error that occurred based on [std::io::ErrorKind]
-    pub error: ErrorKind,
-    /// The formatted error as a [String]
-    pub display: CowStr<'a>,
+    /// Read all the directories and files in the given path blocking the current thread.
+    /// The directories are read in parallel on the rayon thread pool, each thread collecting
+    /// the files and errors of the directories it reads until they are merged at the end,
+    /// then the file formats of all the files are detected in parallel.
+    /// The outcome is the same as [Self::dir_metadata] except for the order of the entries
+    /// when the scan is not [Self::sorted] and the traversal order does not apply.
+    /// The counts passed to [Self::on_progress] are those of the thread reporting them.
+    /// Returns an error if the directory cannot be accessed
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// let outcome = DirMetadata::new("src").unwrap().par_dir_metadata().unwrap();
+    /// dbg!(outcome.size());
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_dir_metadata(mut self) -> Result<DirMetadata<'a>, io::Error> {
+        let started = Instant::now();
+        self.scanned_at.replace(Tai64N::now());
+        self.root_meta = smol::block_on(self.read_root_meta());
+
+        let walk = SharedHook(Arc::new(Mutex::new(ParallelWalk::default())));
+        // The formats are detected once all the directories have been read
+        let workers = (0..rayon::current_num_threads())
+            .map(|_| {
+                Mutex::new(DirMetadata {
+                    defer_format_detection: true,
+                    parallel_walk: Some(walk.clone()),
+                    ..self.clone()
+                })
+            })
+            .collect::<Vec<Mutex<DirMetadata<'a>>>>();
+        let stop = AtomicBool::new(false);
+
+        for root in self.roots.clone() {
+            let prepared_dir = smol::block_on(read_dir(&root))?;
+
+            if self.follow_symlinks {
+                let root_identity = smol::block_on(Self::dir_identity(&root))?;
+                walk.lock().visited.insert(root_identity);
+            }
+
+            if let Some(level) = smol::block_on(self.root_level(root)) {
+                rayon::scope(|scope| {
+                    Self::par_read_level(scope, &workers, &stop, level, Some(prepared_dir))
+                });
+            }
+
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+
+        for worker in workers {
+            self.absorb_worker(
+                worker
+                    .into_inner()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            );
+        }
+        let walk = std::mem::take(&mut *walk.lock());
+        self.visited = walk.visited;
+        #[cfg(feature = "unix")]
+        {
+            self.hard_links = walk.hard_links;
+        }
+
+        for root in self.roots.clone() {
+            self.roll_up_dir_sizes(&root);
+        }
+
+        if let Some(error) = self.errors.first().filter(|_| self.strict) {
+            return Err(io::Error::new(error.error, error.clone().into_owned()));
+        }
+
+        if !self.defer_format_detection {
+            self.files.par_iter().for_each(|file| {
+                file.file_format();
+            });
+        }
+
+        self.fill_dir_entries();
+        self.sort_entries();
+        self.elapsed = started.elapsed();
+
+        Ok(self)
+    }
+
+    /// Reads a directory on the current thread of the rayon pool with the worker of that thread,
+    /// then reads each of its sub-directories on whichever thread is free
+    #[cfg(feature = "rayon")]
+    fn par_read_level<'s>(
+        scope: &rayon::Scope<'s>,
+        workers: &'s [Mutex<DirMetadata<'a>>],
+        stop: &'s AtomicBool,
+        level: DirLevel,
+        prepared_dir: Option<ReadDir>,
+    ) {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let index = rayon::current_thread_index().unwrap_or_default() % workers.len();
+        let mut worker = workers[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let children = smol::block_on(async {
+            let mut prepared_dir = match prepared_dir {
+                Some(prepared_dir) => prepared_dir,
+                None => match read_dir(&level.path).await {
+                    Ok(prepared_dir) => prepared_dir,
+                    Err(error) => {
+                        worker.record_error(Self::unreadable_dir(level.path.clone(), error));
+
+                        return Vec::new();
+                    }
+                },
+            };
+
+            worker.read_level(&mut prepared_dir, &level).await
+        });
+
+        // An aborted, cancelled or failed strict scan stops the other threads too
+        if worker.check_cancelled() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        drop(worker);
+
+        for child in children {
+            scope.spawn(move |scope| Self::par_read_level(scope, workers, stop, child, None));
+        }
+    }
+
+    /// Adds what a thread of [Self::par_dir_metadata] collected to this scan, the errors
+    /// above [Self::max_errors] are dropped once all the threads are merged
+    #[cfg(feature = "rayon")]
+    fn absorb_worker(&mut self, worker: DirMetadata<'a>) {
+        self.size += worker.size;
+        self.disk_usage += worker.disk_usage;
+        #[cfg(feature = "unix")]
+        {
+            self.size_deduped += worker.size_deduped;
+        }
+        self.max_depth_seen = self.max_depth_seen.max(worker.max_depth_seen);
+        self.entries_seen += worker.entries_seen;
+        self.directories_seen += worker.directories_seen;
+        self.cancelled |= worker.cancelled;
+
+        self.files.extend(worker.files);
+        self.directories.extend(worker.directories);
+        self.dir_entries.extend(worker.dir_entries);
+        self.dir_children.extend(worker.dir_children);
+        self.dir_sizes.extend(worker.dir_sizes);
+        self.dir_file_counts.extend(worker.dir_file_counts);
+        self.skipped.extend(worker.skipped);
+        self.errors.extend(worker.errors);
+        self.dropped_errors += worker.dropped_errors;
+        self.path_index.invalidate();
+
+        let limit = self
+            .max_errors
+            .map(|max_errors| max_errors.max(self.strict as usize));
+        if let Some(limit) = limit.filter(|limit| self.errors.len() > *limit) {
+            self.dropped_errors += self.errors.len() - limit;
+            self.errors.truncate(limit);
+        }
+        self.errors_stored = self.errors.len();
+    }
+
+    /// Read all the directories and files in the given path, yielding each file
+    /// and each error as soon as it is found instead of collecting them.
+    /// The scan only makes progress while the stream is polled
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{smol::stream::StreamExt, DirMetadata};
+    ///
+    /// smol::block_on(async {
+    ///     let mut files = DirMetadata::new("src").unwrap().stream_files();
+    ///
+    ///     while let Some(file) = files.next().await {
+    ///         dbg!(file.unwrap().name());
+    ///     }
+    /// });
+    /// ```
+    pub fn stream_files(mut self) -> FileStream<'a> {
+        let (sender, receiver) = channel::bounded::<StreamItem>(FileStream::CAPACITY);
+        let root_sender = sender.clone();
+        self.stream_sender.replace(StreamSender::Items(sender));
+
+        let scan = async move {
+            let root = self.path.clone();
+
+            if let Err(error) = self.dir_metadata().await {
+                let display = Cow::Owned(format!(
+                    "Unable to read the directory `{}`: {}",
+                    root.display(),
+                    error
+                ));
+                let root_error = DirError {
+                    path: root,
+                    error: error.kind(),
+                    os_error: error.raw_os_error(),
+                    display,
+                };
+                root_sender.send(Err(root_error)).await.ok();
+            }
+        };
+
+        FileStream {
+            scan: Some(Box::pin(scan)),
+            receiver,
+        }
+    }
+
+    /// Read all the directories and files in the given path, sending each file
+    /// through `sender` as soon as it is found. The directories, errors and size
+    /// are returned once the scan completes. If the receiver is dropped the scan stops
+    /// early and an error with the display [SENDER_CHANNEL_ERROR] is recorded
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{smol::channel, DirMetadata};
+    ///
+    /// smol::block_on(async {
+    ///     let (sender, receiver) = channel::unbounded();
+    ///     let scan = smol::spawn(DirMetadata::new("src").unwrap().scan_to(sender));
+    ///
+    ///     while let Ok(file) = receiver.recv().await {
+    ///         dbg!(file.name());
+    ///     }
+    ///
+    ///     dbg!(scan.await.unwrap().size());
+    /// });
+    /// ```
+    pub async fn scan_to(
+        mut self,
+        sender: Sender<FileMetadata<'static>>,
+    ) -> Result<Self, io::Error> {
+        self.stream_sender.replace(StreamSender::Files(sender));
+
+        let mut outcome = self.dir_metadata().await?;
+        outcome.stream_sender.take();
+
+        Ok(outcome)
+    }
+
+    /// Read all the directories and files in the given path lazily from synchronous code,
+    /// yielding each file and each error as soon as it is found.
+    /// The scan runs on the current thread while [Walk::next] is called
+    /// so stopping the iteration early also stops the scan
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// let first_rust_file = DirMetadata::new("src")
+    ///     .unwrap()
+    ///     .walk()
+    ///     .filter_map(Result::ok)
+    ///     .find(|file| file.name().ends_with(".rs"));
+    /// assert!(first_rust_file.is_some());
+    /// ```
+    pub fn walk(self) -> Walk<'a> {
+        Walk(Some(self.stream_files()))
+    }
+
+    /// Recursively iterate over directories inside directories
+    pub async fn iter_dir(&mut self, prepared_dir: &mut ReadDir) -> &mut Self {
+        self.iter_root(prepared_dir, self.path.clone()).await;
+        self.sort_entries();
+
+        self
+    }
+
+    /// Sorts the files, directories and errors by path once the whole tree has been read
+    fn sort_entries(&mut self) {
+        if !self.sorted {
+            return;
+        }
+
+        self.files.sort_by(|first, second| first.cmp_path(second));
+        self.directories.sort();
+        self.dir_entries
+            .sort_by(|first, second| first.path.cmp(&second.path));
+        self.skipped.sort();
+        self.errors
+            .sort_by(|first, second| first.path.cmp(&second.path));
+        self.path_index.invalidate();
+    }
+
+    async fn iter_root(&mut self, prepared_dir: &mut ReadDir, root: PathBuf) {
+        let Some(level) = self.root_level(root.clone()).await else {
+            return;
+        };
+
+        // Directories waiting to be read, see [Self::enqueue_levels]
+        let mut queue = VecDeque::<DirLevel>::new();
+        let children = self.read_level(prepared_dir, &level).await;
+        self.enqueue_levels(&mut queue, children);
+
+        while let Some(level) = queue.pop_front() {
+            if self.check_cancelled() {
+                break;
+            }
+
+            match read_dir(&level.path).await {
+                Ok(mut prepared_dir) => {
+                    let children = self.read_level(&mut prepared_dir, &level).await;
+                    self.enqueue_levels(&mut queue, children);
+                }
+                Err(error) => self.record_error(Self::unreadable_dir(level.path, error)),
+            }
+        }
+
+        self.roll_up_dir_sizes(&root);
+    }
+
+    /// The state of the root directory, [Option::None] if the [Self::on_error] hook skipped it
+    async fn root_level(&mut self, root: PathBuf) -> Option<DirLevel> {
+        let root_device = match self.same_filesystem {
+            true => Self::device_of(&root).await,
+            false => None,
+        };
+
+        #[allow(unused_mut)]
+        let mut level = DirLevel {
+            root: root.clone(),
+            path: root,
+            root_device,
+            ..Default::default()
+        };
+        self.skip_subtree = false;
+        #[cfg(feature = "gitignore")]
+        self.load_gitignore(&mut level).await;
+
+        (!self.skip_subtree).then_some(level)
+    }
+
+    /// The error recorded for a directory that cannot be read
+    fn unreadable_dir(path: PathBuf, error: io::Error) -> DirError<'a> {
+        let display = format!("Unable to access metadata of file `{}`", path.display());
+
+        DirError {
+            path,
+            error: error.kind(),
+            os_error: error.raw_os_error(),
+            display: Cow::Owned(display),
+        }
+    }
+
+    /// Adds the sub-directories of the directory just read to the directories waiting to be read.
+    /// They go to the front of the queue for a depth first traversal and to the back for a
+    /// breadth first traversal, keeping the order they were listed in
+    fn enqueue_levels(&self, queue: &mut VecDeque<DirLevel>, children: Vec<DirLevel>) {
+        match self.traversal_order {
+            TraversalOrder::DepthFirst => children
+                .into_iter()
+                .rev()
+                .for_each(|child| queue.push_front(child)),
+            TraversalOrder::BreadthFirst => queue.extend(children),
+        }
+    }
+
+    /// Sets the number of children of each directory that was read
+    fn fill_dir_entries(&mut self) {
+        for dir_entry in &mut self.dir_entries {
+            if let Some((file_count, dir_count)) = self.dir_children.get(&dir_entry.path) {
+                dir_entry.file_count = Some(*file_count);
+                dir_entry.dir_count = Some(*dir_count);
+            }
+        }
+    }
+
+    /// Adds the size and the number of files of each directory read inside `root` to those
+    /// of its ancestors, the deepest directories are added first so that they propagate all the way up
+    fn roll_up_dir_sizes(&mut self, root: &Path) {
+        let mut directories = self
+            .dir_sizes
+            .keys()
+            .filter(|path| path.as_path() != root && path.starts_with(root))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        directories.sort_by_key(|path| Reverse(path.components().count()));
+
+        // The totals are read again since a directory has been updated by its
+        // sub-directories by the time it is added to its own parent
+        for path in directories {
+            let Some(parent) = path.parent() else {
+                continue;
+            };
+
+            let size = self.dir_sizes.get(&path).copied().unwrap_or_default();
+            if let Some(parent_size) = self.dir_sizes.get_mut(parent) {
+                *parent_size += size;
+            }
+
+            let file_count = self.dir_file_counts.get(&path).copied().unwrap_or_default();
+            if let Some(parent_file_count) = self.dir_file_counts.get_mut(parent) {
+                *parent_file_count += file_count;
+            }
+        }
+    }
+
+    /// Reads the entries of a single directory, returning the sub-directories that should be read next
+    async fn read_level(&mut self, prepared_dir: &mut ReadDir, level: &DirLevel) -> Vec<DirLevel> {
+        let mut directories = Vec::<PathBuf>::new();
+        let mut dir_entries = Vec::<DirEntryMetadata>::new();
+        let mut pending_files = VecDeque::<PendingFile<'a>>::new();
+        let mut level_size = 0u64;
+        let mut level_file_count = 0usize;
+        let mut level_dir_count = 0usize;
+        // Shared by all the files of the directory, see [FileMetadata::parent]
+        let parent = Arc::new(level.path.clone());
+        self.max_depth_seen = self.max_depth_seen.max(level.depth);
+        self.skip_subtree = false;
+
+        // The metadata of the next entries is looked up while an entry is recorded
+        let mut lookups = VecDeque::<io::Result<Task<EntryLookup>>>::new();
+        let mut listed = false;
+
+        loop {
+            while !listed && lookups.len() < self.concurrency.max(1) {
+                match prepared_dir.next().await {
+                    Some(Ok(entry)) => {
+                        let entry_path = entry.path();
+                        lookups.push_back(Ok(unblock(move || EntryLookup::new(entry_path))));
+                    }
+                    Some(Err(error)) => lookups.push_back(Err(error)),
+                    None => listed = true,
+                }
+            }
+
+            let Some(lookup) = lookups.pop_front() else {
+                break;
+            };
+
+            self.flush_to_stream().await;
+
+            if self.check_cancelled() || self.skip_subtree {
+                break;
+            }
+
+            self.report_progress(&level.path);
+
+            match lookup {
+                Err(error) => {
+                    self.record_error(DirError {
+                        path: level.path.clone(),
+                        error: error.kind(),
+                        os_error: error.raw_os_error(),
+                        display: error.to_string().into(),
+                    });
+                }
+                Ok(lookup) => {
+                    // Built once, then borrowed until it moves into the recorded directory
+                    // or the probe of the file
+                    let EntryLookup {
+                        path: entry_path,
+                        metadata,
+                        target,
+                        link_target,
+                    } = lookup.await;
+                    let mut is_dir = false;
+                    let mut is_symlink = false;
+                    let mut kind = FileKind::Unknown;
+
+                    match metadata.as_ref() {
+                        Ok(meta) => {
+                            is_dir = meta.is_dir();
+                            is_symlink = meta.is_symlink();
+                            kind = meta.file_type().into();
+
+                            if self.follow_symlinks && is_symlink {
+                                is_dir = target.as_ref().is_some_and(|target| {
+                                    target.as_ref().is_ok_and(|meta| meta.is_dir())
+                                });
+                            }
+                        }
+                        Err(error) => {
+                            self.record_error(DirError {
+                                path: entry_path.clone(),
+                                error: error.kind(),
+                                os_error: error.raw_os_error(),
+                                display: Cow::Owned(format!(
+                                    "Unable to check if `{}` is a directory",
+                                    entry_path.display()
+                                )),
+                            });
+                        }
+                    }
+
+                    match is_dir {
+                        true => level_dir_count += 1,
+                        false => level_file_count += 1,
+                    }
+
+                    if self.is_filtered_out(&entry_path, is_dir, level)
+                        || !self.passes_filter_hook(&entry_path, is_dir)
+                    {
+                        continue;
+                    }
+
+                    if is_dir {
+                        let file_name = entry_path.file_name().unwrap_or_default();
+
+                        if self
+                            .skip_dir_names
+                            .iter()
+                            .any(|name| file_name == name.as_str())
+                            || Self::is_other_filesystem(&entry_path, level).await
+                        {
+                            self.skipped.push(entry_path);
+                        } else {
+                            // The times of a directory are the only reason to read its metadata
+                            #[cfg(feature = "time")]
+                            let meta = target.and_then(Result::ok);
+                            #[cfg(not(feature = "time"))]
+                            let meta = None;
+
+                            self.directories_seen += 1;
+                            dir_entries.push(DirEntryMetadata::new(
+                                &entry_path,
+                                level.depth,
+                                meta.as_ref(),
+                            ));
+                            directories.push(entry_path)
+                        }
+                    } else {
+                        let mut file_meta = FileMetadata {
+                            symlink: is_symlink,
+                            depth: level.depth,
+                            kind,
+                            ..FileMetadata::new(
+                                parent.clone(),
+                                entry_path.file_name().unwrap_or_default(),
+                            )
+                        };
+
+                        match metadata {
+                            Ok(meta) => {
+                                let current_file_size = meta.len();
+                                let current_disk_usage = Self::allocated_size(&meta);
+                                self.size += current_file_size;
+                                self.disk_usage += current_disk_usage;
+                                level_size += meta.len();
+                                #[cfg(feature = "unix")]
+                                self.add_deduped_size(&meta, current_file_size);
+
+                                if !self.size_in_range(current_file_size) {
+                                    continue;
+                                }
+
+                                Self::fill_file_meta(&mut file_meta, &meta);
+
+                                #[cfg(feature = "time")]
+                                if let Some(modified_after) = self.modified_after {
+                                    match meta.modified() {
+                                        Ok(modified) => {
+                                            if Tai64N::from_system_time(&modified) <= modified_after
+                                            {
+                                                continue;
+                                            }
+                                        }
+                                        Err(error) => {
+                                            self.record_error(DirError {
+                                                path: entry_path.clone(),
+                                                error: error.kind(),
+                                                os_error: error.raw_os_error(),
+                                                display: Cow::Owned(format!(
+                                                    "Unable to read the modification time of file `{}`",
+                                                    entry_path.display()
+                                                )),
+                                            });
+                                        }
+                                    }
+                                }
+                            }
+                            Err(error) => {
+                                self.record_error(DirError {
+                                    path: entry_path.clone(),
+                                    error: error.kind(),
+                                    os_error: error.raw_os_error(),
+                                    display: Cow::Owned(format!(
+                                        "Unable to access metadata of file `{}`",
+                                        entry_path.display()
+                                    )),
+                                });
+                            }
+                        }
+
+                        match link_target {
+                            Some(Ok(link_target)) => {
+                                file_meta.link_target = Some(link_target);
+                                file_meta.broken_link =
+                                    target.as_ref().is_none_or(|target| target.is_err());
+                            }
+                            Some(Err(error)) => {
+                                self.record_error(DirError {
+                                    path: entry_path.clone(),
+                                    error: error.kind(),
+                                    os_error: error.raw_os_error(),
+                                    display: Cow::Owned(format!(
+                                        "Unable to read the target of symbolic link `{}`",
+                                        entry_path.display()
+                                    )),
+                                });
+                            }
+                            None => (),
+                        }
+
+                        if pending_files.len() >= self.concurrency.max(1) {
+                            self.complete_oldest_file(&mut pending_files).await;
+                        }
+
+                        let mut detect_format = false;
+
+                        let is_regular = match file_meta.kind {
+                            FileKind::Regular => true,
+                            FileKind::Symlink => target.as_ref().is_some_and(|target| {
+                                target.as_ref().is_ok_and(|meta| meta.is_file())
+                            }),
+                            _ => false,
+                        };
+                        if !self.should_detect_format(&file_meta, is_regular) {
+                            file_meta.file_format = OnceLock::from(FileFormat::default());
+                        } else if let Some(file_format) = self.previous_format(&file_meta) {
+                            file_meta.file_format = OnceLock::from(file_format);
+                        } else {
+                            detect_format = !self.defer_format_detection;
+                        }
+
+                        #[cfg(feature = "hash")]
+                        let hash_algo = self.hash_algo.filter(|_| is_regular);
+                        #[cfg(not(feature = "hash"))]
+                        let hash_algo = Option::<()>::None;
+
+                        if detect_format || hash_algo.is_some() {
+                            let cloned_path = entry_path;
+                            let probe = unblock(move || FileProbe {
+                                file_format: detect_format
+                                    .then(|| FileFormat::from_file(&cloned_path)),
+                                #[cfg(feature = "hash")]
+                                digest: hash_algo
+                                    .map(|hash_algo| hash_algo.digest_file(&cloned_path)),
+                            });
+                            pending_files.push_back((file_meta, Some(probe)));
+                        } else {
+                            pending_files.push_back((file_meta, None));
+                        }
+                    }
+                }
+            }
+        }
+
+        while !pending_files.is_empty() {
+            self.complete_oldest_file(&mut pending_files).await;
+        }
+
+        self.directories.extend_from_slice(&directories);
+        self.dir_entries.extend(dir_entries);
+        self.dir_children
+            .insert(level.path.clone(), (level_file_count, level_dir_count));
+        self.dir_sizes.insert(level.path.clone(), level_size);
+        self.dir_file_counts
+            .insert(level.path.clone(), level_file_count);
+
+        let mut children = Vec::<DirLevel>::new();
+
+        if !self.skip_subtree
+            && self
+                .max_depth
+                .is_none_or(|max_depth| level.depth < max_depth)
+        {
+            for path in &directories {
+                // An error entering a sub-directory only skips that sub-directory
+                self.skip_subtree = false;
+
+                if self.check_cancelled() {
+                    break;
+                }
+
+                if self.follow_symlinks && !self.mark_visited(path).await {
+                    continue;
+                }
+
+                #[allow(unused_mut)]
+                let mut child_level = level.child(path);
+                #[cfg(feature = "gitignore")]
+                self.load_gitignore(&mut child_level).await;
+
+                if self.skip_subtree {
+                    continue;
+                }
+
+                children.push(child_level);
+            }
+        }
+
+        self.flush_to_stream().await;
+
+        children
+    }
+
+    /// Checks the path relative to the root against the include and exclude globs
+    /// and the ignore files loaded for the current directory
+    #[allow(unused_variables)]
+    fn is_filtered_out(&self, path: &Path, is_dir: bool, level: &DirLevel) -> bool {
+        let relative = path.strip_prefix(&level.root).unwrap_or(path);
+
+        if self.exclude_globs.set.is_match(relative) {
+            return true;
+        }
+
+        if !is_dir
+            && !self.include_globs.globs.is_empty()
+            && !self.include_globs.set.is_match(relative)
+        {
+            return true;
+        }
+
+        if !is_dir
+            && !self.extensions.is_empty()
+            && !self
+                .extensions
+                .iter()
+                .any(|extension| FsUtils::path_has_extension(path, extension))
+        {
+            return true;
+        }
+
+        #[cfg(feature = "gitignore")]
+        for gitignore in level.ignores.iter().rev() {
+            match gitignore.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => continue,
+            }
+        }
+
+        false
+    }
+
+    /// Sends the files and errors collected so far to the [FileStream] if the scan is streamed,
+    /// cancelling the scan if the stream has been dropped
+    async fn flush_to_stream(&mut self) {
+        let Some(stream_sender) = self.stream_sender.clone() else {
+            return;
+        };
+
+        self.path_index.invalidate();
+
+        let sent = match &stream_sender {
+            StreamSender::Items(sender) => {
+                let files = self.files.drain(..).map(|file| Ok(file.into_owned()));
+                let errors = self.errors.drain(..).map(|error| Err(error.into_owned()));
+
+                Self::send_all(sender, files.chain(errors)).await
+            }
+            StreamSender::Files(sender) => {
+                let files = self.files.drain(..).map(FileMetadata::into_owned);
+
+                Self::send_all(sender, files).await
+            }
+        };
+
+        if !sent {
+            self.cancelled = true;
+            self.stream_sender.take();
+
+            // Nobody is left to receive the errors of a stream, the errors
+            // of [DirMetadata::scan_to] are still returned with the outcome
+            if let StreamSender::Files(_) = stream_sender {
+                self.record_error(DirError {
+                    path: self.path.clone(),
+                    error: ErrorKind::BrokenPipe,
+                    os_error: None,
+                    display: Cow::Borrowed(SENDER_CHANNEL_ERROR),
+                });
+            }
+        }
+    }
+
+    /// Returns `false` once the receiving half of the channel has been dropped
+    async fn send_all<T>(sender: &Sender<T>, items: impl Iterator<Item = T>) -> bool {
+        for item in items.collect::<Vec<T>>() {
+            if sender.send(item).await.is_err() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Opening a FIFO or a device to read its content can block forever
+    /// so only regular files and symbolic links to them are opened
+    async fn is_regular_file(file_meta: &FileMetadata<'_>) -> bool {
+        match file_meta.kind {
+            FileKind::Regular => true,
+            FileKind::Symlink => smol::fs::metadata(file_meta.path())
+                .await
+                .is_ok_and(|meta| meta.is_file()),
+            _ => false,
+        }
+    }
+
+    /// Checks if the format of a file is detected, `is_regular` comes from [Self::is_regular_file]
+    fn should_detect_format(&self, file_meta: &FileMetadata, is_regular: bool) -> bool {
+        is_regular
+            && !self.skip_format_detection
+            && self
+                .format_detection_limit
+                .is_none_or(|limit| file_meta.size <= limit)
+    }
+
+    /// Sets the size, permissions and timestamps of a file from its metadata
+    fn fill_file_meta(file_meta: &mut FileMetadata, meta: &std::fs::Metadata) {
+        file_meta.size = meta.len();
+        file_meta.disk_usage = Self::allocated_size(meta);
+        file_meta.read_only = meta.permissions().readonly();
+
+        #[cfg(all(unix, feature = "unix"))]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            file_meta.mode.replace(meta.mode());
+            file_meta.uid.replace(meta.uid());
+            file_meta.gid.replace(meta.gid());
+            file_meta.inode.replace(meta.ino());
+            file_meta.device.replace(meta.dev());
+            file_meta.nlink.replace(meta.nlink());
+        }
+        file_meta.accessed = FsUtils::maybe_time(meta.accessed().ok());
+        file_meta.modified = FsUtils::maybe_time(meta.modified().ok());
+        file_meta.created = FsUtils::maybe_time(meta.created().ok());
+        #[cfg(feature = "time")]
+        {
+            file_meta.changed = Self::changed_time(meta);
+        }
+    }
+
+    /// The metadata of the directory at [Self::dir_path], of the directory it points to
+    /// if it is a symlink. Returns [Option::None] if it cannot be read
+    async fn read_root_meta(&self) -> Option<FileMetadata<'a>> {
+        let link_meta = smol::fs::symlink_metadata(&self.path).await.ok()?;
+        let is_symlink = link_meta.is_symlink();
+        let meta = match is_symlink {
+            true => smol::fs::metadata(&self.path).await.ok()?,
+            false => link_meta,
+        };
+
+        let mut root_meta = FileMetadata {
+            name: self.name.clone(),
+            extension_dot: None,
+            symlink: is_symlink,
+            kind: meta.file_type().into(),
+            ..FileMetadata::at_path(&self.path)
+        };
+        Self::fill_file_meta(&mut root_meta, &meta);
+
+        if is_symlink {
+            root_meta.link_target = smol::fs::read_link(&self.path).await.ok();
+        }
+
+        Some(root_meta)
+    }
+
+    /// Checks if the target of the symbolic link at `path` cannot be found on disk,
+    /// whether or not it is inside the scanned directories
+    async fn is_broken_target(path: &Path) -> bool {
+        smol::fs::metadata(path).await.is_err()
+    }
+
+    /// The space allocated on disk for a file, falling back to the apparent size
+    /// on platforms without block information
+    fn allocated_size(meta: &std::fs::Metadata) -> u64 {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            meta.blocks() * 512
+        }
+
+        #[cfg(not(unix))]
+        {
+            meta.len()
+        }
+    }
+
+    /// The time the inode of a file was last changed, only available on unix
+    #[cfg(feature = "time")]
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn changed_time(meta: &std::fs::Metadata) -> Option<Tai64N> {
+        #[cfg(unix)]
+        {
+            use std::{os::unix::fs::MetadataExt, time::UNIX_EPOCH};
+
+            let since_epoch = Duration::new(
+                meta.ctime().unsigned_abs(),
+                meta.ctime_nsec().clamp(0, 999_999_999) as u32,
+            );
+            let changed = if meta.ctime() < 0 {
+                UNIX_EPOCH.checked_sub(since_epoch)
+            } else {
+                UNIX_EPOCH.checked_add(since_epoch)
+            };
+
+            FsUtils::maybe_time(changed)
+        }
+
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Adds the size of a file to [Self::size_deduped] unless another
+    /// hard link to the same file was already counted
+    #[cfg(feature = "unix")]
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn add_deduped_size(&mut self, meta: &std::fs::Metadata, size: u64) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            // Only files with several links can be counted twice
+            if meta.nlink() > 1 {
+                let hard_link = (meta.dev(), meta.ino());
+
+                #[cfg(feature = "rayon")]
+                let first_link = match self.parallel_walk.as_ref() {
+                    Some(walk) => walk.lock().hard_links.insert(hard_link),
+                    None => self.hard_links.insert(hard_link),
+                };
+                #[cfg(not(feature = "rayon"))]
+                let first_link = self.hard_links.insert(hard_link);
+
+                if !first_link {
+                    return;
+                }
+            }
+        }
+
+        self.size_deduped += size;
+    }
+
+    /// The file format from the snapshot passed to [DirMetadata::rescan]
+    /// if the file is unchanged since that snapshot
+    fn previous_format(&self, file_meta: &FileMetadata) -> Option<FileFormat> {
+        let (size, modified, file_format) = self.previous_formats.get(file_meta.path().as_ref())?;
+
+        (*size == file_meta.size && Some(*modified) == file_meta.modified).then_some(*file_format)
+    }
+
+    /// Waits for the file format and the digest of the oldest file to be computed and records the file
+    async fn complete_oldest_file(&mut self, pending_files: &mut VecDeque<PendingFile<'a>>) {
+        if let Some((mut file_meta, probe)) = pending_files.pop_front() {
+            if let Some(probe) = probe {
+                let probe = probe.await;
+
+                if let Some(file_format) = probe.file_format {
+                    file_meta.file_format = OnceLock::from(file_format.unwrap_or_default());
+                }
+
+                #[cfg(feature = "hash")]
+                match probe.digest {
+                    Some(Ok(digest)) => file_meta.digest = Some(digest),
+                    Some(Err(error)) => self.record_error(DirError {
+                        path: file_meta.path().into_owned(),
+                        error: error.kind(),
+                        os_error: error.raw_os_error(),
+                        display: Cow::Owned(format!(
+                            "Unable to hash the content of file `{}`",
+                            file_meta.path().display()
+                        )),
+                    }),
+                    None => (),
+                }
+            }
+
+            self.files.push(file_meta);
+            self.path_index.invalidate();
+        }
+    }
+
+    /// Checks if the scan should stop, either because it was cancelled
+    /// or because an error was found in a [Self::strict] scan
+    fn check_cancelled(&mut self) -> bool {
+        if !self.cancelled {
+            self.cancelled = self
+                .cancel_handle
+                .as_ref()
+                .is_some_and(|cancel_handle| cancel_handle.is_cancelled());
+        }
+
+        self.cancelled || (self.strict && self.errors_stored > 0)
+    }
+
+    /// Passes the error to the [Self::on_error] hook and stores it
+    /// unless [Self::max_errors] errors have already been stored
+    fn record_error(&mut self, error: DirError<'a>) {
+        let action = match self.error_hook.as_ref() {
+            Some(error_hook) => (error_hook.lock())(&error),
+            None => ErrorAction::Continue,
+        };
+
+        let limit = self
+            .max_errors
+            .map(|max_errors| max_errors.max(self.strict as usize));
+        if limit.is_some_and(|limit| self.errors_stored >= limit) {
+            self.dropped_errors += 1;
+        } else {
+            self.errors_stored += 1;
+            self.errors.push(error);
+        }
+
+        match action {
+            ErrorAction::Continue => (),
+            ErrorAction::SkipSubtree => self.skip_subtree = true,
+            ErrorAction::Abort => self.cancelled = true,
+        }
+    }
+
+    fn report_progress(&mut self, current_path: &Path) {
+        self.entries_seen += 1;
+
+        if let Some((every_n_entries, progress_hook)) = self.progress_hook.as_ref() {
+            if self.entries_seen.is_multiple_of(*every_n_entries) {
+                (progress_hook.lock())(&ScanProgress {
+                    files_seen: self.files.len(),
+                    directories_seen: self.directories_seen,
+                    bytes: self.size,
+                    current_path,
+                });
+            }
+        }
+    }
+
+    fn passes_filter_hook(&self, path: &Path, is_dir: bool) -> bool {
+        match self.filter_hook.as_ref() {
+            Some(filter_hook) => (filter_hook.lock())(path, is_dir),
+            None => true,
+        }
+    }
+
+    fn size_in_range(&self, size: u64) -> bool {
+        self.min_size.is_none_or(|min_size| size >= min_size)
+            && self.max_size.is_none_or(|max_size| size <= max_size)
+    }
+
+    /// Reads the `.gitignore` and `.git/info/exclude` files of the directory
+    /// and adds them to the matchers applied to its entries
+    #[cfg(feature = "gitignore")]
+    async fn load_gitignore(&mut self, level: &mut DirLevel) {
+        if !self.respect_gitignore {
+            return;
+        }
+
+        let dir_path = level.path.clone();
+        let (gitignore, errors) = unblock(move || {
+            let mut builder = GitignoreBuilder::new(&dir_path);
+            let mut errors = Vec::<ignore::Error>::new();
+
+            [
+                dir_path.join(".gitignore"),
+                dir_path.join(".git").join("info").join("exclude"),
+            ]
+            .iter()
+            .filter(|ignore_file| ignore_file.is_file())
+            .for_each(|ignore_file| {
+                if let Some(error) = builder.add(ignore_file) {
+                    errors.push(error);
+                }
+            });
+
+            (builder.build(), errors)
+        })
+        .await;
+
+        let gitignore = match gitignore {
+            Ok(gitignore) => Some(gitignore),
+            Err(error) => {
+                self.record_error(Self::gitignore_error(&level.path, error));
+
+                None
+            }
+        };
+
+        errors.into_iter().for_each(|error| {
+            self.record_error(Self::gitignore_error(&level.path, error));
+        });
+
+        if let Some(gitignore) = gitignore.filter(|gitignore| !gitignore.is_empty()) {
+            level.ignores.push(Arc::new(gitignore));
+        }
+    }
+
+    #[cfg(feature = "gitignore")]
+    fn gitignore_error(path: &Path, error: ignore::Error) -> DirError<'a> {
+        DirError {
+            path: path.to_owned(),
+            error: error
+                .io_error()
+                .map(|io_error| io_error.kind())
+                .unwrap_or(ErrorKind::InvalidData),
+            os_error: error
+                .io_error()
+                .and_then(|io_error| io_error.raw_os_error()),
+            display: Cow::Owned(format!(
+                "Unable to read the ignore files of `{}`: {}",
+                path.display(),
+                error
+            )),
+        }
+    }
+
+    /// Checks if the directory is on a different device than the root of the scan
+    async fn is_other_filesystem(path: &Path, level: &DirLevel) -> bool {
+        match level.root_device {
+            Some(root_device) => Self::device_of(path)
+                .await
+                .is_some_and(|device| device != root_device),
+            None => false,
+        }
+    }
+
+    #[cfg(unix)]
+    async fn device_of(path: &Path) -> Option<u64> {
+        use std::os::unix::fs::MetadataExt;
+
+        smol::fs::metadata(path).await.ok().map(|meta| meta.dev())
+    }
+
+    #[cfg(not(unix))]
+    async fn device_of(_path: &Path) -> Option<u64> {
+        None
+    }
+
+    /// Records the directory as visited, returning `false` and recording a [DirError]
+    /// if it was already visited or cannot be identified
+    async fn mark_visited(&mut self, path: &Path) -> bool {
+        match Self::dir_identity(path).await {
+            Ok(identity) => {
+                if self.visit(identity) {
+                    true
+                } else {
+                    self.record_error(DirError {
+                        path: path.to_owned(),
+                        error: ErrorKind::Other,
+                        os_error: None,
+                        display: Cow::Owned(format!(
+                            "Directory `{}` was already visited, a symlink cycle was detected",
+                            path.display()
+                        )),
+                    });
+
+                    false
+                }
+            }
+            Err(error) => {
+                self.record_error(DirError {
+                    path: path.to_owned(),
+                    error: error.kind(),
+                    os_error: error.raw_os_error(),
+                    display: Cow::Owned(format!(
+                        "Unable to access metadata of directory `{}`",
+                        path.display()
+                    )),
+                });
+
+                false
+            }
+        }
+    }
+
+    /// Records a directory as visited, returning `false` if it already was
+    fn visit(&mut self, identity: DirIdentity) -> bool {
+        #[cfg(feature = "rayon")]
+        if let Some(walk) = self.parallel_walk.as_ref() {
+            return walk.lock().visited.insert(identity);
+        }
+
+        self.visited.insert(identity)
+    }
+
+    #[cfg(unix)]
+    async fn dir_identity(path: &Path) -> io::Result<DirIdentity> {
+        use std::os::unix::fs::MetadataExt;
+
+        let meta = smol::fs::metadata(path).await?;
+
+        Ok((meta.dev(), meta.ino()))
+    }
+
+    #[cfg(not(unix))]
+    async fn dir_identity(path: &Path) -> io::Result<DirIdentity> {
+        smol::fs::canonicalize(path).await
+    }
+
+    /// Get the name of the current directory
+    pub fn dir_name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    /// Get the path of the current directory
+    pub fn dir_path(&self) -> &Path {
+        self.path.as_ref()
+    }
+
+    /// Get the TAI64N timestamp when the directory at [Self::dir_path] was last modified,
+    /// recorded when the scan started
+    pub fn root_modified(&self) -> Option<Tai64N> {
+        self.root_meta.as_ref()?.modified
+    }
+
+    /// Get the TAI64N timestamp when the directory at [Self::dir_path] was created
+    pub fn root_created(&self) -> Option<Tai64N> {
+        self.root_meta.as_ref()?.created
+    }
+
+    /// The unix permission mode of the directory at [Self::dir_path] like [FileMetadata::mode].
+    /// Returns [Option::None] on platforms other than unix or before the scan
+    #[cfg(feature = "unix")]
+    pub fn root_permissions(&self) -> Option<u32> {
+        self.root_meta.as_ref()?.mode
+    }
+
+    /// Check if [Self::dir_path] is a symlink to a directory. The scan reads the directory
+    /// it points to whether or not [Self::follow_symlinks] is set, and the other
+    /// `root_` methods describe that directory
+    pub fn root_is_symlink(&self) -> bool {
+        self.root_meta
+            .as_ref()
+            .is_some_and(|root_meta| root_meta.symlink)
+    }
+
+    /// Get the directories that are scanned, this only contains [Self::dir_path]
+    /// unless [Self::new_multi] was used
+    pub fn roots(&self) -> &[PathBuf] {
+        self.roots.as_ref()
+    }
+
+    /// Get the directory in [Self::roots] that the `file` was found in
+    pub fn root_of(&self, file: &FileMetadata) -> Option<&Path> {
+        self.roots
+            .iter()
+            .find(|root| file.path().starts_with(root))
+            .map(|root| root.as_path())
+    }
+
+    /// Get all the sub-directories of the current directory
+    pub fn directories(&self) -> &[PathBuf] {
+        self.directories.as_ref()
+    }
+
+    /// Get the metadata of the sub-directories, in the same order as [Self::directories]
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     for directory in outcome.directory_entries() {
+    ///         dbg!(directory.name(), directory.file_count());
+    ///     }
+    /// });
+    /// ```
+    pub fn directory_entries(&self) -> &[DirEntryMetadata] {
+        self.dir_entries.as_ref()
+    }
+
+    /// Get the directories directly inside the directory at `path`,
+    /// which is either a path inside the scanned directory or relative to it
+    pub fn subdirs_of(&self, path: impl AsRef<Path>) -> Vec<&Path> {
+        let dir_path = self.path_in_root(path.as_ref());
+
+        self.directories
+            .iter()
+            .filter(|directory| directory.parent() == Some(dir_path.as_path()))
+            .map(|directory| directory.as_path())
+            .collect()
+    }
+
+    /// Get the files directly inside the directory at `path`, not those of its sub-directories.
+    /// The `path` is either a path inside the scanned directory or relative to it
+    pub fn files_in_dir(&self, path: impl AsRef<Path>) -> Vec<&FileMetadata<'a>> {
+        let dir_path = self.path_in_root(path.as_ref());
+
+        self.files
+            .iter()
+            .filter(|file| file.parent() == dir_path)
+            .collect()
+    }
+
+    /// Resolves a path relative to the scanned directory, paths
+    /// that are already inside the scanned directory are kept as they are
+    fn path_in_root(&self, path: &Path) -> PathBuf {
+        if path.starts_with(&self.path) {
+            path.to_path_buf()
+        } else {
+            self.path.join(path)
+        }
+    }
+
+    /// Get all the files in the current directory and all the files in it's sub-directory
+    pub fn files(&self) -> &[FileMetadata<'a>] {
+        self.files.as_ref()
+    }
+
+    /// Keep only the files for which `keep` returns `true`.
+    /// The size of the removed files is subtracted from [Self::size], [Self::disk_usage]
+    /// and the sizes of the directories containing them
+    pub fn retain_files(&mut self, mut keep: impl FnMut(&FileMetadata) -> bool) {
+        let (kept, removed) = self
+            .files
+            .drain(..)
+            .partition::<Vec<FileMetadata<'a>>, _>(|file| keep(file));
+        self.files = kept;
+
+        removed.iter().for_each(|file| self.forget_file(file));
+        self.path_index.invalidate();
+    }
+
+    /// Remove the file at `path`, or if `path` is a directory remove it with all the files
+    /// and sub-directories inside it eg to drop everything under `.git`.
+    /// The sizes are updated like [Self::retain_files] does.
+    /// Returns `true` if anything was removed
+    pub fn remove_path(&mut self, path: impl AsRef<Path>) -> bool {
+        let path = self.path_in_root(path.as_ref());
+
+        let files = self.files.len();
+        self.retain_files(|file| !file.path().starts_with(&path));
+
+        let directories = self.directories.len();
+        self.directories
+            .retain(|directory| !directory.starts_with(&path));
+        self.dir_entries
+            .retain(|dir_entry| !dir_entry.path.starts_with(&path));
+        self.dir_children
+            .retain(|directory, _| !directory.starts_with(&path));
+        self.dir_sizes
+            .retain(|directory, _| !directory.starts_with(&path));
+        self.dir_file_counts
+            .retain(|directory, _| !directory.starts_with(&path));
+
+        files != self.files.len() || directories != self.directories.len()
+    }
+
+    /// Add the files, directories and errors of `other`, for example a scan of another root.
+    /// A file or an error found in both is only kept once, and the sizes of `other` are added
+    /// to those of `self` without counting the files kept once twice.
+    /// The size and number of files of a directory found in both are those of `self`
+    pub fn merge(&mut self, other: DirMetadata<'a>) {
+        let known = self
+            .files
+            .iter()
+            .map(|file| file.path().into_owned())
+            .collect::<HashSet<PathBuf>>();
+
+        self.size += other.size;
+        self.disk_usage += other.disk_usage;
+        #[cfg(feature = "unix")]
+        {
+            self.size_deduped += other.size_deduped;
+        }
+
+        for file in other.files {
+            if known.contains(file.path().as_ref()) {
+                // Only the totals were added, the directory totals of `other` are not
+                self.forget_file_totals(&file);
+            } else {
+                self.files.push(file);
+            }
+        }
+
+        let known_directories = self
+            .directories
+            .iter()
+            .cloned()
+            .collect::<HashSet<PathBuf>>();
+        self.directories.extend(
+            other
+                .directories
+                .into_iter()
+                .filter(|directory| !known_directories.contains(directory)),
+        );
+        self.dir_entries.extend(
+            other
+                .dir_entries
+                .into_iter()
+                .filter(|dir_entry| !known_directories.contains(&dir_entry.path)),
+        );
+        for error in other.errors {
+            if !self.errors.contains(&error) {
+                self.errors.push(error);
+            }
+        }
+        self.dropped_errors += other.dropped_errors;
+        self.skipped.extend(other.skipped);
+
+        other.roots.into_iter().for_each(|root| {
+            if !self.roots.contains(&root) {
+                self.roots.push(root);
+            }
+        });
+        other.dir_sizes.into_iter().for_each(|(directory, size)| {
+            self.dir_sizes.entry(directory).or_insert(size);
+        });
+        other
+            .dir_children
+            .into_iter()
+            .for_each(|(directory, children)| {
+                self.dir_children.entry(directory).or_insert(children);
+            });
+        other
+            .dir_file_counts
+            .into_iter()
+            .for_each(|(directory, file_count)| {
+                self.dir_file_counts.entry(directory).or_insert(file_count);
+            });
+
+        self.path_index.invalidate();
+    }
+
+    /// Scan the roots again with the options of this scan, replacing the files, directories
+    /// and errors in place. The file formats of unchanged files are reused like [Self::rescan] does.
+    /// The hooks, the [CancelHandle] and [Self::strict] carry over so a strict scan returns
+    /// an error, leaving `self` unchanged, if an entry cannot be read anymore
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let mut outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     outcome.refresh().await.unwrap();
+    ///     dbg!(outcome.size());
+    /// });
+    /// ```
+    pub async fn refresh(&mut self) -> io::Result<()> {
+        *self = self
+            .rescanner(self.path.clone(), self.roots.clone(), 0)
+            .dir_metadata()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Same as [Self::refresh] blocking the current thread
+    pub fn refresh_blocking(&mut self) -> io::Result<()> {
+        smol::block_on(self.refresh())
+    }
+
+    /// Scan only the file or directory at `path` again, a path relative to the scanned
+    /// directory or one inside it. The entries and errors under `path` are replaced and
+    /// the sizes are adjusted, the rest of the scan is left as it is.
+    /// A root is refreshed like [Self::refresh] does.
+    /// Returns `true` if `path` still exists and is part of the scan
+    pub async fn refresh_subdir(&mut self, path: impl AsRef<Path>) -> io::Result<bool> {
+        let path = self.path_in_root(path.as_ref());
+
+        if self.roots.contains(&path) {
+            self.refresh().await?;
+
+            return Ok(true);
+        }
+
+        self.refresh_path(&path).await
+    }
+
+    /// A scan of the directory at `path` with the options of this scan that reuses the
+    /// file formats detected by it. The depth limit is reduced by the depth of `path`
+    pub(crate) fn rescanner(&self, path: PathBuf, roots: Vec<PathBuf>, depth: usize) -> Self {
+        DirMetadata {
+            name: self.name.clone(),
+            path,
+            roots,
+            max_depth: self
+                .max_depth
+                .map(|max_depth| max_depth.saturating_sub(depth)),
+            follow_symlinks: self.follow_symlinks,
+            include_globs: self.include_globs.clone(),
+            exclude_globs: self.exclude_globs.clone(),
+            extensions: self.extensions.clone(),
+            min_size: self.min_size,
+            max_size: self.max_size,
+            skip_dir_names: self.skip_dir_names.clone(),
+            #[cfg(feature = "time")]
+            modified_after: self.modified_after,
+            filter_hook: self.filter_hook.clone(),
+            #[cfg(feature = "gitignore")]
+            respect_gitignore: self.respect_gitignore,
+            same_filesystem: self.same_filesystem,
+            concurrency: self.concurrency,
+            skip_format_detection: self.skip_format_detection,
+            format_detection_limit: self.format_detection_limit,
+            defer_format_detection: self.defer_format_detection,
+            #[cfg(feature = "hash")]
+            hash_algo: self.hash_algo,
+            sorted: self.sorted,
+            traversal_order: self.traversal_order,
+            strict: self.strict,
+            error_hook: self.error_hook.clone(),
+            max_errors: self.max_errors,
+            cancel_handle: self.cancel_handle.clone(),
+            progress_hook: self.progress_hook.clone(),
+            ..Default::default()
+        }
+        .rescan(self)
+    }
+
+    /// Replaces what the scan knows about `path` with what is on disk now: the file is read
+    /// again or the directory is scanned again with the options of this scan if it still exists,
+    /// then it replaces the entries at `path`. Returns `true` if `path` is part of the scan.
+    /// The scan is left unchanged if reading `path` fails.
+    /// The ignore files are only applied to the entries of a directory that is scanned again
+    pub(crate) async fn refresh_path(&mut self, path: &Path) -> io::Result<bool> {
+        let Some(root) = self
+            .roots
+            .iter()
+            .find(|root| path.starts_with(root) && path != root.as_path())
+            .cloned()
+        else {
+            self.forget_path(path);
+
+            return Ok(false);
+        };
+
+        let meta = match smol::fs::symlink_metadata(path).await {
+            Ok(meta) => meta,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                self.forget_path(path);
+
+                return Ok(false);
+            }
+            Err(error) => return Err(error),
+        };
+        let is_symlink = meta.is_symlink();
+        let is_dir = meta.is_dir() || (self.follow_symlinks && is_symlink && path.is_dir());
+        let parent = path.parent().unwrap_or(&root).to_path_buf();
+        // The depth of the directory containing the entry, the root is at depth 0
+        let depth = parent
+            .strip_prefix(&root)
+            .map(|relative| relative.components().count())
+            .unwrap_or_default();
+        let level = DirLevel {
+            root,
+            path: parent,
+            depth,
+            ..Default::default()
+        };
+
+        if self.max_depth.is_some_and(|max_depth| depth > max_depth)
+            || self.is_filtered_out(path, is_dir, &level)
+            || !self.passes_filter_hook(path, is_dir)
+        {
+            self.forget_path(path);
+
+            return Ok(false);
+        }
+
+        if is_dir {
+            let file_name = path.file_name().unwrap_or_default();
+            if self
+                .skip_dir_names
+                .iter()
+                .any(|name| file_name == name.as_str())
+            {
+                self.forget_path(path);
+
+                return Ok(false);
+            }
+
+            // The directory is listed but not read beyond the depth limit
+            let scan = match self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                true => None,
+                false => Some(
+                    self.rescanner(path.to_path_buf(), vec![path.to_path_buf()], depth + 1)
+                        .dir_metadata()
+                        .await?,
+                ),
+            };
+            let dir_meta = match is_symlink {
+                true => smol::fs::metadata(path).await.ok(),
+                false => Some(meta),
+            };
+            self.forget_path(path);
+            self.dir_entries
+                .push(DirEntryMetadata::new(path, depth, dir_meta.as_ref()));
+
+            match scan {
+                Some(scan) => self.absorb(scan, path, depth + 1),
+                None => self.directories.push(path.to_path_buf()),
+            }
+            self.fill_dir_entries();
+        } else {
+            let file_meta = self.read_file(path, &meta, is_symlink, depth).await;
+            self.forget_path(path);
+            let Some(file_meta) = file_meta else {
+                return Ok(false);
+            };
+            self.remember_file(file_meta);
+        }
+
+        self.sort_entries();
+
+        Ok(true)
+    }
+
+    /// Removes the entries, errors and skipped entries at `path`
+    fn forget_path(&mut self, path: &Path) {
+        self.remove_path(path);
+        self.errors.retain(|error| !error.path.starts_with(path));
+        self.skipped.retain(|skipped| !skipped.starts_with(path));
+    }
+
+    /// Reads a single file like a scan does, returns [Option::None] if it is filtered out
+    async fn read_file(
+        &mut self,
+        path: &Path,
+        meta: &std::fs::Metadata,
+        is_symlink: bool,
+        depth: usize,
+    ) -> Option<FileMetadata<'a>> {
+        if !self.size_in_range(meta.len()) {
+            return None;
+        }
+
+        #[cfg(feature = "time")]
+        if let Some(modified_after) = self.modified_after {
+            if meta
+                .modified()
+                .ok()
+                .map(|modified| Tai64N::from_system_time(&modified))
+                <= Some(modified_after)
+            {
+                return None;
+            }
+        }
+
+        let mut file_meta = FileMetadata {
+            symlink: is_symlink,
+            depth,
+            kind: meta.file_type().into(),
+            ..FileMetadata::at_path(path)
+        };
+        Self::fill_file_meta(&mut file_meta, meta);
+
+        if is_symlink {
+            file_meta.link_target = smol::fs::read_link(path).await.ok();
+            file_meta.broken_link = Self::is_broken_target(path).await;
+        }
+
+        let is_regular = Self::is_regular_file(&file_meta).await;
+        if !self.should_detect_format(&file_meta, is_regular) {
+            file_meta.file_format = OnceLock::from(FileFormat::default());
+        } else if !self.defer_format_detection {
+            let cloned_path = file_meta.path().into_owned();
+            let file_format = unblock(move || FileFormat::from_file(&cloned_path)).await;
+            file_meta.file_format = OnceLock::from(file_format.unwrap_or_default());
+        }
+
+        #[cfg(feature = "hash")]
+        if let Some(hash_algo) = self.hash_algo.filter(|_| is_regular) {
+            let cloned_path = file_meta.path().into_owned();
+            file_meta.digest = unblock(move || hash_algo.digest_file(&cloned_path))
+                .await
+                .ok();
+        }
+
+        Some(file_meta)
+    }
+
+    /// Records a file read after the scan and adds its size to the totals
+    fn remember_file(&mut self, file: FileMetadata<'a>) {
+        self.size += file.size;
+        self.disk_usage += file.disk_usage;
+        #[cfg(feature = "unix")]
+        if file.nlink.is_none_or(|nlink| nlink <= 1) {
+            self.size_deduped += file.size;
+        }
+
+        file.parent().ancestors().for_each(|directory| {
+            if let Some(size) = self.dir_sizes.get_mut(directory) {
+                *size += file.size;
+            }
+
+            if let Some(file_count) = self.dir_file_counts.get_mut(directory) {
+                *file_count += 1;
+            }
+        });
+
+        self.files.push(file);
+        self.path_index.invalidate();
+    }
+
+    /// Adds a scan of the directory at `path` found at `depth` inside this scan,
+    /// the size and number of files of the directory are added to its ancestors
+    fn absorb(&mut self, scan: DirMetadata<'a>, path: &Path, depth: usize) {
+        let size = scan.dir_sizes.get(path).copied().unwrap_or_default();
+        let file_count = scan.dir_file_counts.get(path).copied().unwrap_or_default();
+        path.ancestors().skip(1).for_each(|directory| {
+            if let Some(directory_size) = self.dir_sizes.get_mut(directory) {
+                *directory_size += size;
+            }
+
+            if let Some(directory_file_count) = self.dir_file_counts.get_mut(directory) {
+                *directory_file_count += file_count;
+            }
+        });
+
+        self.size += scan.size;
+        self.disk_usage += scan.disk_usage;
+        #[cfg(feature = "unix")]
+        {
+            self.size_deduped += scan.size_deduped;
+        }
+        self.max_depth_seen = self.max_depth_seen.max(scan.max_depth_seen + depth);
+
+        self.directories.push(path.to_path_buf());
+        self.directories.extend(scan.directories);
+        self.dir_entries.extend(scan.dir_entries);
+        self.dir_children.extend(scan.dir_children);
+        self.files.extend(scan.files);
+        self.errors.extend(scan.errors);
+        self.skipped.extend(scan.skipped);
+        self.dir_sizes.extend(scan.dir_sizes);
+        self.dir_file_counts.extend(scan.dir_file_counts);
+        self.path_index.invalidate();
+    }
+
+    /// Subtracts the size of a file that is no longer part of the scan from the totals
+    fn forget_file(&mut self, file: &FileMetadata) {
+        self.forget_file_totals(file);
+
+        file.parent().ancestors().for_each(|directory| {
+            if let Some(size) = self.dir_sizes.get_mut(directory) {
+                *size = size.saturating_sub(file.size);
+            }
+
+            if let Some(file_count) = self.dir_file_counts.get_mut(directory) {
+                *file_count = file_count.saturating_sub(1);
+            }
+        });
+    }
+
+    /// Subtracts the size of a file from the totals of the whole scan
+    fn forget_file_totals(&mut self, file: &FileMetadata) {
+        self.size = self.size.saturating_sub(file.size);
+        self.disk_usage = self.disk_usage.saturating_sub(file.disk_usage);
+        #[cfg(feature = "unix")]
+        if file.nlink.is_none_or(|nlink| nlink <= 1) {
+            self.size_deduped = self.size_deduped.saturating_sub(file.size);
+        }
+    }
+
+    /// Iterate over all the files, the same as iterating over [Self::files]
+    pub fn iter(&self) -> std::slice::Iter<'_, FileMetadata<'a>> {
+        self.files.iter()
+    }
+
+    /// Get the number of files found
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check if no file was found
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Estimate the memory used by the scan in bytes, counting the files, directories and errors
+    /// with the paths and text they own. The directory shared by the files found in it is counted
+    /// once, see [FileMetadata::parent]. The index built by [Self::get_file_by_path] is counted
+    /// once it is built, the unused capacity of collections and the bookkeeping
+    /// of the allocator are not counted
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     println!(
+    ///         "{} files use about {} bytes",
+    ///         outcome.len(),
+    ///         outcome.memory_footprint_estimate()
+    ///     );
+    /// });
+    /// ```
+    pub fn memory_footprint_estimate(&self) -> usize {
+        let path_size = |path: &Path| size_of::<PathBuf>() + path.as_os_str().len();
+        let text_size = |text: &CowStr| match text {
+            Cow::Borrowed(_) => 0,
+            Cow::Owned(text) => text.len(),
+        };
+
+        let mut parents = HashSet::<*const PathBuf>::new();
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                // The reference counts are stored next to the shared directory
+                let parent = match parents.insert(Arc::as_ptr(&file.parent)) {
+                    true => 2 * size_of::<usize>() + path_size(&file.parent),
+                    false => 0,
+                };
+                #[cfg(feature = "hash")]
+                let digest = file.digest.as_ref().map_or(0, String::len);
+                #[cfg(not(feature = "hash"))]
+                let digest = 0;
+
+                size_of::<FileMetadata>()
+                    + text_size(&file.name)
+                    + file.name_os.as_ref().map_or(0, |name| name.len())
+                    + file.link_target.as_deref().map_or(0, path_size)
+                    + digest
+                    + parent
+            })
+            .sum::<usize>();
+
+        let directories = self
+            .directories
+            .iter()
+            .chain(&self.skipped)
+            .map(|path| path_size(path))
+            .sum::<usize>();
+        let dir_entries = self
+            .dir_entries
+            .iter()
+            .map(|entry| {
+                size_of::<DirEntryMetadata>() + entry.name.len() + entry.path.as_os_str().len()
+            })
+            .sum::<usize>();
+        let errors = self
+            .errors
+            .iter()
+            .map(|error| {
+                size_of::<DirError>() + error.path.as_os_str().len() + text_size(&error.display)
+            })
+            .sum::<usize>();
+        // The totals of each directory are keyed by its path
+        let totals = self
+            .dir_sizes
+            .keys()
+            .chain(self.dir_file_counts.keys())
+            .chain(self.dir_children.keys())
+            .map(|path| path_size(path) + size_of::<(usize, usize)>())
+            .sum::<usize>();
+
+        size_of::<Self>()
+            + files
+            + directories
+            + dir_entries
+            + errors
+            + totals
+            + self.path_index.footprint()
+    }
+
+    /// Get the files whose path relative to the scanned directory matches the glob `pattern`
+    /// eg `**/*.min.js`. Only `**` matches across directories, `*` and `?` never match a `/`.
+    /// Returns an error if the pattern is invalid
+    pub fn find(&self, pattern: &str) -> Result<Vec<&FileMetadata<'a>>, globset::Error> {
+        self.find_glob(pattern, false)
+    }
+
+    /// Get the files whose path relative to the scanned directory matches the glob `pattern`
+    /// ignoring case. Returns an error if the pattern is invalid
+    pub fn find_ci(&self, pattern: &str) -> Result<Vec<&FileMetadata<'a>>, globset::Error> {
+        self.find_glob(pattern, true)
+    }
+
+    fn find_glob(
+        &self,
+        pattern: &str,
+        case_insensitive: bool,
+    ) -> Result<Vec<&FileMetadata<'a>>, globset::Error> {
+        let matcher = GlobBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+
+        Ok(self
+            .files
+            .iter()
+            .filter(|file| {
+                let root = self.root_of(file).unwrap_or(&self.path);
+
+                let path = file.path();
+
+                matcher.is_match(path.strip_prefix(root).unwrap_or(&path))
+            })
+            .collect())
+    }
+
+    /// Get the files whose name matches the regular expression `regex`
+    #[cfg(feature = "regex")]
+    pub fn find_regex(&self, regex: &Regex) -> Vec<&FileMetadata<'a>> {
+        self.files
+            .iter()
+            .filter(|file| regex.is_match(file.name()))
+            .collect()
+    }
+
+    /// Get the files whose name matches the regular expression `regex` ignoring case
+    #[cfg(feature = "regex")]
+    pub fn find_regex_ci(&self, regex: &Regex) -> Vec<&FileMetadata<'a>> {
+        // The pattern already compiled once so it only fails to compile again
+        // if ignoring case makes it exceed the size limit
+        let regex = RegexBuilder::new(regex.as_str())
+            .case_insensitive(true)
+            .build()
+            .unwrap_or_else(|_| regex.clone());
+
+        self.find_regex(&regex)
+    }
+
+    /// Get the first file named exactly `name`
+    pub fn get_file(&self, name: &str) -> Option<&FileMetadata<'a>> {
+        self.files.iter().find(|file| file.name == name)
+    }
+
+    /// Get the first file whose name is `name` ignoring case, eg `README.md` finds `ReadMe.MD`.
+    /// With the `unicode` feature names that only differ in their Unicode normalization also match
+    pub fn get_file_ci(&self, name: &str) -> Option<&FileMetadata<'a>> {
+        let lowercase = |name: &'_ str| -> Vec<char> {
+            #[cfg(feature = "unicode")]
+            let name = name.nfc();
+            #[cfg(not(feature = "unicode"))]
+            let name = name.chars();
+
+            name.flat_map(char::to_lowercase).collect()
+        };
+        let name = lowercase(name);
+
+        self.files.iter().find(|file| lowercase(&file.name) == name)
+    }
+
+    /// Get the file at `path`, trying in order the exact path, the path relative to
+    /// [Self::dir_path] and the canonical path so `src/lib.rs`, `lib.rs` and
+    /// `/home/user/project/src/lib.rs` all find the same file of a scan of `src`.
+    /// This also finds files whose name is not valid UTF-8. With the `unicode` feature paths
+    /// that only differ in their Unicode normalization also match, eg a name stored decomposed (NFD)
+    /// by the filesystem is found using the composed (NFC) form typed by a user.
+    ///
+    /// The first lookup builds an index of the files by directory so that later lookups
+    /// are fast, the index keeps the position of every file in memory
+    /// which is counted by [Self::memory_footprint_estimate]
+    pub fn get_file_by_path(&self, path: impl AsRef<Path>) -> Option<&FileMetadata<'a>> {
+        let path = path.as_ref();
+
+        let found = self
+            .file_at(path)
+            .or_else(|| self.file_at(&self.path.join(path)))
+            .or_else(|| self.file_at(&self.canonical_in_root(path)?));
+
+        #[cfg(feature = "unicode")]
+        let found = found.or_else(|| {
+            let path = path.to_str()?;
+
+            self.files.iter().find(|file| {
+                file.path()
+                    .to_str()
+                    .is_some_and(|file_path| file_path.nfc().eq(path.nfc()))
+            })
+        });
+
+        found
+    }
+
+    /// Looks up a file by its exact path in the index
+    fn file_at(&self, path: &Path) -> Option<&FileMetadata<'a>> {
+        self.path_index
+            .position(&self.files, path)
+            .map(|index| &self.files[index])
+    }
+
+    /// Resolves `path` to the form the files of the scan are stored in by canonicalizing it
+    /// and the scanned directory, eg an absolute path when [Self::dir_path] is relative
+    fn canonical_in_root(&self, path: &Path) -> Option<PathBuf> {
+        let canonical = std::fs::canonicalize(path).ok()?;
+        let canonical_root = std::fs::canonicalize(&self.path).ok()?;
+
+        match canonical.strip_prefix(&canonical_root) {
+            Ok(relative) => Some(self.path.join(relative)),
+            Err(_) => Some(canonical),
+        }
+    }
+
+    /// Check if a file with the exact `path` was found, this uses
+    /// the same index as [Self::get_file_by_path]
+    pub fn contains_path(&self, path: impl AsRef<Path>) -> bool {
+        self.path_index
+            .position(&self.files, path.as_ref())
+            .is_some()
+    }
+
+    /// Get all the files whose [FileFormat] is of the given `kind`. This only uses the formats
+    /// already detected so files whose detection was deferred with
+    /// [Self::defer_format_detection] and not done yet are not returned
+    pub fn files_of_kind(&self, kind: Kind) -> Vec<&FileMetadata<'a>> {
+        self.files
+            .iter()
+            .filter(|file| {
+                file.file_format
+                    .get()
+                    .is_some_and(|file_format| file_format.kind() == kind)
+            })
+            .collect()
+    }
+
+    /// Get all the image files, see [Self::files_of_kind]
+    pub fn images(&self) -> Vec<&FileMetadata<'a>> {
+        self.files_of_kind(Kind::Image)
+    }
+
+    /// Get all the video files, see [Self::files_of_kind]
+    pub fn videos(&self) -> Vec<&FileMetadata<'a>> {
+        self.files_of_kind(Kind::Video)
+    }
+
+    /// Get all the audio files, see [Self::files_of_kind]
+    pub fn audio(&self) -> Vec<&FileMetadata<'a>> {
+        self.files_of_kind(Kind::Audio)
+    }
+
+    /// Get all the document files like PDF or office documents, see [Self::files_of_kind]
+    pub fn documents(&self) -> Vec<&FileMetadata<'a>> {
+        self.files_of_kind(Kind::Document)
+    }
+
+    /// Get all the files whose extension matches `extension` ignoring ASCII case
+    pub fn files_with_extension(&self, extension: &str) -> Vec<&FileMetadata<'a>> {
+        self.files
+            .iter()
+            .filter(|file| FsUtils::path_has_extension(&file.path(), extension))
+            .collect()
+    }
+
+    /// Get all the files modified within the `duration` before the current time.
+    /// Files whose modification time could not be read are not returned
+    #[cfg(feature = "time")]
+    pub fn files_modified_within(&self, duration: Duration) -> Vec<&FileMetadata<'a>> {
+        let cutoff = Tai64N::now() - duration;
+
+        self.files
+            .iter()
+            .filter(|file| file.modified.is_some_and(|modified| modified >= cutoff))
+            .collect()
+    }
+
+    /// Get all the files sorted from the largest to the smallest,
+    /// files of the same size are sorted by path
+    pub fn files_sorted_by_size(&self) -> Vec<&FileMetadata<'a>> {
+        let mut files = self.files.iter().collect::<Vec<&FileMetadata<'a>>>();
+        files.sort_by(|first, second| {
+            second
+                .size
+                .cmp(&first.size)
+                .then_with(|| first.cmp_path(second))
+        });
+
+        files
+    }
+
+    /// Get all the files sorted from the most to the least recently modified,
+    /// files whose modification time could not be read are sorted last
+    /// and files with the same modification time are sorted by path
+    #[cfg(feature = "time")]
+    pub fn files_sorted_by_modified(&self) -> Vec<&FileMetadata<'a>> {
+        let mut files = self.files.iter().collect::<Vec<&FileMetadata<'a>>>();
+        files.sort_by(|first, second| {
+            // `None` is smaller than any `Some` so comparing in reverse sorts it last
+            second
+                .modified
+                .cmp(&first.modified)
+                .then_with(|| first.cmp_path(second))
+        });
+
+        files
+    }
+
+    /// Get all the files sorted by name, files with the same name are sorted by path
+    pub fn files_sorted_by_name(&self) -> Vec<&FileMetadata<'a>> {
+        let mut files = self.files.iter().collect::<Vec<&FileMetadata<'a>>>();
+        files.sort_by(|first, second| {
+            first
+                .name
+                .cmp(&second.name)
+                .then_with(|| first.cmp_path(second))
+        });
+
+        files
+    }
+
+    /// Get the symbolic links whose target did not exist when they were scanned,
+    /// including those pointing outside of the scanned directories
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     for link in outcome.broken_links() {
+    ///         println!("{} -> {:?}", link.path().display(), link.link_target());
+    ///     }
+    /// });
+    /// ```
+    pub fn broken_links(&self) -> Vec<&FileMetadata<'a>> {
+        self.files.iter().filter(|file| file.broken_link).collect()
+    }
+
+    /// Get the `count` largest files from the largest to the smallest without sorting all the files,
+    /// files of the same size are returned in the order they were found
+    pub fn largest_files(&self, count: usize) -> Vec<&FileMetadata<'a>> {
+        // A min-heap of the largest files found so far, the smallest of them is removed
+        // whenever a larger file is found
+        let mut largest = BinaryHeap::<Reverse<(u64, Reverse<usize>)>>::with_capacity(count + 1);
+
+        for (index, file) in self.files.iter().enumerate() {
+            largest.push(Reverse((file.size, Reverse(index))));
+
+            if largest.len() > count {
+                largest.pop();
+            }
+        }
+
+        largest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((_, Reverse(index)))| &self.files[index])
+            .collect()
+    }
+
+    /// Get the size of the directory including the  size of all files in the sub-directories
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the size of the directory including the  size of all files in the sub-directories in human readable format
+    pub fn size_formatted(&self) -> String {
+        FsUtils::size_to_bytes(self.size)
+    }
+
+    /// Get the size of the directory in human readable format in the units of `style`
+    pub fn size_formatted_with(&self, style: SizeStyle) -> String {
+        FsUtils::format_size(self.size, style)
+    }
+
+    /// Get the space allocated on disk for all files in the directory and its sub-directories
+    /// like `du` reports it. On platforms without block information this is the same as [Self::size]
+    pub fn disk_usage(&self) -> u64 {
+        self.disk_usage
+    }
+
+    /// Get [Self::disk_usage] in human readable format
+    pub fn disk_usage_formatted(&self) -> String {
+        FsUtils::size_to_bytes(self.disk_usage)
+    }
+
+    /// Get the size of the directory like [Self::size] but counting the size of
+    /// files with several hard links only once. On platforms other than unix
+    /// this is the same as [Self::size]
+    #[cfg(feature = "unix")]
+    pub fn size_deduped(&self) -> u64 {
+        self.size_deduped
+    }
+
+    /// Get the size of the directory at `path` including all its sub-directories, like `du`.
+    /// The `path` is either a path inside the scanned directory or relative to it.
+    /// Returns [Option::None] if the directory was not read
+    pub fn dir_size(&self, path: impl AsRef<Path>) -> Option<u64> {
+        self.dir_sizes
+            .get(&self.path_in_root(path.as_ref()))
+            .copied()
+    }
+
+    /// Get the size of every directory that was read including all its sub-directories
+    pub fn dir_sizes(&self) -> &HashMap<PathBuf, u64> {
+        &self.dir_sizes
+    }
+
+    /// Get the `count` largest sub-directories from the largest to the smallest,
+    /// the scanned directories themselves are not included
+    pub fn largest_dirs(&self, count: usize) -> Vec<(&Path, u64)> {
+        let mut directories = self
+            .dir_sizes
+            .iter()
+            .filter(|(path, _)| !self.roots.contains(path))
+            .map(|(path, size)| (path.as_path(), *size))
+            .collect::<Vec<(&Path, u64)>>();
+        directories.sort_by(|first, second| second.1.cmp(&first.1).then(first.0.cmp(second.0)));
+        directories.truncate(count);
+
+        directories
+    }
+
+    /// Get [Self::largest_dirs] with the sizes in human readable format
+    pub fn largest_dirs_formatted(&self, count: usize) -> Vec<(&Path, String)> {
+        self.largest_dirs(count)
+            .into_iter()
+            .map(|(path, size)| (path, FsUtils::size_to_bytes(size)))
+            .collect()
+    }
+
+    /// Format the size of each directory up to `max_depth` levels below the scanned
+    /// directory like `du -h --max-depth=<max_depth> | sort -rh` does, one directory
+    /// per line with the size including its sub-directories, a tab and the path.
+    /// The scanned directory itself has the depth `0` and the last line is the total size
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     print!("{}", outcome.du_report(2));
+    /// });
+    /// ```
+    pub fn du_report(&self, max_depth: usize) -> String {
+        let depth = |path: &Path| {
+            self.roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())
+                .map(|relative| relative.components().count())
+        };
+
+        let mut directories = self
+            .dir_sizes
+            .iter()
+            .filter(|(path, _)| depth(path).is_some_and(|depth| depth <= max_depth))
+            .collect::<Vec<(&PathBuf, &u64)>>();
+        directories.sort_by(|first, second| second.1.cmp(first.1).then(first.0.cmp(second.0)));
+
+        let total = self
+            .roots
+            .iter()
+            .filter_map(|root| self.dir_sizes.get(root))
+            .sum::<u64>();
+
+        directories
+            .iter()
+            .map(|(path, size)| format!("{}\t{}\n", FsUtils::size_to_bytes(**size), path.display()))
+            .chain([format!("{}\ttotal\n", FsUtils::size_to_bytes(total))])
+            .collect()
+    }
+
+    /// Get the files whose size is zero
+    pub fn empty_files(&self) -> Vec<&FileMetadata<'a>> {
+        self.files.iter().filter(|file| file.size == 0).collect()
+    }
+
+    /// Get the sub-directories that contain no files at any depth, including those that
+    /// only contain other empty directories. Files excluded from the scan by a filter still
+    /// count as content, and only directories that were read are returned
+    pub fn empty_dirs(&self) -> Vec<&Path> {
+        let mut directories = self
+            .dir_file_counts
+            .iter()
+            .filter(|(path, file_count)| **file_count == 0 && !self.roots.contains(path))
+            .map(|(path, _)| path.as_path())
+            .collect::<Vec<&Path>>();
+        directories.sort();
+
+        directories
+    }
+
+    /// Get the algorithm passed to [Self::hash_files]
+    #[cfg(feature = "hash")]
+    pub fn hash_algo(&self) -> Option<HashAlgo> {
+        self.hash_algo
+    }
+
+    /// Get the depth of the deepest directory that was read,
+    /// `0` if only the root directory was read
+    pub fn max_depth_seen(&self) -> usize {
+        self.max_depth_seen
+    }
+
+    /// Get the directories that were not read because their name matched [Self::skip_dir_names],
+    /// if this is not empty the files and size of the directory are partial
+    pub fn skipped(&self) -> &[PathBuf] {
+        self.skipped.as_ref()
+    }
+
+    /// Get the TAI64N timestamp when the scan started,
+    /// [Option::None] if the directory has not been scanned
+    pub fn scanned_at(&self) -> Option<Tai64N> {
+        self.scanned_at
+    }
+
+    /// Get how long the scan took
+    pub fn scan_duration(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Check if the scan started more than `max_age` ago or the directory has not been scanned
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    /// use std::time::Duration;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     assert!(!outcome.is_stale(Duration::from_secs(60)));
+    ///     assert!(outcome.is_stale(Duration::ZERO));
+    /// });
+    /// ```
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.scanned_at
+            .and_then(|scanned_at| Tai64N::now().duration_since(&scanned_at).ok())
+            .is_none_or(|age| age > max_age)
+    }
+
+    /// Was the scan stopped early by a [CancelHandle] or [ErrorAction::Abort],
+    /// in which case the files, directories and size are partial
+    pub fn was_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// Get all the errors encountered while opening the sub-directories and files
+    pub fn errors(&self) -> &[DirError<'a>] {
+        self.errors.as_ref()
+    }
+
+    /// The number of errors that were not stored because [Self::max_errors] was reached
+    pub fn dropped_error_count(&self) -> usize {
+        self.dropped_errors
+    }
+
+    /// Get the number of files found, the same as [Self::len]
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Get the number of sub-directories found, the roots are not counted
+    pub fn dir_count(&self) -> usize {
+        self.directories.len()
+    }
+
+    /// Get the number of errors stored, see [Self::dropped_error_count]
+    /// for the errors that were not stored
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Get the number of errors stored of each [ErrorKind]
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     for (kind, count) in outcome.errors_by_kind() {
+    ///         println!("{count} {kind}");
+    ///     }
+    /// });
+    /// ```
+    pub fn errors_by_kind(&self) -> HashMap<ErrorKind, usize> {
+        self.errors
+            .iter()
+            .fold(HashMap::new(), |mut errors_by_kind, error| {
+                *errors_by_kind.entry(error.error).or_default() += 1;
+
+                errors_by_kind
+            })
+    }
+
+    /// Get the errors for the file or directory at `path` and everything inside it,
+    /// `path` is relative to the scanned directory or a path inside it
+    pub fn errors_for(&self, path: impl AsRef<Path>) -> Vec<&DirError<'a>> {
+        let path = self.path_in_root(path.as_ref());
+
+        self.errors
+            .iter()
+            .filter(|error| error.path.starts_with(&path))
+            .collect()
+    }
+}
+
+/// The state of the directory currently being read, passed down the recursion
+#[derive(Debug, Default, Clone)]
+struct DirLevel {
+    root: PathBuf,
+    path: PathBuf,
+    depth: usize,
+    root_device: Option<u64>,
+    #[cfg(feature = "gitignore")]
+    ignores: Vec<Arc<Gitignore>>,
+}
+
+impl DirLevel {
+    fn child(&self, path: &Path) -> Self {
+        Self {
+            root: self.root.clone(),
+            path: path.to_owned(),
+            depth: self.depth + 1,
+            root_device: self.root_device,
+            #[cfg(feature = "gitignore")]
+            ignores: self.ignores.clone(),
+        }
+    }
+}
+
+/// The progress of a scan passed to the closure of [DirMetadata::on_progress]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ScanProgress<'p> {
+    /// The number of files recorded so far
+    pub files_seen: usize,
+    /// The number of sub-directories found so far
+    pub directories_seen: usize,
+    /// The size in bytes of all the files read so far
+    pub bytes: u64,
+    /// The directory currently being read
+    pub current_path: &'p Path,
+}
+
+/// A stream of the files and errors found while scanning a directory,
+/// created using [DirMetadata::stream_files].
+/// The scan is driven by polling the stream so a slow consumer slows down the scan
+/// instead of files piling up in memory
+pub struct FileStream<'a> {
+    scan: Option<Pin<Box<dyn Future<Output = ()> + Send + 'a>>>,
+    receiver: Receiver<StreamItem>,
+}
+
+// The receiver is only ever accessed by shared reference so it never needs to be pinned
+impl Unpin for FileStream<'_> {}
+
+impl FileStream<'_> {
+    /// The number of items buffered before the scan waits for the consumer
+    const CAPACITY: usize = 64;
+}
+
+impl fmt::Debug for FileStream<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileStream")
+            .field("scan_complete", &self.scan.is_none())
+            .field("buffered", &self.receiver.len())
+            .finish()
+    }
+}
+
+impl Stream for FileStream<'_> {
+    type Item = StreamItem;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Ok(item) = self.receiver.try_recv() {
+                return Poll::Ready(Some(item));
+            }
+
+            match self.scan.as_mut() {
+                Some(scan) => {
+                    if scan.as_mut().poll(cx).is_ready() {
+                        self.scan.take();
+                    } else {
+                        return match self.receiver.try_recv() {
+                            Ok(item) => Poll::Ready(Some(item)),
+                            Err(_) => Poll::Pending,
+                        };
+                    }
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// A blocking iterator over the files and errors found while scanning a directory,
+/// created using [DirMetadata::walk]
+#[derive(Debug)]
+pub struct Walk<'a>(Option<FileStream<'a>>);
+
+impl Iterator for Walk<'_> {
+    type Item = StreamItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = smol::block_on(self.0.as_mut()?.next());
+
+        if item.is_none() {
+            self.0.take();
+        }
+
+        item
+    }
+}
+
+impl FusedIterator for Walk<'_> {}
+
+impl<'d, 'a> IntoIterator for &'d DirMetadata<'a> {
+    type Item = &'d FileMetadata<'a>;
+    type IntoIter = std::slice::Iter<'d, FileMetadata<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.iter()
+    }
+}
+
+impl<'a> IntoIterator for DirMetadata<'a> {
+    type Item = FileMetadata<'static>;
+    type IntoIter =
+        std::iter::Map<std::vec::IntoIter<FileMetadata<'a>>, fn(FileMetadata<'a>) -> Self::Item>;
+
+    /// Iterate over the files taking ownership of them, names borrowed
+    /// from the scanned path are copied so the files outlive it
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.into_iter().map(FileMetadata::into_owned)
+    }
+}
+
+/// The positions in [DirMetadata::files] of the files of each directory sorted by name,
+/// built on the first lookup. The directories are shared with the files instead of copying
+/// the path of every file. It is not compared since it is only a cache of the files
+#[derive(Debug, Default, Clone)]
+struct PathIndex(OnceLock<HashMap<SharedDir, Vec<usize>>>);
+
+impl PathIndex {
+    fn get_or_build(&self, files: &[FileMetadata]) -> &HashMap<SharedDir, Vec<usize>> {
+        self.0.get_or_init(|| {
+            let mut directories = HashMap::<SharedDir, Vec<usize>>::new();
+            files.iter().enumerate().for_each(|(index, file)| {
+                directories
+                    .entry(SharedDir(file.parent.clone()))
+                    .or_default()
+                    .push(index)
+            });
+            directories.values_mut().for_each(|positions| {
+                positions
+                    .sort_by(|first, second| files[*first].name_os().cmp(files[*second].name_os()))
+            });
+
+            directories
+        })
+    }
+
+    /// The position of the file at the exact `path` in `files`
+    fn position(&self, files: &[FileMetadata], path: &Path) -> Option<usize> {
+        // Split like [FileMetadata::at_path] stores a path
+        let (parent, file_name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(file_name)) => (parent, file_name),
+            _ => (Path::new(""), path.as_os_str()),
+        };
+        let positions = self.get_or_build(files).get(parent)?;
+
+        positions
+            .binary_search_by(|index| files[*index].name_os().cmp(file_name))
+            .ok()
+            .map(|found| positions[found])
+    }
+
+    /// The memory used by the index in bytes, `0` until it is built
+    fn footprint(&self) -> usize {
+        self.0.get().map_or(0, |directories| {
+            directories
+                .values()
+                .map(|positions| {
+                    size_of::<(SharedDir, Vec<usize>)>() + size_of_val(positions.as_slice())
+                })
+                .sum()
+        })
+    }
+
+    /// Drops the index, it must be called whenever the files change
+    fn invalidate(&mut self) {
+        self.0.take();
+    }
+}
+
+impl PartialEq for PathIndex {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for PathIndex {}
+
+/// A directory shared by the files found in it, looked up by its [Path]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SharedDir(Arc<PathBuf>);
+
+impl Hash for SharedDir {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_path().hash(state)
+    }
+}
+
+impl Borrow<Path> for SharedDir {
+    fn borrow(&self) -> &Path {
+        self.0.as_path()
+    }
+}
+
+/// The sending half of the channel used by [FileStream] or [DirMetadata::scan_to]
+#[derive(Debug, Clone)]
+enum StreamSender {
+    Items(Sender<StreamItem>),
+    Files(Sender<FileMetadata<'static>>),
+}
+
+impl PartialEq for StreamSender {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Items(sender), Self::Items(other)) => sender.same_channel(other),
+            (Self::Files(sender), Self::Files(other)) => sender.same_channel(other),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for StreamSender {}
+
+/// What the scan does after an error, returned by the closure of [DirMetadata::on_error]
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub enum ErrorAction {
+    /// Keep scanning as if the error was not found
+    #[default]
+    Continue,
+    /// Stop reading the directory the error was found in, or the sub-directory
+    /// that could not be entered, and do not descend into its sub-directories.
+    /// The files already read are kept
+    SkipSubtree,
+    /// Stop the scan, which returns whatever was collected so far
+    /// with [DirMetadata::was_cancelled] set to `true`
+    Abort,
+}
+
+/// The order the directories of a scan are read in, set using [DirMetadata::traversal_order]
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TraversalOrder {
+    /// Read the sub-directories of a directory before its next sibling,
+    /// like `find` does
+    #[default]
+    DepthFirst,
+    /// Read all the directories at a depth before any directory one level deeper
+    /// so that the files closest to the scanned directory are found first
+    BreadthFirst,
+}
+
+/// A handle used to stop a scan created with [DirMetadata::cancellable].
+/// It can be cloned and sent to another thread or task
+#[derive(Debug, Default, Clone)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    /// Stop the scan before the next entry or directory is read
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Check if [Self::cancel] has been called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for CancelHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancelHandle {}
+
+/// A user supplied callback that can be shared between clones of [DirMetadata]
+pub(crate) struct SharedHook<F: ?Sized>(pub(crate) Arc<Mutex<F>>);
+
+impl<F: ?Sized> SharedHook<F> {
+    pub(crate) fn lock(&self) -> std::sync::MutexGuard<'_, F> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<F: ?Sized> Clone for SharedHook<F> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for SharedHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedHook")
+    }
+}
+
+impl<F: ?Sized> PartialEq for SharedHook<F> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<F: ?Sized> Eq for SharedHook<F> {}
+
+/// A set of glob patterns compiled once when a pattern is added
+#[derive(Debug, Default, Clone)]
+pub(crate) struct GlobFilter {
+    pub(crate) globs: Vec<Glob>,
+    pub(crate) set: GlobSet,
+}
+
+impl GlobFilter {
+    pub(crate) fn add(&mut self, pattern: &str) -> Result<(), globset::Error> {
+        self.globs.push(Glob::new(pattern)?);
+
+        let mut builder = GlobSetBuilder::new();
+        self.globs.iter().for_each(|glob| {
+            builder.add(glob.clone());
+        });
+        self.set = builder.build()?;
+
+        Ok(())
+    }
+}
+
+impl PartialEq for GlobFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.globs == other.globs
+    }
+}
+
+impl Eq for GlobFilter {}
+
+/// The type of a file that is not a directory
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FileKind {
+    /// A regular file
+    Regular,
+    /// A symbolic link
+    Symlink,
+    /// A named pipe
+    Fifo,
+    /// A unix domain socket
+    Socket,
+    /// A character device like a terminal
+    CharDevice,
+    /// A block device like a disk
+    BlockDevice,
+    /// The type of the file could not be read or is not known on this platform
+    #[default]
+    Unknown,
+}
+
+impl From<std::fs::FileType> for FileKind {
+    fn from(file_type: std::fs::FileType) -> Self {
+        #[cfg(unix)]
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_file() {
+            return Self::Regular;
+        }
+
+        if file_type.is_symlink() {
+            return Self::Symlink;
+        }
+
+        #[cfg(unix)]
+        {
+            if file_type.is_fifo() {
+                return Self::Fifo;
+            }
+
+            if file_type.is_socket() {
+                return Self::Socket;
+            }
+
+            if file_type.is_char_device() {
+                return Self::CharDevice;
+            }
+
+            if file_type.is_block_device() {
+                return Self::BlockDevice;
+            }
+        }
+
+        Self::Unknown
+    }
+}
+
+/// The file metadata like file name, file type, file size, file path etc
+#[derive(Debug, PartialEq, Eq, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMetadata<'a> {
+    name: CowStr<'a>,
+    /// The name exactly as it is stored on disk, only kept when it is not valid UTF-8
+    /// since it is the same as [Self::name] otherwise
+    name_os: Option<OsString>,
+    /// The position of the `.` separating the extension in [Self::name]
+    extension_dot: Option<usize>,
+    /// The directory containing the file, shared by all the files found in it
+    /// instead of storing the full path of every file
+    parent: Arc<PathBuf>,
+    size: u64,
+    disk_usage: u64,
+    read_only: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::tai64n_option"))]
+    created: Option<Tai64N>,
+    #[cfg(feature = "time")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::tai64n_option"))]
+    changed: Option<Tai64N>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::tai64n_option"))]
+    accessed: Option<Tai64N>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::tai64n_option"))]
+    modified: Option<Tai64N>,
+    symlink: bool,
+    depth: usize,
+    kind: FileKind,
+    link_target: Option<PathBuf>,
+    broken_link: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::file_format_cell"))]
+    file_format: OnceLock<FileFormat>,
+    #[cfg(feature = "hash")]
+    digest: Option<String>,
+    #[cfg(feature = "unix")]
+    mode: Option<u32>,
+    #[cfg(feature = "unix")]
+    uid: Option<u32>,
+    #[cfg(feature = "unix")]
+    gid: Option<u32>,
+    #[cfg(feature = "unix")]
+    inode: Option<u64>,
+    #[cfg(feature = "unix")]
+    device: Option<u64>,
+    #[cfg(feature = "unix")]
+    nlink: Option<u64>,
+}
+
+impl<'a> FileMetadata<'a> {
+    /// A file named `file_name` inside the directory `parent`
+    fn new(parent: Arc<PathBuf>, file_name: &OsStr) -> Self {
+        let name = file_name.to_string_lossy().into_owned();
+        let name_os = (file_name.to_str().is_none()).then(|| file_name.to_owned());
+        let extension_dot = Path::new(&name)
+            .extension()
+            .map(|extension| name.len() - extension.len() - 1);
+
+        FileMetadata {
+            name: CowStr::Owned(name),
+            name_os,
+            extension_dot,
+            parent,
+            ..Default::default()
+        }
+    }
+
+    /// The file at `path`, a path without a file name like `..` is kept whole as the name
+    fn at_path(path: &Path) -> Self {
+        let (parent, file_name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(file_name)) => (parent, file_name),
+            _ => (Path::new(""), path.as_os_str()),
+        };
+
+        Self::new(Arc::new(parent.to_path_buf()), file_name)
+    }
+
+    /// Convert the file to one that does not borrow from the scan it was found by,
+    /// its name is copied if it is borrowed
+    pub fn into_owned(self) -> FileMetadata<'static> {
+        FileMetadata {
+            name: Cow::Owned(self.name.into_owned()),
+            name_os: self.name_os,
+            extension_dot: self.extension_dot,
+            parent: self.parent,
+            size: self.size,
+            disk_usage: self.disk_usage,
+            read_only: self.read_only,
+            created: self.created,
+            #[cfg(feature = "time")]
+            changed: self.changed,
+            accessed: self.accessed,
+            modified: self.modified,
+            symlink: self.symlink,
+            depth: self.depth,
+            kind: self.kind,
+            link_target: self.link_target,
+            broken_link: self.broken_link,
+            file_format: self.file_format,
+            #[cfg(feature = "hash")]
+            digest: self.digest,
+            #[cfg(feature = "unix")]
+            mode: self.mode,
+            #[cfg(feature = "unix")]
+            uid: self.uid,
+            #[cfg(feature = "unix")]
+            gid: self.gid,
+            #[cfg(feature = "unix")]
+            inode: self.inode,
+            #[cfg(feature = "unix")]
+            device: self.device,
+            #[cfg(feature = "unix")]
+            nlink: self.nlink,
+        }
+    }
+
+    /// Get the name of the file, characters that are not valid UTF-8
+    /// are replaced with `U+FFFD REPLACEMENT CHARACTER`
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    /// Get the name of the file exactly as it is stored on disk
+    pub fn name_os(&self) -> &OsStr {
+        self.name_os
+            .as_deref()
+            .unwrap_or(OsStr::new(self.name.as_ref()))
+    }
+
+    /// Get the depth of the directory containing the file,
+    /// `0` if the file is directly in the scanned directory
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Get the path of the directory containing the file
+    pub fn parent(&self) -> &Path {
+        self.parent.as_path()
+    }
+
+    /// Get the extension of the file, the part of the name after the last `.`.
+    /// Returns [Option::None] if the name has no `.` or only starts with one,
+    /// so `.gitignore` has no extension and `archive.tar.gz` has the extension `gz`
+    pub fn extension(&self) -> Option<&str> {
+        self.extension_dot.map(|dot| &self.name[dot + 1..])
+    }
+
+    /// Get the name of the file without its [Self::extension],
+    /// so `.gitignore` has the stem `.gitignore` and `archive.tar.gz` has the stem `archive.tar`
+    pub fn stem(&self) -> Option<&str> {
+        if self.name.is_empty() {
+            return None;
+        }
+
+        Some(&self.name[..self.extension_dot.unwrap_or(self.name.len())])
+    }
+
+    /// Check if the extension of the file matches `extension` ignoring ASCII case.
+    /// A leading `.` in `extension` is ignored
+    pub fn has_extension(&self, extension: &str) -> bool {
+        let extension = extension.strip_prefix('.').unwrap_or(extension);
+
+        self.extension()
+            .is_some_and(|file_extension| file_extension.eq_ignore_ascii_case(extension))
+    }
+
+    /// Get the path of the file. Only the directory of the file is stored, shared with the other
+    /// files of the directory, so the path is built from [Self::parent] and [Self::name_os]
+    /// on each call. Use those two to avoid the allocation, eg when comparing many paths
+    pub fn path(&self) -> Cow<'_, Path> {
+        match self.parent.as_os_str().is_empty() {
+            true => Cow::Borrowed(Path::new(self.name_os())),
+            false => Cow::Owned(self.parent.join(self.name_os())),
+        }
+    }
+
+    /// The components of [Self::path] without building it
+    fn path_components(&self) -> impl Iterator<Item = Component<'_>> {
+        self.parent
+            .components()
+            .chain(Path::new(self.name_os()).components())
+    }
+
+    /// Compares the paths of two files like [Path::cmp] does without building them
+    pub(crate) fn cmp_path(&self, other: &FileMetadata) -> std::cmp::Ordering {
+        self.path_components().cmp(other.path_components())
+    }
+
+    /// Get the size of the file
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the size of the file in human readable format
+    pub fn formatted_size(&self) -> String {
+        FsUtils::size_to_bytes(self.size)
+    }
+
+    /// Get the size of the file in human readable format in the units of `style`
+    pub fn formatted_size_with(&self, style: SizeStyle) -> String {
+        FsUtils::format_size(self.size, style)
+    }
+
+    /// Get the space allocated on disk for the file like `du` reports it, this is smaller
+    /// than [Self::size] for sparse files and larger for small files.
+    /// On platforms without block information this is the same as [Self::size]
+    pub fn disk_usage(&self) -> u64 {
+        self.disk_usage
+    }
+
+    /// Get [Self::disk_usage] in human readable format
+    pub fn disk_usage_formatted(&self) -> String {
+        FsUtils::size_to_bytes(self.disk_usage)
+    }
+
+    /// Get the TAI64N timestamp when the file was last accessed
+    pub fn accessed(&self) -> Option<Tai64N> {
+        self.accessed
+    }
+
+    /// Get the TAI64N timestamp when the file was last modified
+    pub fn modified(&self) -> Option<Tai64N> {
+        self.modified
+    }
+
+    /// Get the TAI64N timestamp when the file was last created
+    pub fn created(&self) -> Option<Tai64N> {
+        self.created
+    }
+
+    /// Get the [SystemTime] when the file was last accessed
+    pub fn accessed_systemtime(&self) -> Option<SystemTime> {
+        Some(FsUtils::tai64_to_system_time(&self.accessed?))
+    }
+
+    /// Get the seconds since UNIX EPOCH when the file was last accessed
+    pub fn accessed_unix_secs(&self) -> Option<u64> {
+        FsUtils::tai64_to_unix_secs(&self.accessed?)
+    }
+
+    /// Get the milliseconds since UNIX EPOCH when the file was last accessed
+    pub fn accessed_unix_millis(&self) -> Option<u64> {
+        FsUtils::tai64_to_unix_millis(&self.accessed?)
+    }
+
+    /// Get the [SystemTime] when the file was last modified
+    pub fn modified_systemtime(&self) -> Option<SystemTime> {
+        Some(FsUtils::tai64_to_system_time(&self.modified?))
+    }
+
+    /// Get the seconds since UNIX EPOCH when the file was last modified
+    pub fn modified_unix_secs(&self) -> Option<u64> {
+        FsUtils::tai64_to_unix_secs(&self.modified?)
+    }
+
+    /// Get the milliseconds since UNIX EPOCH when the file was last modified
+    pub fn modified_unix_millis(&self) -> Option<u64> {
+        FsUtils::tai64_to_unix_millis(&self.modified?)
+    }
+
+    /// Get the [SystemTime] when the file was created
+    pub fn created_systemtime(&self) -> Option<SystemTime> {
+        Some(FsUtils::tai64_to_system_time(&self.created?))
+    }
+
+    /// Get the seconds since UNIX EPOCH when the file was created
+    pub fn created_unix_secs(&self) -> Option<u64> {
+        FsUtils::tai64_to_unix_secs(&self.created?)
+    }
+
+    /// Get the milliseconds since UNIX EPOCH when the file was created
+    pub fn created_unix_millis(&self) -> Option<u64> {
+        FsUtils::tai64_to_unix_millis(&self.created?)
+    }
+
+    /// Check if the file was modified after `time`, a file without
+    /// a modification time is older than any time
+    pub fn modified_after(&self, time: &Tai64N) -> bool {
+        self.modified
+            .is_some_and(|modified| FsUtils::tai64_cmp(&modified, time).is_gt())
+    }
+
+    /// Check if the file was modified before `time`, a file without
+    /// a modification time is older than any time
+    pub fn modified_before(&self, time: &Tai64N) -> bool {
+        self.modified
+            .is_none_or(|modified| FsUtils::tai64_cmp(&modified, time).is_lt())
+    }
+
+    /// Check if the file was modified after `other`, a file without
+    /// a modification time is older than any other file
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     let newest = outcome.files().iter().reduce(|newest, file| {
+    ///         if file.newer_than(newest) {
+    ///             file
+    ///         } else {
+    ///             newest
+    ///         }
+    ///     });
+    ///     dbg!(newest.map(|file| file.name()));
+    /// });
+    /// ```
+    pub fn newer_than(&self, other: &FileMetadata) -> bool {
+        // [Option::None] is less than any [Option::Some]
+        self.modified > other.modified
+    }
+
+    /// Get the time passed since the file was modified, zero for a modification time
+    /// in the future. Returns [Option::None] if the modification time is not known
+    pub fn age(&self) -> Option<Duration> {
+        Some(
+            Tai64N::now()
+                .duration_since(&self.modified?)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Get the TAI64N timestamp when the metadata of the file was last changed (the unix ctime),
+    /// for example by writing to it or changing its permissions.
+    /// Returns [Option::None] on platforms other than unix
+    #[cfg(feature = "time")]
+    pub fn changed(&self) -> Option<Tai64N> {
+        self.changed
+    }
+
+    /// Get the timestamp in local time in 24 hour format when the file was last accessed
+    #[cfg(feature = "time")]
+    pub fn accessed_24hr(&self) -> Option<DateTimeString<'a>> {
+        Some(FsUtils::tai64_to_local_hrs(&self.accessed?))
+    }
+
+    /// Get the timestamp in local time in 12 hour format when the file was last accessed
+    #[cfg(feature = "time")]
+    pub fn accessed_am_pm(&self) -> Option<DateTimeString<'a>> {
+        Some(FsUtils::tai64_to_local_am_pm(&self.accessed?))
+    }
+
+    /// Get the time passed since access of a file eg `3 sec ago`
+    #[cfg(feature = "time")]
+    pub fn accessed_humatime(&self) -> Option<String> {
+        FsUtils::tai64_now_duration_to_humantime(&self.accessed?)
+    }
+
+    /// Get the time passed since access of a file rounded to the largest unit eg `2 days ago`,
+    /// like [FsUtils::tai64_now_to_coarse_humantime] describes it
+    #[cfg(feature = "time")]
+    pub fn accessed_humantime_coarse(&self) -> Option<String> {
+        Some(FsUtils::tai64_now_to_coarse_humantime(&self.accessed?))
+    }
+
+    /// Get the RFC 3339 timestamp in UTC when the file was last accessed eg `2024-01-31T08:15:00.000000000Z`
+    #[cfg(feature = "time")]
+    pub fn accessed_rfc3339(&self) -> Option<String> {
+        Some(FsUtils::tai64_to_rfc3339(&self.accessed?))
+    }
+
+    /// Get the timestamp when the file was last accessed with the date and time formatted
+    /// like [FsUtils::tai64_to_formatted] does. Returns [Option::None] if the time is not known
+    #[cfg(feature = "time")]
+    pub fn accessed_formatted(
+        &self,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<Option<DateTimeString<'a>>, fmt::Error> {
+        self.accessed
+            .map(|time| FsUtils::tai64_to_formatted(&time, date_format, time_format))
+            .transpose()
+    }
+
+    /// Get the timestamp in local time in 24 hour format when the file was last modified
+    #[cfg(feature = "time")]
+    pub fn modified_24hr(&self) -> Option<DateTimeString<'a>> {
+        Some(FsUtils::tai64_to_local_hrs(&self.modified?))
+    }
+
+    /// Get the timestamp in local time in 12 hour format when the file was last modified
+    #[cfg(feature = "time")]
+    pub fn modified_am_pm(&self) -> Option<DateTimeString<'a>> {
+        Some(FsUtils::tai64_to_local_am_pm(&self.modified?))
+    }
+
+    /// Get the time passed since modification of a file eg `3 sec ago`
+    #[cfg(feature = "time")]
+    pub fn modified_humatime(&self) -> Option<String> {
+        FsUtils::tai64_now_duration_to_humantime(&self.modified?)
+    }
+
+    /// Get the time passed since modification of a file rounded to the largest unit eg `2 days ago`,
+    /// like [FsUtils::tai64_now_to_coarse_humantime] describes it
+    #[cfg(feature = "time")]
+    pub fn modified_humantime_coarse(&self) -> Option<String> {
+        Some(FsUtils::tai64_now_to_coarse_humantime(&self.modified?))
+    }
+
+    /// Get the RFC 3339 timestamp in UTC when the file was last modified eg `2024-01-31T08:15:00.000000000Z`
+    #[cfg(feature = "time")]
+    pub fn modified_rfc3339(&self) -> Option<String> {
+        Some(FsUtils::tai64_to_rfc3339(&self.modified?))
+    }
+
+    /// Get the timestamp when the file was last modified with the date and time formatted
+    /// like [FsUtils::tai64_to_formatted] does. Returns [Option::None] if the time is not known
+    #[cfg(feature = "time")]
+    pub fn modified_formatted(
+        &self,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<Option<DateTimeString<'a>>, fmt::Error> {
+        self.modified
+            .map(|time| FsUtils::tai64_to_formatted(&time, date_format, time_format))
+            .transpose()
+    }
+
+    /// Get the timestamp in local time in 24 hour format when the file was created
+    #[cfg(feature = "time")]
+    pub fn created_24hr(&self) -> Option<DateTimeString<'a>> {
+        Some(FsUtils::tai64_to_local_hrs(&self.created?))
+    }
+
+    /// Get the timestamp in local time in 12 hour format when the file was created
+    #[cfg(feature = "time")]
+    pub fn created_am_pm(&self) -> Option<DateTimeString<'a>> {
+        Some(FsUtils::tai64_to_local_am_pm(&self.created?))
+    }
+
+    /// Get the time passed since file was created of a file eg `3 sec ago`
+    #[cfg(feature = "time")]
+    pub fn created_humatime(&self) -> Option<String> {
+        FsUtils::tai64_now_duration_to_humantime(&self.created?)
+    }
+
+    /// Get the time passed since the file was created rounded to the largest unit eg `2 days ago`,
+    /// like [FsUtils::tai64_now_to_coarse_humantime] describes it
+    #[cfg(feature = "time")]
+    pub fn created_humantime_coarse(&self) -> Option<String> {
+        Some(FsUtils::tai64_now_to_coarse_humantime(&self.created?))
+    }
+
+    /// Get the RFC 3339 timestamp in UTC when the file was created eg `2024-01-31T08:15:00.000000000Z`
+    #[cfg(feature = "time")]
+    pub fn created_rfc3339(&self) -> Option<String> {
+        Some(FsUtils::tai64_to_rfc3339(&self.created?))
+    }
+
+    /// Get the timestamp when the file was created with the date and time formatted
+    /// like [FsUtils::tai64_to_formatted] does. Returns [Option::None] if the time is not known
+    #[cfg(feature = "time")]
+    pub fn created_formatted(
+        &self,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<Option<DateTimeString<'a>>, fmt::Error> {
+        self.created
+            .map(|time| FsUtils::tai64_to_formatted(&time, date_format, time_format))
+            .transpose()
+    }
+
+    /// Get the timestamp in local time in 24 hour format when the file was last changed
+    #[cfg(feature = "time")]
+    pub fn changed_24hr(&self) -> Option<DateTimeString<'a>> {
+        Some(FsUtils::tai64_to_local_hrs(&self.changed?))
+    }
+
+    /// Get the timestamp in local time in 12 hour format when the file was last changed
+    #[cfg(feature = "time")]
+    pub fn changed_am_pm(&self) -> Option<DateTimeString<'a>> {
+        Some(FsUtils::tai64_to_local_am_pm(&self.changed?))
+    }
+
+    /// Get the time passed since the file was last changed eg `3 sec ago`
+    #[cfg(feature = "time")]
+    pub fn changed_humatime(&self) -> Option<String> {
+        FsUtils::tai64_now_duration_to_humantime(&self.changed?)
+    }
+
+    /// Get the time passed since the file was last changed rounded to the largest unit eg `2 days ago`,
+    /// like [FsUtils::tai64_now_to_coarse_humantime] describes it
+    #[cfg(feature = "time")]
+    pub fn changed_humantime_coarse(&self) -> Option<String> {
+        Some(FsUtils::tai64_now_to_coarse_humantime(&self.changed?))
+    }
+
+    /// Get the RFC 3339 timestamp in UTC when the file was last changed eg `2024-01-31T08:15:00.000000000Z`
+    #[cfg(feature = "time")]
+    pub fn changed_rfc3339(&self) -> Option<String> {
+        Some(FsUtils::tai64_to_rfc3339(&self.changed?))
+    }
+
+    /// Get the timestamp when the file was last changed with the date and time formatted
+    /// like [FsUtils::tai64_to_formatted] does. Returns [Option::None] if the time is not known
+    #[cfg(feature = "time")]
+    pub fn changed_formatted(
+        &self,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<Option<DateTimeString<'a>>, fmt::Error> {
+        self.changed
+            .map(|time| FsUtils::tai64_to_formatted(&time, date_format, time_format))
+            .transpose()
+    }
+
+    /// Is the file read only
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The unix permission mode of the file including the file type bits.
+    /// Returns [Option::None] on platforms other than unix
+    #[cfg(feature = "unix")]
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    /// The user ID of the owner of the file.
+    /// Returns [Option::None] on platforms other than unix
+    #[cfg(feature = "unix")]
+    pub fn owner_uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    /// The group ID of the owner of the file.
+    /// Returns [Option::None] on platforms other than unix
+    #[cfg(feature = "unix")]
+    pub fn group_gid(&self) -> Option<u32> {
+        self.gid
+    }
+
+    /// The inode number of the file.
+    /// Returns [Option::None] on platforms other than unix
+    #[cfg(feature = "unix")]
+    pub fn inode(&self) -> Option<u64> {
+        self.inode
+    }
+
+    /// The ID of the device containing the file.
+    /// Returns [Option::None] on platforms other than unix
+    #[cfg(feature = "unix")]
+    pub fn device(&self) -> Option<u64> {
+        self.device
+    }
+
+    /// The number of hard links to the file.
+    /// Returns [Option::None] on platforms other than unix
+    #[cfg(feature = "unix")]
+    pub fn nlink(&self) -> Option<u64> {
+        self.nlink
+    }
+
+    /// The permissions of the file formatted like `ls -l` does eg `rwxr-xr-x`,
+    /// including the setuid, setgid and sticky bits.
+    /// Returns [Option::None] on platforms other than unix
+    #[cfg(feature = "unix")]
+    pub fn permissions_string(&self) -> Option<String> {
+        let mode = self.mode?;
+
+        let permissions = [
+            (0o400, 'r', None),
+            (0o200, 'w', None),
+            (0o100, 'x', Some((0o4000, 's', 'S'))),
+            (0o040, 'r', None),
+            (0o020, 'w', None),
+            (0o010, 'x', Some((0o2000, 's', 'S'))),
+            (0o004, 'r', None),
+            (0o002, 'w', None),
+            (0o001, 'x', Some((0o1000, 't', 'T'))),
+        ]
+        .iter()
+        .map(|(bit, symbol, special)| {
+            let is_set = mode & bit != 0;
+
+            match special {
+                Some((special_bit, with_bit, without_bit)) if mode & special_bit != 0 => {
+                    if is_set {
+                        *with_bit
+                    } else {
+                        *without_bit
+                    }
+                }
+                _ if is_set => *symbol,
+                _ => '-',
+            }
+        })
+        .collect::<String>();
+
+        Some(permissions)
+    }
+
+    /// Get the type of the file as read while scanning, without following symbolic links
+    pub fn kind(&self) -> FileKind {
+        self.kind
+    }
+
+    /// Is the file a symbolic link. The size and times of a symbolic link
+    /// are those of the link itself, not of its target which may not exist
+    pub fn symlink(&self) -> bool {
+        self.symlink
+    }
+
+    /// Get the digest of the content of the file in lowercase hexadecimal,
+    /// only set if the scan used [DirMetadata::hash_files]
+    #[cfg(feature = "hash")]
+    pub fn digest(&self) -> Option<&str> {
+        self.digest.as_deref()
+    }
+
+    /// The path a symbolic link points to as it is stored in the link,
+    /// a relative target is relative to the directory containing the link.
+    /// Returns [Option::None] if the file is not a symbolic link
+    pub fn link_target(&self) -> Option<&Path> {
+        self.link_target.as_deref()
+    }
+
+    /// Is the file a symbolic link whose target did not exist when it was scanned
+    pub fn is_broken_link(&self) -> bool {
+        self.broken_link
+    }
+
+    /// Get the format of the current file.
+    /// If the scan used [DirMetadata::defer_format_detection] the file is opened
+    /// to detect its format the first time this is called, blocking the current thread
+    pub fn file_format(&self) -> &FileFormat {
+        self.file_format
+            .get_or_init(|| FileFormat::from_file(self.path()).unwrap_or_default())
+    }
+
+    /// Get the format of the current file only if it was already detected,
+    /// without opening the file
+    pub fn detected_format(&self) -> Option<&FileFormat> {
+        self.file_format.get()
+    }
+
+    /// Get the format of the current file, detecting it on the blocking thread pool
+    /// if the scan used [DirMetadata::defer_format_detection] and it has not been detected yet
+    pub async fn file_format_async(&self) -> &FileFormat {
+        if let Some(file_format) = self.file_format.get() {
+            return file_format;
+        }
+
+        let cloned_path = self.path().into_owned();
+        let file_format = unblock(move || FileFormat::from_file(cloned_path))
+            .await
+            .unwrap_or_default();
+
+        self.file_format.get_or_init(|| file_format)
+    }
+
+    /// Open the file for reading. Returns the error of the operating system
+    /// if the file was removed or cannot be read anymore since the scan
+    pub fn open(&self) -> io::Result<std::fs::File> {
+        std::fs::File::open(self.path())
+    }
+
+    /// Same as [Self::open] without blocking the current thread
+    pub async fn open_async(&self) -> io::Result<smol::fs::File> {
+        smol::fs::File::open(self.path()).await
+    }
+
+    /// Read at most the first `max_bytes` bytes of the file, eg to preview it or to check
+    /// if it is text or binary. Fewer bytes are returned if the file is shorter, it is
+    /// read again so the content may have changed since the scan
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///     let lib_rs = outcome.get_file("lib.rs").unwrap();
+    ///
+    ///     assert_eq!(b"#![", lib_rs.read_head(3).unwrap().as_slice());
+    /// });
+    /// ```
+    pub fn read_head(&self, max_bytes: usize) -> io::Result<Vec<u8>> {
+        let mut head = Vec::with_capacity(self.head_capacity(max_bytes));
+        self.open()?.take(max_bytes as u64).read_to_end(&mut head)?;
+
+        Ok(head)
+    }
+
+    /// Same as [Self::read_head] without blocking the current thread
+    pub async fn read_head_async(&self, max_bytes: usize) -> io::Result<Vec<u8>> {
+        let mut head = Vec::with_capacity(self.head_capacity(max_bytes));
+        self.open_async()
+            .await?
+            .take(max_bytes as u64)
+            .read_to_end(&mut head)
+            .await?;
+
+        Ok(head)
+    }
+
+    /// The bytes to allocate for [Self::read_head], at most the size seen by the scan
+    /// so that a large `max_bytes` does not allocate more than the file needs
+    fn head_capacity(&self, max_bytes: usize) -> usize {
+        max_bytes.min(usize::try_from(self.size).unwrap_or(usize::MAX))
+    }
+}
+
+/// A single line like `ls -l` prints it with the type and permissions of the file, its size,
+/// the time it was last modified in UTC with the `time` feature and its name.
+/// The permissions are only known with the `unix` feature and are `?????????` otherwise.
+/// A width like `{:8}` right-aligns the size to that many characters
+impl fmt::Display for FileMetadata<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            FileKind::Symlink => 'l',
+            FileKind::Fifo => 'p',
+            FileKind::Socket => 's',
+            FileKind::CharDevice => 'c',
+            FileKind::BlockDevice => 'b',
+            FileKind::Regular | FileKind::Unknown => '-',
+        };
+        #[cfg(feature = "unix")]
+        let permissions = self.permissions_string();
+        #[cfg(not(feature = "unix"))]
+        let permissions = Option::<String>::None;
+
+        write!(
+            f,
+            "{kind}{} {:>width$}",
+            permissions.as_deref().unwrap_or("?????????"),
+            self.formatted_size(),
+            width = f.width().unwrap_or_default()
+        )?;
+
+        #[cfg(feature = "time")]
+        {
+            let modified = self.modified.map(|modified| {
+                chrono::DateTime::<chrono::Utc>::from(modified.to_system_time())
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            });
+            write!(f, " {:<16}", modified.as_deref().unwrap_or("-"))?;
+        }
+
+        write!(f, " {}", self.name)?;
+
+        if let Some(link_target) = self.link_target.as_ref() {
+            write!(f, " -> {}", link_target.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The metadata of a sub-directory found by a scan
+#[derive(Debug, PartialEq, Eq, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirEntryMetadata {
+    name: String,
+    path: PathBuf,
+    depth: usize,
+    #[cfg(feature = "time")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::tai64n_option"))]
+    modified: Option<Tai64N>,
+    #[cfg(feature = "time")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::tai64n_option"))]
+    created: Option<Tai64N>,
+    file_count: Option<usize>,
+    dir_count: Option<usize>,
+}
+
+impl DirEntryMetadata {
+    #[allow(unused_variables)]
+    fn new(path: &Path, depth: usize, meta: Option<&std::fs::Metadata>) -> Self {
+        Self {
+            name: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            path: path.to_path_buf(),
+            depth,
+            #[cfg(feature = "time")]
+            modified: FsUtils::maybe_time(meta.and_then(|meta| meta.modified().ok())),
+            #[cfg(feature = "time")]
+            created: FsUtils::maybe_time(meta.and_then(|meta| meta.created().ok())),
+            file_count: None,
+            dir_count: None,
+        }
+    }
+
+    /// Get the name of the directory
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the path of the directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the depth of the directory containing this one,
+    /// `0` if it is directly in the scanned directory
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Get the TAI64N timestamp when the directory was last modified
+    #[cfg(feature = "time")]
+    pub fn modified(&self) -> Option<Tai64N> {
+        self.modified
+    }
+
+    /// Get the TAI64N timestamp when the directory was created
+    #[cfg(feature = "time")]
+    pub fn created(&self) -> Option<Tai64N> {
+        self.created
+    }
+
+    /// Get the number of files directly inside the directory, including the filtered out ones.
+    /// Returns [Option::None] if the directory was not read, for example beyond [DirMetadata::max_depth]
+    pub fn file_count(&self) -> Option<usize> {
+        self.file_count
+    }
+
+    /// Get the number of sub-directories directly inside the directory, including
+    /// the filtered out ones. Returns [Option::None] if the directory was not read
+    pub fn dir_count(&self) -> Option<usize> {
+        self.dir_count
+    }
+}
+
+/// An error encountered while accessing a file or sub-directory
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirError<'a> {
+    /// The path to the sub-directory or file where the error occurred
+    pub path: PathBuf,
+    /// The kind of error that occurred based on [std::io::ErrorKind]
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::error_kind"))]
+    pub error: ErrorKind,
+    /// The error code of the operating system like `13` for `EACCES`,
+    /// [Option::None] if the error did not come from the operating system
+    pub os_error: Option<i32>,
+    /// The formatted error as a [String]
+    pub display: CowStr<'a>,
+}
+
+impl DirError<'_> {
+    fn into_owned(self) -> DirError<'static> {
+        DirError {
+            path: self.path,
+            error: self.error,
+            os_error: self.os_error,
+            display: Cow::Owned(self.display.into_owned()),
+        }
+    }
+}
+
+/// The path, the kind of error and the message eg
+/// `/tmp/private: permission denied: Unable to read the directory (os error 13)`
+impl fmt::Display for DirError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            self.path.display(),
+            self.error,
+            self.display
+        )?;
+
+        if let Some(os_error) = self.os_error {
+            write!(f, " (os error {os_error})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for DirError<'_> {}
+
+#[cfg(test)]
+mod sanity_checks {
+    use crate::{
+        test_utils::{file_names, nested_fixture, scan, LookupProbe},
+        DirMetadata,
+    };
+    use file_format::FileFormat;
+    use smol::io::ErrorKind;
+    use std::{
+        fs,
+        path::Path,
+        sync::{atomic::Ordering, Arc, Mutex, OnceLock},
+    };
+
+    #[test]
+    fn max_depth() {
+        let root = nested_fixture();
+
+        let outcome = scan(root.path(), None);
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], file_names(&outcome));
+        assert_eq!(6, outcome.size());
+
+        let outcome = scan(root.path(), Some(0));
+        assert_eq!(vec!["a.txt"], file_names(&outcome));
+        assert_eq!(vec![root.path().join("one")], outcome.directories());
+        assert_eq!(1, outcome.size());
+
+        let outcome = scan(root.path(), Some(1));
+        assert_eq!(vec!["a.txt", "b.txt"], file_names(&outcome));
+        assert_eq!(2, outcome.directories().len());
+        assert!(outcome
+            .directories()
+            .contains(&root.path().join("one").join("two")));
+
+        let outcome = scan(root.path(), Some(2));
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], file_names(&outcome));
+    }
+
+    #[test]
+    fn glob_filters() {
+        let root = nested_fixture();
+        fs::write(root.path().join("one").join("b.rs"), b"fn").unwrap();
+        let root_path = root.path().to_str().unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root_path)
+                .unwrap()
+                .include_glob("*.rs")
+                .unwrap()
+                .include_glob("**/c.txt")
+                .unwrap()
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["b.rs", "c.txt"], file_names(&outcome));
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root_path)
+                .unwrap()
+                .exclude_glob("one/two")
+                .unwrap()
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["a.txt", "b.rs", "b.txt"], file_names(&outcome));
+        assert_eq!(vec![root.path().join("one")], outcome.directories());
+
+        assert!(DirMetadata::new(root_path)
+            .unwrap()
+            .exclude_glob("a[")
+            .is_err());
+    }
+
+    #[test]
+    fn skip_dir_names() {
+        let root = nested_fixture();
+        fs::create_dir_all(root.path().join("target").join("debug")).unwrap();
+        fs::write(root.path().join("target").join("debug").join("d.txt"), b"d").unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .skip_dir_names(["target", "two"])
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["a.txt", "b.txt"], file_names(&outcome));
+        assert_eq!(vec![root.path().join("one")], outcome.directories());
+
+        let mut skipped = outcome.skipped().to_vec();
+        skipped.sort();
+        assert_eq!(
+            vec![
+                root.path().join("one").join("two"),
+                root.path().join("target")
+            ],
+            skipped
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn modified_after() {
+        use std::time::{Duration, SystemTime};
+
+        let root = nested_fixture();
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(root.path().join("a.txt"))
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+
+        let cutoff = tai64::Tai64N::from_system_time(&(hour_ago + Duration::from_secs(60)));
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .modified_after(cutoff)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["b.txt", "c.txt"], file_names(&outcome));
+        // The skipped `a.txt` still counts towards the size of the directory
+        assert_eq!(6, outcome.size());
+        assert_eq!(Some(6), outcome.dir_size(root.path()));
+        assert_eq!(
+            5,
+            outcome.files().iter().map(|file| file.size()).sum::<u64>()
+        );
+
+        let outcome = scan(root.path(), None);
+        assert_eq!(
+            2,
+            outcome.files_modified_within(Duration::from_secs(60)).len()
+        );
+        assert_eq!(
+            3,
+            outcome
+                .files_modified_within(Duration::from_secs(7200))
+                .len()
+        );
+    }
+
+    #[test]
+    fn size_range() {
+        let root = nested_fixture();
+        let root_path = root.path().to_str().unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root_path)
+                .unwrap()
+                .min_size(2)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["b.txt", "c.txt"], file_names(&outcome));
+        assert_eq!(6, outcome.size());
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root_path)
+                .unwrap()
+                .min_size(2)
+                .max_size(2)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["b.txt"], file_names(&outcome));
+        assert_eq!(6, outcome.size());
+    }
+
+    #[test]
+    fn extensions() {
+        let root = nested_fixture();
+        fs::write(root.path().join("image.PNG"), b"png").unwrap();
+        fs::write(root.path().join("one").join("photo.jpg"), b"jpg").unwrap();
+        fs::write(root.path().join("one").join("png"), b"no extension").unwrap();
+        let root_path = root.path().to_str().unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root_path)
+                .unwrap()
+                .filter_extensions(&["png", ".JPG"])
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["image.PNG", "photo.jpg"], file_names(&outcome));
+
+        let outcome = scan(root.path(), None);
+        let pngs = outcome.files_with_extension("png");
+        assert_eq!(1, pngs.len());
+        assert_eq!("image.PNG", pngs[0].name());
+        assert_eq!(3, outcome.files_with_extension("TXT").len());
+        assert!(outcome.files_with_extension("gif").is_empty());
+    }
+
+    #[test]
+    fn filter_with() {
+        let root = nested_fixture();
+        let mut calls = 0usize;
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .filter_with(move |path, is_dir| {
+                    calls += 1;
+                    assert!(calls <= 4);
+
+                    !(path.ends_with("a.txt") || is_dir && path.ends_with("two"))
+                })
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["b.txt"], file_names(&outcome));
+        assert_eq!(vec![root.path().join("one")], outcome.directories());
+    }
+
+    #[test]
+    fn cancellable() {
+        let root = nested_fixture();
+
+        let (scan, _cancel) = DirMetadata::new(root.path().to_str().unwrap())
+            .unwrap()
+            .cancellable();
+        let outcome = smol::block_on(scan.dir_metadata()).unwrap();
+        assert!(!outcome.was_cancelled());
+        assert_eq!(3, outcome.files().len());
+
+        let (scan, cancel) = DirMetadata::new(root.path().to_str().unwrap())
+            .unwrap()
+            .cancellable();
+        let cancel_in_filter = cancel.clone();
+        let scan = scan.filter_with(move |path, _| {
+            if path.ends_with("one") {
+                cancel_in_filter.cancel();
+            }
+
+            true
+        });
+        let outcome = smol::block_on(scan.dir_metadata()).unwrap();
+        assert!(outcome.was_cancelled());
+        assert!(cancel.is_cancelled());
+        assert!(!file_names(&outcome).contains(&"b.txt"));
+        assert!(!file_names(&outcome).contains(&"c.txt"));
+    }
+
+    #[test]
+    fn on_progress() {
+        let root = nested_fixture();
+        let reports = Arc::new(Mutex::new(Vec::<(usize, usize, u64)>::new()));
+        let reports_in_hook = reports.clone();
+
+        smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .shallow()
+                .on_progress(1, move |progress| {
+                    reports_in_hook.lock().unwrap().push((
+                        progress.files_seen,
+                        progress.directories_seen,
+                        progress.bytes,
+                    ))
+                })
+                .dir_metadata(),
+        )
+        .unwrap();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(2, reports.len());
+        assert_eq!((0, 0, 0), reports[0]);
+        assert!(reports[1] == (1, 0, 1) || reports[1] == (0, 1, 0));
+    }
+
+    #[test]
+    fn new_multi() {
+        let first = nested_fixture();
+        let second = nested_fixture();
+        let first_root = fs::canonicalize(first.path()).unwrap();
+        let second_root = fs::canonicalize(second.path()).unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new_multi([
+                first.path().to_path_buf(),
+                second.path().join("one"),
+                first.path().join("one").join("two"),
+                first.path().to_path_buf(),
+            ])
+            .dir_metadata(),
+        )
+        .unwrap();
+
+        let mut expected_roots = vec![first_root.clone(), second_root.join("one")];
+        expected_roots.sort();
+        assert_eq!(expected_roots, outcome.roots());
+        assert_eq!(
+            vec!["a.txt", "b.txt", "b.txt", "c.txt", "c.txt"],
+            file_names(&outcome)
+        );
+        assert_eq!(11, outcome.size());
+
+        let second_one = second_root.join("one");
+        outcome.files().iter().for_each(|file| {
+            let expected_root = if file.path().starts_with(&first_root) {
+                first_root.as_path()
+            } else {
+                second_one.as_path()
+            };
+            assert_eq!(Some(expected_root), outcome.root_of(file));
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn same_filesystem() {
+        let root = nested_fixture();
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .same_filesystem(true)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], file_names(&outcome));
+        assert!(outcome.skipped().is_empty());
+
+        let outcome = smol::block_on(
+            DirMetadata::new("/")
+                .unwrap()
+                .same_filesystem(true)
+                .shallow()
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert!(outcome
+            .skipped()
+            .contains(&std::path::PathBuf::from("/proc")));
+    }
+
+    #[test]
+    fn stream_files() {
+        use smol::stream::StreamExt;
+
+        let root = nested_fixture();
+
+        let mut names = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .stream_files()
+                .map(|file| file.unwrap().name().to_owned())
+                .collect::<Vec<String>>(),
+        );
+        names.sort();
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], names);
+
+        let first = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .stream_files()
+                .next(),
+        );
+        assert!(first.unwrap().is_ok());
+
+        let removed = root.path().join("removed");
+        fs::create_dir(&removed).unwrap();
+        let scan = DirMetadata::new(removed.to_str().unwrap()).unwrap();
+        fs::remove_dir(&removed).unwrap();
+        let outcome = smol::block_on(scan.stream_files().collect::<Vec<_>>());
+        assert_eq!(1, outcome.len());
+        assert_eq!(ErrorKind::NotFound, outcome[0].as_ref().unwrap_err().error);
+    }
+
+    #[test]
+    fn scan_to() {
+        let root = nested_fixture();
+
+        let (sender, receiver) = smol::channel::unbounded();
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .scan_to(sender),
+        )
+        .unwrap();
+        let mut names = std::iter::from_fn(|| receiver.try_recv().ok())
+            .map(|file| file.name().to_owned())
+            .collect::<Vec<String>>();
+        names.sort();
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], names);
+        assert!(outcome.files().is_empty());
+        assert_eq!(2, outcome.directories().len());
+        assert_eq!(6, outcome.size());
+        assert!(receiver.is_closed());
+
+        let (sender, receiver) = smol::channel::unbounded();
+        drop(receiver);
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .scan_to(sender),
+        )
+        .unwrap();
+        assert!(outcome.was_cancelled());
+        assert!(outcome
+            .errors()
+            .iter()
+            .any(|error| error.display == crate::SENDER_CHANNEL_ERROR));
+    }
+
+    #[test]
+    fn rescan() {
+        let root = nested_fixture();
+
+        let mut previous = scan(root.path(), None);
+        // Formats that detection would never produce show which files were reused
+        previous
+            .files
+            .iter_mut()
+            .for_each(|file| file.file_format = OnceLock::from(FileFormat::ArbitraryBinaryData));
+
+        fs::write(root.path().join("a.txt"), b"changed").unwrap();
+        fs::write(root.path().join("new.txt"), b"new").unwrap();
+        fs::remove_file(root.path().join("one").join("b.txt")).unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .rescan(&previous)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["a.txt", "c.txt", "new.txt"], file_names(&outcome));
+
+        let formats = outcome
+            .files()
+            .iter()
+            .map(|file| (file.name(), file.file_format()))
+            .collect::<std::collections::HashMap<&str, &FileFormat>>();
+        assert_eq!(&FileFormat::PlainText, formats["a.txt"]);
+        assert_eq!(&FileFormat::ArbitraryBinaryData, formats["c.txt"]);
+        assert_eq!(&FileFormat::PlainText, formats["new.txt"]);
+    }
+
+    #[test]
+    fn read_only() {
+        let root = nested_fixture();
+        let file_path = root.path().join("a.txt");
+
+        let mut permissions = fs::metadata(&file_path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&file_path, permissions.clone()).unwrap();
+
+        let outcome = scan(root.path(), None);
+        let read_only = outcome
+            .files()
+            .iter()
+            .filter(|file| file.read_only())
+            .map(|file| file.name())
+            .collect::<Vec<&str>>();
+        assert_eq!(vec!["a.txt"], read_only);
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&file_path, permissions).unwrap();
+    }
+
+    #[test]
+    fn walk() {
+        let root = nested_fixture();
+
+        let mut walk = DirMetadata::new(root.path().to_str().unwrap())
+            .unwrap()
+            .walk();
+        let mut names = walk
+            .by_ref()
+            .map(|file| file.unwrap().name().to_owned())
+            .collect::<Vec<String>>();
+        names.sort();
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], names);
+        assert!(walk.next().is_none());
+        assert!(walk.next().is_none());
+
+        let first_two = DirMetadata::new(root.path().to_str().unwrap())
+            .unwrap()
+            .include_glob("**/c.txt")
+            .unwrap()
+            .walk()
+            .take(2)
+            .collect::<Vec<_>>();
+        assert_eq!(1, first_two.len());
+        assert_eq!("c.txt", first_two[0].as_ref().unwrap().name());
+    }
+
+    #[test]
+    fn concurrency() {
+        let root = tempfile::tempdir().unwrap();
+        (0..10).for_each(|dir_index| {
+            let dir = root.path().join(format!("dir{dir_index}"));
+            fs::create_dir(&dir).unwrap();
+
+            (0..20).for_each(|file_index| {
+                let contents = match file_index % 2 {
+                    0 => b"%PDF-1.7\n".to_vec(),
+                    _ => b"plain text\n".to_vec(),
+                };
+                fs::write(dir.join(format!("file{file_index}")), contents).unwrap();
+            });
+        });
+        let root_path = root.path().to_str().unwrap();
+
+        let sequential_lookups = LookupProbe::track(&root.path().join("dir0"));
+        let sequential =
+            smol::block_on(DirMetadata::new(root_path).unwrap().dir_metadata()).unwrap();
+        let concurrent_lookups = LookupProbe::track(&root.path().join("dir0"));
+        let concurrent = smol::block_on(
+            DirMetadata::new(root_path)
+                .unwrap()
+                .concurrency(16)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(1, sequential_lookups.max_in_flight.load(Ordering::SeqCst));
+        assert!(concurrent_lookups.max_in_flight.load(Ordering::SeqCst) > 1);
+
+        let summary = |outcome: &DirMetadata| {
+            outcome
+                .files()
+                .iter()
+                .map(|file| (file.path().into_owned(), file.size(), *file.file_format()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(200, concurrent.files().len());
+        assert_eq!(summary(&sequential), summary(&concurrent));
+        assert_eq!(
+            100,
+            concurrent
+                .files()
+                .iter()
+                .filter(|file| *file.file_format() == FileFormat::PortableDocumentFormat)
+                .count()
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_dir_metadata() {
+        let summary = |outcome: &DirMetadata| {
+            let mut files = outcome
+                .files()
+                .iter()
+                .map(|file| (file.path().into_owned(), file.size(), *file.file_format()))
+                .collect::<Vec<_>>();
+            files.sort_by(|first, second| first.0.cmp(&second.0));
+
+            files
+        };
+
+        let serial = smol::block_on(DirMetadata::new("src").unwrap().dir_metadata()).unwrap();
+        let parallel = DirMetadata::new("src").unwrap().par_dir_metadata().unwrap();
+
+        assert!(!parallel.files().is_empty());
+        assert_eq!(serial.size(), parallel.size());
+        assert_eq!(summary(&serial), summary(&parallel));
+
+        // Enough directories for the threads of the pool to share them
+        let root = nested_fixture();
+        for outer in 0..8 {
+            for inner in 0..8 {
+                let dir = root
+                    .path()
+                    .join(format!("{outer}"))
+                    .join(format!("{inner}"));
+                fs::create_dir_all(&dir).unwrap();
+                fs::write(dir.join("file.txt"), vec![b'x'; outer * 8 + inner]).unwrap();
+            }
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.path(), root.path().join("one").join("loop")).unwrap();
+
+        let scan = || {
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .follow_symlinks(true)
+                .sorted(true)
+        };
+        let serial = smol::block_on(scan().dir_metadata()).unwrap();
+        let parallel = scan().par_dir_metadata().unwrap();
+
+        assert_eq!(67, parallel.file_count());
+        assert_eq!(serial.size(), parallel.size());
+        assert_eq!(summary(&serial), summary(&parallel));
+        assert_eq!(serial.directories(), parallel.directories());
+        assert_eq!(serial.dir_sizes(), parallel.dir_sizes());
+        assert_eq!(serial.max_depth_seen(), parallel.max_depth_seen());
+        assert_eq!(serial.errors(), parallel.errors());
+        #[cfg(unix)]
+        assert_eq!(1, parallel.error_count());
+    }
+
+    #[test]
+    fn deeply_nested() {
+        // Linux accepts paths up to 4096 bytes, macOS only up to 1024 and Windows up to 260
+        #[cfg(target_os = "linux")]
+        const DEPTH: usize = 1500;
+        #[cfg(not(target_os = "linux"))]
+        const DEPTH: usize = 80;
+
+        let root = tempfile::tempdir().unwrap();
+        let mut deepest = root.path().to_path_buf();
+        (0..DEPTH).for_each(|_| deepest.push("d"));
+        fs::create_dir_all(&deepest).unwrap();
+        fs::write(deepest.join("deep.txt"), b"deep").unwrap();
+
+        let outcome = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || {
+                let outcome = scan(root.path(), None);
+
+                (
+                    file_names(&outcome)
+                        .into_iter()
+                        .map(ToOwned::to_owned)
+                        .collect::<Vec<String>>(),
+                    outcome.directories().len(),
+                    outcome.errors().len(),
+                )
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!((vec!["deep.txt".to_owned()], DEPTH, 0), outcome);
+    }
+
+    #[test]
+    fn format_detection() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("small.pdf"), b"%PDF-1.7\n").unwrap();
+        fs::write(root.path().join("large.pdf"), b"%PDF-1.7\n0123456789").unwrap();
+        let root_path = root.path().to_str().unwrap();
+        let format_of = |outcome: &DirMetadata, name: &str| {
+            *outcome
+                .files()
+                .iter()
+                .find(|file| file.name() == name)
+                .unwrap()
+                .file_format()
+        };
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root_path)
+                .unwrap()
+                .format_detection_limit(10)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(
+            FileFormat::PortableDocumentFormat,
+            format_of(&outcome, "small.pdf")
+        );
+        assert_eq!(FileFormat::default(), format_of(&outcome, "large.pdf"));
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root_path)
+                .unwrap()
+                .detect_formats(false)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(FileFormat::default(), format_of(&outcome, "small.pdf"));
+        assert_eq!(FileFormat::default(), format_of(&outcome, "large.pdf"));
+    }
+
+    #[test]
+    fn defer_format_detection() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("first.pdf"), b"%PDF-1.7\n").unwrap();
+        fs::write(root.path().join("second.pdf"), b"%PDF-1.7\n").unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .defer_format_detection()
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert!(outcome
+            .files()
+            .iter()
+            .all(|file| file.file_format.get().is_none()));
+
+        let (first, second) = (&outcome.files()[0], &outcome.files()[1]);
+        assert_eq!(&FileFormat::PortableDocumentFormat, first.file_format());
+        assert!(first.file_format.get().is_some());
+        assert!(second.file_format.get().is_none());
+        assert_eq!(
+            &FileFormat::PortableDocumentFormat,
+            smol::block_on(second.file_format_async())
+        );
+        assert!(second.file_format.get().is_some());
+    }
+
+    #[test]
+    fn shallow() {
+        let root = nested_fixture();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .shallow()
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["a.txt"], file_names(&outcome));
+        assert_eq!(vec![root.path().join("one")], outcome.directories());
+    }
+
+    #[cfg(feature = "gitignore")]
+    #[test]
+    fn respect_gitignore() {
+        let root = nested_fixture();
+        fs::write(root.path().join(".gitignore"), "*.log\n/one/two/\n").unwrap();
+        fs::write(
+            root.path().join("one").join(".gitignore"),
+            "b.txt\n!keep.log\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("a.log"), b"log").unwrap();
+        fs::write(root.path().join("one").join("keep.log"), b"log").unwrap();
+        fs::create_dir_all(root.path().join(".git").join("info")).unwrap();
+        fs::write(
+            root.path().join(".git").join("info").join("exclude"),
+            ".git\n",
+        )
+        .unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .respect_gitignore(true)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![".gitignore", ".gitignore", "a.txt", "keep.log"],
+            file_names(&outcome)
+        );
+        assert_eq!(vec![root.path().join("one")], outcome.directories());
+        assert!(outcome.errors().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_symlinks_cycle() {
+        let root = nested_fixture();
+        std::os::unix::fs::symlink(root.path(), root.path().join("one").join("loop")).unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .follow_symlinks(true)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], file_names(&outcome));
+        assert_eq!(1, outcome.errors().len());
+        assert_eq!(
+            root.path().join("one").join("loop"),
+            outcome.errors()[0].path
+        );
+
+        let outcome = scan(root.path(), None);
+        assert_eq!(
+            vec!["a.txt", "b.txt", "c.txt", "loop"],
+            file_names(&outcome)
+        );
+        assert!(outcome.errors().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        let root = nested_fixture();
+        std::os::unix::fs::symlink(root.path().join("a.txt"), root.path().join("valid")).unwrap();
+        std::os::unix::fs::symlink(root.path().join("missing"), root.path().join("broken"))
+            .unwrap();
+        std::os::unix::fs::symlink(root.path().join("one"), root.path().join("linked_dir"))
+            .unwrap();
+
+        let outcome = scan(root.path(), None);
+        assert_eq!(
+            vec!["a.txt", "b.txt", "broken", "c.txt", "linked_dir", "valid"],
+            file_names(&outcome)
+        );
+        assert!(outcome.errors().is_empty());
+
+        let symlinks = outcome
+            .files()
+            .iter()
+            .filter(|file| file.symlink())
+            .map(|file| file.name())
+            .collect::<Vec<&str>>();
+        assert_eq!(3, symlinks.len());
+
+        let link_sizes = outcome
+            .files()
+            .iter()
+            .filter(|file| file.symlink())
+            .map(|file| file.size())
+            .sum::<u64>();
+        assert_eq!(6 + link_sizes, outcome.size());
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .follow_symlinks(true)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert!(outcome
+            .directories()
+            .contains(&root.path().join("linked_dir")));
+        assert!(outcome
+            .files()
+            .iter()
+            .any(|file| file.name() == "broken" && file.symlink()));
+    }
+
+    #[cfg(all(unix, feature = "unix"))]
+    #[test]
+    fn unix_permissions() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let root = nested_fixture();
+        let file_path = root.path().join("a.txt");
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o754)).unwrap();
+
+        let outcome = scan(root.path(), Some(0));
+        let file = &outcome.files()[0];
+        let file_meta = fs::metadata(&file_path).unwrap();
+        assert_eq!(Some(file_meta.uid()), file.owner_uid());
+        assert_eq!(Some(file_meta.gid()), file.group_gid());
+        assert_eq!(Some(0o754), file.mode().map(|mode| mode & 0o7777));
+
+        let ls = std::process::Command::new("ls")
+            .arg("-l")
+            .arg(&file_path)
+            .output()
+            .unwrap();
+        let ls = String::from_utf8(ls.stdout).unwrap();
+        assert_eq!(Some(&ls[1..10]), file.permissions_string().as_deref());
+        assert_eq!(Some("rwxr-xr--"), file.permissions_string().as_deref());
+    }
+
+    #[cfg(all(unix, feature = "unix"))]
+    #[test]
+    fn hard_links() {
+        let root = nested_fixture();
+        fs::hard_link(
+            root.path().join("a.txt"),
+            root.path().join("one").join("link.txt"),
+        )
+        .unwrap();
+
+        let outcome = scan(root.path(), None);
+        assert_eq!(7, outcome.size());
+        assert_eq!(6, outcome.size_deduped());
+
+        let linked = outcome
+            .files()
+            .iter()
+            .filter(|file| file.nlink() == Some(2))
+            .map(|file| (file.device(), file.inode()))
+            .collect::<Vec<_>>();
+        assert_eq!(2, linked.len());
+        assert_eq!(linked[0], linked[1]);
+        assert!(linked[0].1.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn disk_usage() {
+        let root = nested_fixture();
+        let sparse = fs::File::create(root.path().join("sparse")).unwrap();
+        sparse.set_len(64 * 1024 * 1024).unwrap();
+
+        let outcome = scan(root.path(), Some(0));
+        let sparse = outcome
+            .files()
+            .iter()
+            .find(|file| file.name() == "sparse")
+            .unwrap();
+        assert_eq!(64 * 1024 * 1024, sparse.size());
+        assert!(sparse.disk_usage() < sparse.size());
+        assert_eq!(
+            outcome
+                .files()
+                .iter()
+                .map(|file| file.disk_usage())
+                .sum::<u64>(),
+            outcome.disk_usage()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_target() {
+        let root = nested_fixture();
+        let absolute = root.path().join("one").join("b.txt");
+        std::os::unix::fs::symlink(&absolute, root.path().join("absolute")).unwrap();
+        std::os::unix::fs::symlink("one/two/c.txt", root.path().join("relative")).unwrap();
+        std::os::unix::fs::symlink("one/missing", root.path().join("broken")).unwrap();
+
+        let outcome = scan(root.path(), Some(0));
+        let file = |name: &str| {
+            outcome
+                .files()
+                .iter()
+                .find(|file| file.name() == name)
+                .unwrap()
+        };
+        assert_eq!(Some(absolute.as_path()), file("absolute").link_target());
+        assert_eq!(
+            Some(Path::new("one/two/c.txt")),
+            file("relative").link_target()
+        );
+        assert_eq!(None, file("a.txt").link_target());
+        assert!(!file("absolute").is_broken_link());
+        assert!(!file("relative").is_broken_link());
+        assert!(file("broken").is_broken_link());
+        assert!(!file("a.txt").is_broken_link());
+    }
+
+    #[test]
+    fn extension_and_stem() {
+        let root = nested_fixture();
+        fs::write(root.path().join(".gitignore"), b"").unwrap();
+        fs::write(root.path().join("archive.tar.gz"), b"").unwrap();
+        fs::write(root.path().join("README"), b"").unwrap();
+
+        let outcome = scan(root.path(), Some(0));
+        let file = |name: &str| {
+            outcome
+                .files()
+                .iter()
+                .find(|file| file.name() == name)
+                .unwrap()
+        };
+        assert_eq!(None, file(".gitignore").extension());
+        assert_eq!(Some(".gitignore"), file(".gitignore").stem());
+        assert_eq!(Some("gz"), file("archive.tar.gz").extension());
+        assert_eq!(Some("archive.tar"), file("archive.tar.gz").stem());
+        assert_eq!(None, file("README").extension());
+        assert_eq!(Some("README"), file("README").stem());
+        assert_eq!(Some("txt"), file("a.txt").extension());
+        assert!(file("a.txt").has_extension("TXT"));
+        assert!(file("archive.tar.gz").has_extension(".gz"));
+        assert!(!file("archive.tar.gz").has_extension("tar.gz"));
+        assert!(!file(".gitignore").has_extension("gitignore"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_file_kinds() {
+        use crate::FileKind;
+
+        let root = nested_fixture();
+        let fifo = root.path().join("fifo");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let _socket = std::os::unix::net::UnixListener::bind(root.path().join("socket")).unwrap();
+        std::os::unix::fs::symlink(&fifo, root.path().join("fifo_link")).unwrap();
+
+        let outcome = scan(root.path(), Some(0));
+        let file = |name: &str| {
+            outcome
+                .files()
+                .iter()
+                .find(|file| file.name() == name)
+                .unwrap()
+        };
+        assert_eq!(FileKind::Regular, file("a.txt").kind());
+        assert_eq!(FileKind::Fifo, file("fifo").kind());
+        assert_eq!(FileKind::Socket, file("socket").kind());
+        assert_eq!(FileKind::Symlink, file("fifo_link").kind());
+        assert_eq!(&FileFormat::default(), file("fifo").file_format());
+        assert_eq!(&FileFormat::default(), file("fifo_link").file_format());
+        assert_eq!(&FileFormat::PlainText, file("a.txt").file_format());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let root = nested_fixture();
+        let name = OsStr::from_bytes(b"invalid-\xff.txt");
+        let file_path = root.path().join(name);
+        fs::write(&file_path, b"bytes").unwrap();
+
+        let outcome = scan(root.path(), Some(0));
+        let file = outcome.get_file_by_path(&file_path).unwrap();
+        assert_eq!(name, file.name_os());
+        assert_eq!("invalid-\u{FFFD}.txt", file.name());
+        assert_eq!(file_path, file.path());
+        assert!(outcome
+            .get_file_by_path(root.path().join("invalid-\u{FFFD}.txt"))
+            .is_none());
+    }
+
+    #[cfg(all(unix, feature = "time"))]
+    #[test]
+    fn changed() {
+        use std::os::unix::fs::MetadataExt;
+
+        let root = nested_fixture();
+        let file_meta = fs::metadata(root.path().join("a.txt")).unwrap();
+
+        let outcome = scan(root.path(), Some(0));
+        let file = &outcome.files()[0];
+        let changed = file.changed().unwrap();
+        let since_epoch = crate::FsUtils::tai64_duration_since_epoch(&changed).unwrap();
+        assert_eq!(file_meta.ctime() as u64, since_epoch.as_secs());
+        assert_eq!(file_meta.ctime_nsec() as u32, since_epoch.subsec_nanos());
+        assert!(file.changed_24hr().is_some());
+        assert!(file.changed_am_pm().is_some());
+        assert!(file.changed_humatime().is_some());
+    }
+
+    #[test]
+    fn depth_and_parent() {
+        let root = nested_fixture();
+
+        let outcome = scan(root.path(), None);
+        let mut depths = outcome
+            .files()
+            .iter()
+            .map(|file| (file.name(), file.depth(), file.parent().to_path_buf()))
+            .collect::<Vec<_>>();
+        depths.sort();
+        assert_eq!(
+            vec![
+                ("a.txt", 0, root.path().to_path_buf()),
+                ("b.txt", 1, root.path().join("one")),
+                ("c.txt", 2, root.path().join("one").join("two")),
+            ],
+            depths
+        );
+        assert_eq!(2, outcome.max_depth_seen());
+        assert_eq!(1, scan(root.path(), Some(1)).max_depth_seen());
+        assert_eq!(0, scan(root.path(), Some(0)).max_depth_seen());
+    }
+
+    #[test]
+    fn files_in_dir_and_subdirs_of() {
+        let root = nested_fixture();
+        fs::create_dir(root.path().join("one").join("empty")).unwrap();
+
+        let outcome = scan(root.path(), None);
+        let names = |files: Vec<&crate::FileMetadata>| {
+            files
+                .iter()
+                .map(|file| file.name().to_owned())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(vec!["a.txt"], names(outcome.files_in_dir(root.path())));
+        assert_eq!(vec!["a.txt"], names(outcome.files_in_dir("")));
+        assert_eq!(vec!["b.txt"], names(outcome.files_in_dir("one")));
+        assert_eq!(
+            vec!["b.txt"],
+            names(outcome.files_in_dir(root.path().join("one")))
+        );
+        assert!(outcome.files_in_dir("one/empty").is_empty());
+
+        assert_eq!(vec![root.path().join("one")], outcome.subdirs_of(""));
+        let mut subdirs = outcome.subdirs_of(root.path().join("one"));
+        subdirs.sort();
+        assert_eq!(
+            vec![root.path().join("one/empty"), root.path().join("one/two")],
+            subdirs
+        );
+        assert_eq!(2, outcome.subdirs_of("one").len());
+        assert!(outcome.subdirs_of("one/two").is_empty());
+    }
+
+    #[test]
+    fn sorted_files() {
+        let root = nested_fixture();
+        fs::write(root.path().join("z.txt"), b"zzzz").unwrap();
+
+        let outcome = scan(root.path(), None);
+        let names = |files: Vec<&crate::FileMetadata>| {
+            files
+                .iter()
+                .map(|file| file.name().to_owned())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            vec!["z.txt", "c.txt", "b.txt", "a.txt"],
+            names(outcome.files_sorted_by_size())
+        );
+        assert_eq!(
+            vec!["a.txt", "b.txt", "c.txt", "z.txt"],
+            names(outcome.files_sorted_by_name())
+        );
+        assert_eq!(vec!["z.txt", "c.txt"], names(outcome.largest_files(2)));
+        assert_eq!(4, outcome.largest_files(10).len());
+        assert!(outcome.largest_files(0).is_empty());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn sorted_by_modified() {
+        let root = nested_fixture();
+        let old = fs::File::options()
+            .write(true)
+            .open(root.path().join("one").join("b.txt"))
+            .unwrap();
+        old.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(60))
+            .unwrap();
+
+        let mut outcome = scan(root.path(), None);
+        let a_txt = outcome
+            .files
+            .iter()
+            .position(|file| file.name() == "a.txt")
+            .unwrap();
+        outcome.files[a_txt].modified = None;
+
+        let names = outcome
+            .files_sorted_by_modified()
+            .iter()
+            .map(|file| file.name())
+            .collect::<Vec<&str>>();
+        assert_eq!(vec!["c.txt", "b.txt", "a.txt"], names);
+    }
+
+    #[test]
+    fn dir_sizes() {
+        let root = nested_fixture();
+        fs::create_dir(root.path().join("one").join("empty")).unwrap();
+
+        let outcome = scan(root.path(), None);
+        assert_eq!(Some(6), outcome.dir_size(root.path()));
+        assert_eq!(Some(5), outcome.dir_size("one"));
+        assert_eq!(
+            Some(3),
+            outcome.dir_size(root.path().join("one").join("two"))
+        );
+        assert_eq!(Some(0), outcome.dir_size("one/empty"));
+        assert_eq!(None, outcome.dir_size("missing"));
+        assert_eq!(4, outcome.dir_sizes().len());
+        assert_eq!(
+            vec![
+                (root.path().join("one").as_path(), 5),
+                (root.path().join("one").join("two").as_path(), 3),
+            ],
+            outcome.largest_dirs(2)
+        );
+    }
+
+    #[test]
+    fn disk_usage_reports() {
+        use crate::FsUtils;
+
+        let root = tempfile::tempdir().unwrap();
+        let media = root.path().join("media");
+        let logs = root.path().join("logs");
+        fs::create_dir_all(media.join("raw")).unwrap();
+        fs::create_dir(&logs).unwrap();
+        fs::write(media.join("raw").join("clip.MOV"), vec![0u8; 4096]).unwrap();
+        fs::write(media.join("poster.png"), vec![0u8; 1024]).unwrap();
+        fs::write(logs.join("app.log"), vec![0u8; 2048]).unwrap();
+        fs::write(logs.join("old.log"), vec![0u8; 512]).unwrap();
+        fs::write(root.path().join("LICENSE"), vec![0u8; 100]).unwrap();
+
+        let outcome = scan(root.path(), None);
+        assert_eq!(
+            vec![
+                (media.as_path(), 5120),
+                (media.join("raw").as_path(), 4096),
+                (logs.as_path(), 2560),
+            ],
+            outcome.largest_dirs(3)
+        );
+        assert_eq!(
+            vec![(media.as_path(), FsUtils::size_to_bytes(5120))],
+            outcome.largest_dirs_formatted(1)
+        );
+
+        assert_eq!(
+            vec![
+                ("mov".to_owned(), 4096, 1),
+                ("log".to_owned(), 2560, 2),
+                ("png".to_owned(), 1024, 1),
+                (String::new(), 100, 1),
+            ],
+            outcome.size_by_extension()
+        );
+        assert_eq!(
+            ("log".to_owned(), FsUtils::size_to_bytes(2560), 2),
+            outcome.size_by_extension_formatted()[1]
+        );
+    }
+
+    #[test]
+    fn empty_files_and_dirs() {
+        let root = nested_fixture();
+        let nested_empty = root.path().join("outer").join("inner");
+        fs::create_dir_all(&nested_empty).unwrap();
+        fs::create_dir(root.path().join("one").join("hidden")).unwrap();
+        fs::write(root.path().join("one").join("hidden").join("skip.log"), b"").unwrap();
+        fs::write(root.path().join("empty.txt"), b"").unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .exclude_glob("**/*.log")
+                .unwrap()
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["empty.txt"],
+            outcome
+                .empty_files()
+                .iter()
+                .map(|file| file.name())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(
+            vec![root.path().join("outer").as_path(), nested_empty.as_path()],
+            outcome.empty_dirs()
+        );
+    }
+
+    fn tricky_fixture() -> tempfile::TempDir {
+        let root = nested_fixture();
+        let nested = root.path().join("with space").join("ünïcödé");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("app.MIN.js"), b"").unwrap();
+        fs::write(nested.join("my file.min.js"), b"").unwrap();
+        fs::write(root.path().join("日本語.min.js"), b"").unwrap();
+        fs::write(root.path().join("app.js"), b"").unwrap();
+
+        root
+    }
+
+    #[test]
+    fn find() {
+        let root = tricky_fixture();
+        let outcome = scan(root.path(), None);
+        let names = |files: Vec<&crate::FileMetadata>| {
+            let mut names = files
+                .iter()
+                .map(|file| file.name().to_owned())
+                .collect::<Vec<String>>();
+            names.sort();
+
+            names
+        };
+
+        assert_eq!(
+            vec!["my file.min.js", "日本語.min.js"],
+            names(outcome.find("**/*.min.js").unwrap())
+        );
+        assert_eq!(
+            vec!["app.MIN.js", "my file.min.js", "日本語.min.js"],
+            names(outcome.find_ci("**/*.min.js").unwrap())
+        );
+        assert_eq!(
+            vec!["my file.min.js"],
+            names(outcome.find("with space/ünïcödé/my *").unwrap())
+        );
+        assert_eq!(vec!["b.txt"], names(outcome.find("one/*.txt").unwrap()));
+        assert!(outcome.find("[").is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn find_regex() {
+        let root = tricky_fixture();
+        let outcome = scan(root.path(), None);
+        let regex = regex::Regex::new(r"^app\.min\.js$").unwrap();
+
+        assert!(outcome.find_regex(&regex).is_empty());
+        assert_eq!(1, outcome.find_regex_ci(&regex).len());
+        assert_eq!(
+            1,
+            outcome
+                .find_regex(&regex::Regex::new(r"^\p{Han}+\.").unwrap())
+                .len()
+        );
+    }
+
+    #[test]
+    fn get_file() {
+        let root = nested_fixture();
+        fs::write(root.path().join("ReadMe.MD"), b"").unwrap();
+        fs::write(root.path().join("ÉTÉ.txt"), b"").unwrap();
+
+        let outcome = scan(root.path(), None);
+        let query = String::from("b.txt");
+        let found = outcome
+            .get_file(&query)
+            .map(|file| file.path().into_owned());
+        drop(query);
+        assert_eq!(Some(root.path().join("one").join("b.txt")), found);
+        assert!(outcome.get_file("README.md").is_none());
+        assert_eq!(
+            "ReadMe.MD",
+            outcome.get_file_ci("README.md").unwrap().name()
+        );
+        assert_eq!("ÉTÉ.txt", outcome.get_file_ci("été.TXT").unwrap().name());
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn unicode_normalization() {
+        let root = nested_fixture();
+        // `e` followed by a combining acute accent, the decomposed form of `é`
+        let decomposed = "cafe\u{301}.txt";
+        fs::write(root.path().join(decomposed), b"").unwrap();
+
+        let outcome = scan(root.path(), Some(0));
+        let composed = root.path().join("caf\u{e9}.txt");
+        assert_eq!(
+            decomposed,
+            outcome.get_file_by_path(&composed).unwrap().name()
+        );
+        assert_eq!(
+            decomposed,
+            outcome.get_file_ci("CAF\u{c9}.TXT").unwrap().name()
+        );
+    }
+
+    #[test]
+    fn contains_path() {
+        let root = nested_fixture();
+
+        let outcome = scan(root.path(), None);
+        let c_txt = root.path().join("one").join("two").join("c.txt");
+        assert!(outcome.contains_path(&c_txt));
+        assert!(!outcome.contains_path(root.path().join("c.txt")));
+        assert_eq!(c_txt, outcome.get_file_by_path(&c_txt).unwrap().path());
+        assert_eq!(outcome, outcome.clone());
+    }
+
+    #[test]
+    fn shared_parent_paths() {
+        use std::path::PathBuf;
+
+        let root = tempfile::tempdir().unwrap();
+        let deep = root
+            .path()
+            .join("a-rather-long-directory-name-shared-by-every-file")
+            .join("and-another-level-below-it");
+        fs::create_dir_all(&deep).unwrap();
+        for index in 0..50 {
+            fs::write(deep.join(format!("{index}.txt")), b"x").unwrap();
+        }
+
+        let outcome = scan(root.path(), None);
+        let files = outcome.files();
+        assert_eq!(50, files.len());
+        assert!(files
+            .iter()
+            .all(|file| Arc::ptr_eq(&file.parent, &files[0].parent)));
+
+        let before_index = outcome.memory_footprint_estimate();
+        let absolute = fs::canonicalize(deep.join("7.txt")).unwrap();
+        let found = outcome.get_file_by_path(&absolute).unwrap();
+        assert_eq!("7.txt", found.name());
+        assert_eq!(deep.join("7.txt"), found.path());
+        assert_eq!(deep.as_path(), found.parent());
+        assert!(outcome.contains_path(deep.join("7.txt")));
+        assert!(!outcome.contains_path(deep.join("50.txt")));
+        assert!(!outcome.contains_path(&deep));
+
+        // The index only holds the position of each file besides the shared directory
+        let index = outcome.memory_footprint_estimate() - before_index;
+        assert!(index >= files.len() * size_of::<usize>());
+        assert!(index < files.len() * 2 * size_of::<usize>() + 128);
+
+        // Storing the full path of every file would take more than the whole scan
+        let full_paths =
+            files.len() * (size_of::<PathBuf>() + deep.join("7.txt").as_os_str().len());
+        assert!(before_index > size_of::<DirMetadata>());
+        assert!(before_index - size_of::<DirMetadata>() < full_paths + size_of_val(files));
+    }
+
+    #[test]
+    fn cmp_path_matches_path_order() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("b").join("c")).unwrap();
+        fs::write(root.path().join("b").join("c").join("x"), b"").unwrap();
+        fs::write(root.path().join("b.txt"), b"").unwrap();
+        fs::write(root.path().join("b").join("a"), b"").unwrap();
+        fs::write(root.path().join("a"), b"").unwrap();
+
+        let outcome = scan(root.path(), None);
+        let mut expected = outcome
+            .files()
+            .iter()
+            .map(|file| file.path().into_owned())
+            .collect::<Vec<_>>();
+        expected.sort();
+        let mut files = outcome.files().iter().collect::<Vec<_>>();
+        files.sort_by(|first, second| first.cmp_path(second));
+
+        assert_eq!(
+            expected,
+            files
+                .iter()
+                .map(|file| file.path().into_owned())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn get_file_by_relative_path() {
+        let root = nested_fixture();
+        let b_txt = root.path().join("one").join("b.txt");
+
+        let outcome = scan(root.path(), None);
+        let found = |path: &Path| {
+            outcome
+                .get_file_by_path(path)
+                .map(|file| file.path().into_owned())
+        };
+        assert_eq!(Some(b_txt.clone()), found(&b_txt));
+        assert_eq!(Some(b_txt.clone()), found(Path::new("one/b.txt")));
+        assert_eq!(
+            Some(b_txt.clone()),
+            found(&root.path().join("one").join("two").join("..").join("b.txt"))
+        );
+        assert_eq!(None, found(Path::new("b.txt")));
+
+        let relative = smol::block_on(DirMetadata::new("src").unwrap().dir_metadata()).unwrap();
+        let lib_rs = Path::new("src").join("lib.rs");
+        let by_path = |path: &Path| {
+            relative
+                .get_file_by_path(path)
+                .map(|file| file.path().into_owned())
+        };
+        assert_eq!(Some(lib_rs.clone()), by_path(&lib_rs));
+        assert_eq!(Some(lib_rs.clone()), by_path(Path::new("lib.rs")));
+        assert_eq!(
+            Some(lib_rs.clone()),
+            by_path(&fs::canonicalize(&lib_rs).unwrap())
+        );
+    }
+
+    #[test]
+    fn files_of_kind() {
+        let root = nested_fixture();
+        fs::write(root.path().join("image.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+        fs::write(
+            root.path().join("song.mp3"),
+            b"ID3\x03\x00\x00\x00\x00\x00\x00",
+        )
+        .unwrap();
+        fs::write(root.path().join("paper.pdf"), b"%PDF-1.7\n").unwrap();
+
+        let outcome = scan(root.path(), None);
+        let names = |files: Vec<&crate::FileMetadata>| {
+            files
+                .iter()
+                .map(|file| file.name().to_owned())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(vec!["image.png"], names(outcome.images()));
+        assert_eq!(vec!["song.mp3"], names(outcome.audio()));
+        assert_eq!(vec!["paper.pdf"], names(outcome.documents()));
+        assert!(outcome.videos().is_empty());
+        assert_eq!(3, outcome.files_of_kind(file_format::Kind::Text).len());
+
+        let deferred = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .defer_format_detection()
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert!(deferred.images().is_empty());
+    }
+
+    #[test]
+    fn traversal_order() {
+        use crate::TraversalOrder;
+
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("a.txt"), b"a").unwrap();
+        for (top, deep) in [("x", "y"), ("z", "w")] {
+            fs::create_dir_all(root.path().join(top).join(deep)).unwrap();
+            fs::write(root.path().join(top).join("top.txt"), b"top").unwrap();
+            fs::write(root.path().join(top).join(deep).join("deep.txt"), b"deep").unwrap();
+        }
+
+        let scan = |order: TraversalOrder| {
+            smol::block_on(
+                DirMetadata::new(root.path().to_str().unwrap())
+                    .unwrap()
+                    .traversal_order(order)
+                    .dir_metadata(),
+            )
+            .unwrap()
+        };
+        let depths = |files: &[crate::FileMetadata]| {
+            files
+                .iter()
+                .map(|file| file.depth())
+                .collect::<Vec<usize>>()
+        };
+
+        // Depth first reads a whole top-level directory before the next one
+        assert_eq!(
+            vec![0, 1, 2, 1, 2],
+            depths(scan(TraversalOrder::DepthFirst).files())
+        );
+        assert_eq!(
+            vec![0, 1, 1, 2, 2],
+            depths(scan(TraversalOrder::BreadthFirst).files())
+        );
+
+        let walked = DirMetadata::new(root.path().to_str().unwrap())
+            .unwrap()
+            .traversal_order(TraversalOrder::BreadthFirst)
+            .walk()
+            .map(|file| file.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![0, 1, 1, 2, 2], depths(&walked));
+    }
+
+    #[test]
+    fn sorted() {
+        let root = nested_fixture();
+        fs::write(root.path().join("one").join("a.txt"), b"a").unwrap();
+        fs::write(root.path().join("z.txt"), b"z").unwrap();
+        fs::create_dir(root.path().join("b")).unwrap();
+
+        let sorted_scan = || {
+            smol::block_on(
+                DirMetadata::new(root.path().to_str().unwrap())
+                    .unwrap()
+                    .sorted(true)
+                    .dir_metadata(),
+            )
+            .unwrap()
+        };
+        let paths = |outcome: &DirMetadata| {
+            outcome
+                .files()
+                .iter()
+                .map(|file| file.path().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let first = sorted_scan();
+        let second = sorted_scan();
+        assert_eq!(paths(&first), paths(&second));
+        assert_eq!(first.directories(), second.directories());
+        assert_eq!(
+            vec![
+                root.path().join("a.txt"),
+                root.path().join("one").join("a.txt"),
+                root.path().join("one").join("b.txt"),
+                root.path().join("one").join("two").join("c.txt"),
+                root.path().join("z.txt"),
+            ],
+            paths(&first)
+        );
+        assert_eq!(
+            vec![
+                root.path().join("b"),
+                root.path().join("one"),
+                root.path().join("one").join("two"),
+            ],
+            first.directories()
+        );
+    }
+
+    #[test]
+    fn into_iterator() {
+        let root = nested_fixture();
+
+        let outcome = scan(root.path(), None);
+        assert_eq!(3, outcome.len());
+        assert!(!outcome.is_empty());
+        assert!(DirMetadata::default().is_empty());
+
+        let mut sizes = Vec::<u64>::new();
+        for file in &outcome {
+            sizes.push(file.size());
+        }
+        sizes.sort();
+        assert_eq!(vec![1, 2, 3], sizes);
+        assert_eq!(6, outcome.iter().map(|file| file.size()).sum::<u64>());
+
+        let owned = {
+            let path = root.path().to_str().unwrap().to_owned();
+            let outcome = smol::block_on(DirMetadata::new(&path).unwrap().dir_metadata()).unwrap();
+
+            outcome
+                .into_iter()
+                .collect::<Vec<crate::FileMetadata<'static>>>()
+        };
+        assert_eq!(3, owned.len());
+    }
+
+    #[test]
+    fn retain_remove_and_merge() {
+        let consistent = |outcome: &DirMetadata| {
+            assert_eq!(
+                outcome.files().iter().map(|file| file.size()).sum::<u64>(),
+                outcome.size()
+            );
+            assert_eq!(
+                outcome
+                    .files()
+                    .iter()
+                    .map(|file| file.disk_usage())
+                    .sum::<u64>(),
+                outcome.disk_usage()
+            );
+        };
+
+        let root = nested_fixture();
+        fs::create_dir(root.path().join(".git")).unwrap();
+        fs::write(root.path().join(".git").join("HEAD"), b"ref: main").unwrap();
+
+        let mut outcome = scan(root.path(), None);
+        consistent(&outcome);
+
+        assert!(outcome.remove_path(".git"));
+        assert!(!outcome.remove_path(".git"));
+        assert!(!outcome.directories().contains(&root.path().join(".git")));
+        assert_eq!(None, outcome.dir_size(".git"));
+        assert_eq!(Some(6), outcome.dir_size(""));
+        consistent(&outcome);
+
+        outcome.retain_files(|file| file.size() > 1);
+        assert_eq!(vec!["b.txt", "c.txt"], file_names(&outcome));
+        assert_eq!(Some(5), outcome.dir_size(""));
+        assert_eq!(Some(5), outcome.dir_size("one"));
+        consistent(&outcome);
+
+        let other_root = nested_fixture();
+        let mut other = scan(other_root.path(), None);
+        other.remove_path("one");
+        consistent(&other);
+
+        let duplicate = scan(root.path(), Some(0));
+        outcome.merge(other);
+        consistent(&outcome);
+        outcome.merge(duplicate);
+        consistent(&outcome);
+        assert_eq!(
+            vec!["a.txt", "a.txt", "b.txt", "c.txt"],
+            file_names(&outcome)
+        );
+        assert_eq!(2, outcome.roots().len());
+        assert!(outcome.contains_path(other_root.path().join("a.txt")));
+    }
+
+    #[test]
+    fn merge_overlapping() {
+        let root = nested_fixture();
+        let mut outcome = scan(root.path(), None);
+        outcome.merge(outcome.clone());
+        outcome.merge(scan(root.path(), Some(0)));
+
+        assert_eq!(6, outcome.size());
+        assert_eq!(3, outcome.file_count());
+        assert_eq!(Some(6), outcome.dir_size(root.path()));
+        assert_eq!(Some(5), outcome.dir_size("one"));
+        assert_eq!(Some(3), outcome.dir_size("one/two"));
+        assert_eq!(Some(&3), outcome.dir_file_counts.get(root.path()));
+        assert_eq!(
+            Some(&2),
+            outcome.dir_file_counts.get(&root.path().join("one"))
+        );
+        assert!(outcome.empty_dirs().is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let root = nested_fixture();
+        let mut outcome = scan(root.path(), None);
+        outcome.files[0].file_format();
+        outcome.errors.push(crate::DirError {
+            path: root.path().join("missing"),
+            error: ErrorKind::NotFound,
+            os_error: Some(2),
+            display: "No such file or directory".into(),
+        });
+
+        let json = serde_json::to_string(&outcome).unwrap();
+        assert!(json.contains("\"error\":\"NotFound\""));
+        assert!(json.contains("\"file_format\":\"PlainText\""));
+
+        let restored = serde_json::from_str::<DirMetadata>(&json).unwrap();
+        assert_eq!(outcome, restored);
+        assert_eq!(
+            outcome.files()[0].modified(),
+            restored.files()[0].modified()
+        );
+    }
+
+    #[test]
+    fn du_report() {
+        let root = nested_fixture();
+        fs::create_dir(root.path().join("three")).unwrap();
+        fs::write(root.path().join("three").join("d.txt"), b"dddd").unwrap();
+        let outcome = scan(root.path(), None);
+
+        let line = |size: &str, path: &Path| format!("{size}\t{}", path.display());
+        assert_eq!(
+            vec![
+                line("10.00B", root.path()),
+                line("5.00B", &root.path().join("one")),
+                line("4.00B", &root.path().join("three")),
+                "10.00B\ttotal".to_owned(),
+            ],
+            outcome.du_report(1).lines().collect::<Vec<&str>>()
+        );
+        assert_eq!(5, outcome.du_report(2).lines().count());
+        assert_eq!(2, outcome.du_report(0).lines().count());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn rfc3339() {
+        use chrono::{DateTime, Utc};
+
+        let root = nested_fixture();
+        let outcome = scan(root.path(), Some(0));
+        let modified = outcome
+            .get_file("a.txt")
+            .unwrap()
+            .modified_rfc3339()
+            .unwrap();
+
+        assert!(modified.ends_with('Z'));
+        assert_eq!(30, modified.len());
+        let expected: DateTime<Utc> = fs::metadata(root.path().join("a.txt"))
+            .unwrap()
+            .modified()
+            .unwrap()
+            .into();
+        assert_eq!(expected, DateTime::parse_from_rfc3339(&modified).unwrap());
+        assert_eq!(
+            "1970-01-01T00:00:00.000000000Z",
+            crate::FsUtils::tai64_to_rfc3339(&tai64::Tai64N::UNIX_EPOCH)
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn formatted_times() {
+        let root = nested_fixture();
+        let outcome = scan(root.path(), Some(0));
+        let file = outcome.get_file("a.txt").unwrap();
+
+        let formatted = file.modified_formatted("%A, %-d %B, %C%y", "%H:%M:%S");
+        assert_eq!(file.modified_24hr(), formatted.unwrap());
+        assert!(file.modified_formatted("%Y", "%H:%").is_err());
+        assert!(file.accessed_formatted("%Q", "%H").is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn local_time() {
+        use crate::FsUtils;
+        use chrono::{FixedOffset, Local, Offset, TimeZone};
+        use tai64::Tai64N;
+
+        let plus_three = FixedOffset::east_opt(3 * 3600).unwrap();
+        let shifted =
+            FsUtils::tai64_to_formatted_in(&Tai64N::UNIX_EPOCH, &plus_three, "%F", "%T").unwrap();
+        assert_eq!("1970-01-01", shifted.date);
+        assert_eq!("03:00:00", shifted.time);
+        assert_eq!("+03:00", shifted.offset);
+
+        // Whatever the time zone of the machine running the tests is
+        let local = FsUtils::tai64_to_local_hrs(&Tai64N::UNIX_EPOCH);
+        let local_offset = Local.timestamp_opt(0, 0).unwrap().offset().fix();
+        assert_eq!(local_offset.to_string(), local.offset);
+        assert_eq!(
+            FsUtils::tai64_to_formatted_in(&Tai64N::UNIX_EPOCH, &local_offset, "%F", "%T")
+                .unwrap()
+                .time,
+            local.time
+        );
+
+        let utc = FsUtils::tai64_to_utc_hrs(&Tai64N::UNIX_EPOCH);
+        assert_eq!("00:00:00", utc.time);
+        assert_eq!("+00:00", utc.offset);
+        assert_eq!("Thursday, 1 January, 1970", utc.date);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn coarse_humantime() {
+        use crate::FsUtils;
+        use std::time::Duration;
+
+        let root = nested_fixture();
+        let outcome = scan(root.path(), Some(0));
+        let file = outcome.get_file("a.txt").unwrap();
+        assert_eq!(
+            Some("just now"),
+            file.modified_humantime_coarse().as_deref()
+        );
+
+        let now = tai64::Tai64N::now();
+        let ago = |secs: u64| {
+            FsUtils::tai64_to_coarse_humantime(&(now - Duration::from_secs(secs)), &now)
+        };
+        assert_eq!("just now", ago(59));
+        assert_eq!("1 minute ago", ago(60));
+        assert_eq!("1 hour ago", ago(3600 + 59 * 60));
+        assert_eq!("3 weeks ago", ago(21 * 24 * 3600));
+        assert_eq!("2 years ago", ago(2 * 365 * 24 * 3600));
+        assert_eq!(
+            "in 1 day",
+            FsUtils::tai64_to_coarse_humantime(&(now + Duration::from_secs(24 * 3600)), &now)
+        );
+    }
+
+    #[test]
+    fn size_styles() {
+        use crate::{FsUtils, SizeStyle};
+
+        assert_eq!("1023.00B", FsUtils::size_to_bytes(1023));
+        assert_eq!("1.00KiB", FsUtils::size_to_bytes(1024));
+        assert_eq!(
+            "123.457MB",
+            FsUtils::format_size_with_decimals(123_456_789, SizeStyle::Si, 3)
+        );
+        assert_eq!(
+            "16.0000010MiB",
+            FsUtils::format_size_with_decimals(16 * 1024 * 1024 + 1, SizeStyle::Binary, 7)
+        );
+        assert_eq!(
+            "16.00EiB",
+            FsUtils::format_size(u64::MAX, SizeStyle::Binary)
+        );
+
+        let root = nested_fixture();
+        let outcome = scan(root.path(), None);
+        assert_eq!("6B", outcome.size_formatted_with(SizeStyle::Bytes));
+        assert_eq!(
+            outcome.size_formatted(),
+            outcome.size_formatted_with(SizeStyle::default())
+        );
+        assert_eq!(
+            "1.00B",
+            outcome
+                .get_file("a.txt")
+                .unwrap()
+                .formatted_size_with(SizeStyle::Si)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sparse_file_over_4gib() {
+        let root = tempfile::tempdir().unwrap();
+        let size = 5 * 1024 * 1024 * 1024u64;
+        fs::File::create(root.path().join("sparse.bin"))
+            .unwrap()
+            .set_len(size)
+            .unwrap();
+
+        let outcome = scan(root.path(), None);
+        let file = outcome.get_file("sparse.bin").unwrap();
+        assert_eq!(size, file.size());
+        assert_eq!(size, outcome.size());
+        assert_eq!(Some(size), outcome.dir_size(""));
+        assert_eq!("5.00GiB", file.formatted_size());
+        assert!(file.disk_usage() < size);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dir_error_display() {
+        let missing = Path::new("/missing");
+        let io_error = fs::read_dir(missing).unwrap_err();
+        let error = crate::DirError {
+            path: missing.to_owned(),
+            error: io_error.kind(),
+            os_error: io_error.raw_os_error(),
+            display: "Unable to read the directory".into(),
+        };
+
+        let displayed = error.to_string();
+        assert_eq!(
+            "/missing: entity not found: Unable to read the directory (os error 2)",
+            displayed
+        );
+
+        let boxed: Box<dyn std::error::Error> = Box::new(error);
+        assert_eq!(displayed, boxed.to_string());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn strict() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let root = nested_fixture();
+        let path = root.path().to_str().unwrap();
+        assert!(smol::block_on(DirMetadata::new(path).unwrap().strict().dir_metadata()).is_ok());
+
+        // Permissions are not enforced when running as root
+        let denied = root.path().join("denied");
+        fs::create_dir(&denied).unwrap();
+        fs::set_permissions(&denied, fs::Permissions::from_mode(0o000)).unwrap();
+        if fs::read_dir(&denied).is_err() {
+            let error = smol::block_on(DirMetadata::new(path).unwrap().strict().dir_metadata())
+                .unwrap_err();
+            assert_eq!(ErrorKind::PermissionDenied, error.kind());
+            assert_eq!(1, scan(root.path(), None).errors().len());
+        }
+        fs::set_permissions(&denied, fs::Permissions::from_mode(0o755)).unwrap();
+
+        symlink(root.path(), root.path().join("one").join("cycle")).unwrap();
+        let lenient = smol::block_on(
+            DirMetadata::new(path)
+                .unwrap()
+                .follow_symlinks(true)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(1, lenient.errors().len());
+
+        let error = smol::block_on(
+            DirMetadata::new(path)
+                .unwrap()
+                .follow_symlinks(true)
+                .strict()
+                .dir_metadata(),
+        )
+        .unwrap_err();
+        let dir_error = error
+            .get_ref()
+            .and_then(|error| error.downcast_ref::<crate::DirError>())
+            .unwrap();
+        assert_eq!(&lenient.errors()[0], dir_error);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refresh_keeps_strict() {
+        use std::os::unix::fs::symlink;
+
+        let root = nested_fixture();
+        let (scan, cancel_handle) = DirMetadata::new(root.path().to_str().unwrap())
+            .unwrap()
+            .follow_symlinks(true)
+            .strict()
+            .on_progress(1, |_| ())
+            .cancellable();
+        let mut outcome = smol::block_on(scan.dir_metadata()).unwrap();
+        smol::block_on(outcome.refresh()).unwrap();
+        assert!(outcome.strict);
+        assert_eq!(Some(&cancel_handle), outcome.cancel_handle.as_ref());
+        assert!(outcome.progress_hook.is_some());
+
+        // A directory that cannot be read anymore fails the refresh of a strict scan
+        symlink(
+            root.path(),
+            root.path().join("one").join("two").join("cycle"),
+        )
+        .unwrap();
+        assert!(smol::block_on(outcome.refresh()).is_err());
+        assert_eq!(3, outcome.file_count());
+
+        // The subtree is only replaced once it was scanned again
+        let files = file_names(&outcome)
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<String>>();
+        assert!(smol::block_on(outcome.refresh_subdir("one")).is_err());
+        assert_eq!(6, outcome.size());
+        assert_eq!(files, file_names(&outcome));
+        assert!(outcome.errors().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn error_hook_and_cap() {
+        use crate::ErrorAction;
+        use std::os::unix::fs::symlink;
+
+        let root = nested_fixture();
+        let path = root.path().to_str().unwrap();
+        let two = root.path().join("one").join("two");
+        symlink(root.path(), root.path().join("cycle")).unwrap();
+        symlink(root.path(), two.join("cycle")).unwrap();
+
+        let lenient = smol::block_on(
+            DirMetadata::new(path)
+                .unwrap()
+                .follow_symlinks(true)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(2, lenient.errors().len());
+        assert_eq!(0, lenient.dropped_error_count());
+
+        let seen = Arc::new(Mutex::new(0usize));
+        let hook_seen = Arc::clone(&seen);
+        let capped = smol::block_on(
+            DirMetadata::new(path)
+                .unwrap()
+                .follow_symlinks(true)
+                .max_errors(1)
+                .on_error(move |_| {
+                    *hook_seen.lock().unwrap() += 1;
+
+                    ErrorAction::Continue
+                })
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert_eq!(2, *seen.lock().unwrap());
+        assert_eq!(1, capped.errors().len());
+        assert_eq!(1, capped.dropped_error_count());
+        assert_eq!(file_names(&lenient), file_names(&capped));
+
+        let aborted = smol::block_on(
+            DirMetadata::new(path)
+                .unwrap()
+                .follow_symlinks(true)
+                .on_error(|_| ErrorAction::Abort)
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert!(aborted.was_cancelled());
+        assert_eq!(1, aborted.errors().len());
+
+        // A cycle is found when entering the symbolic link so only the link is skipped
+        let skipped = smol::block_on(
+            DirMetadata::new(path)
+                .unwrap()
+                .follow_symlinks(true)
+                .on_error(|error| match error.path.ends_with("two/cycle") {
+                    true => ErrorAction::SkipSubtree,
+                    false => ErrorAction::Continue,
+                })
+                .dir_metadata(),
+        )
+        .unwrap();
+        assert!(!skipped.was_cancelled());
+        assert_eq!(2, skipped.errors().len());
+        assert_eq!(file_names(&lenient), file_names(&skipped));
+    }
+
+    #[test]
+    fn new_validates_the_path() {
+        let root = nested_fixture();
+
+        let error = DirMetadata::new(root.path().join("missing").to_str().unwrap()).unwrap_err();
+        assert_eq!(ErrorKind::NotFound, error.kind());
+        let error = DirMetadata::new(root.path().join("a.txt").to_str().unwrap()).unwrap_err();
+        assert_eq!(ErrorKind::NotADirectory, error.kind());
+
+        let current = std::env::current_dir().unwrap();
+        let current_name = current.file_name().unwrap().to_str().unwrap();
+        assert_eq!(current_name, DirMetadata::new(".").unwrap().dir_name());
+        assert_eq!(current_name, DirMetadata::new("src/..").unwrap().dir_name());
+        assert_eq!("/", DirMetadata::new("/").unwrap().dir_name());
+    }
+
+    #[test]
+    fn open_and_read_head() {
+        let root = nested_fixture();
+        fs::write(root.path().join("long.txt"), b"0123456789").unwrap();
+
+        let outcome = scan(root.path(), None);
+        let long = outcome.get_file("long.txt").unwrap();
+        assert_eq!(b"0123".to_vec(), long.read_head(4).unwrap());
+        assert_eq!(b"0123456789".to_vec(), long.read_head(1024).unwrap());
+        assert!(long.read_head(0).unwrap().is_empty());
+        assert_eq!(
+            b"012".to_vec(),
+            smol::block_on(long.read_head_async(3)).unwrap()
+        );
+        assert_eq!(10, long.open().unwrap().metadata().unwrap().len());
+
+        fs::remove_file(long.path()).unwrap();
+        assert_eq!(ErrorKind::NotFound, long.open().unwrap_err().kind());
+        assert_eq!(ErrorKind::NotFound, long.read_head(4).unwrap_err().kind());
+        assert_eq!(
+            ErrorKind::NotFound,
+            smol::block_on(long.open_async()).unwrap_err().kind()
+        );
+        assert_eq!(
+            ErrorKind::NotFound,
+            smol::block_on(long.read_head_async(4)).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn borrowed_name() {
+        use std::borrow::Cow;
+
+        let root = nested_fixture();
+        let path = root.path().to_str().unwrap().to_owned();
+        let outcome = smol::block_on(DirMetadata::new(&path).unwrap().dir_metadata()).unwrap();
+        assert!(matches!(outcome.name, Cow::Borrowed(_)));
+        assert!(matches!(
+            outcome.root_meta.as_ref().unwrap().name,
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(DirMetadata::new(".").unwrap().name, Cow::Owned(_)));
+
+        let expected = outcome.clone().into_owned();
+        let owned = outcome.into_owned();
+        drop(path);
+        assert!(matches!(owned.name, Cow::Owned(_)));
+        assert_eq!(expected, owned);
+
+        let file = std::thread::spawn(move || owned.files()[0].clone().into_owned())
+            .join()
+            .unwrap();
+        assert!(file.path().starts_with(root.path()));
+    }
+
+    #[test]
+    fn timestamps_as_system_time() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let root = tempfile::tempdir().unwrap();
+        let modified = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        fs::File::create(root.path().join("a.txt"))
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let outcome = scan(root.path(), None);
+        let file = &outcome.files()[0];
+        assert_eq!(Some(modified), file.modified_systemtime());
+        assert_eq!(Some(1_700_000_000), file.modified_unix_secs());
+        assert_eq!(Some(1_700_000_000_250), file.modified_unix_millis());
+        assert!(file.accessed_unix_secs().is_some());
+    }
+
+    #[test]
+    fn timestamp_comparisons() {
+        use std::time::{Duration, SystemTime};
+
+        let root = tempfile::tempdir().unwrap();
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        let tomorrow = SystemTime::now() + Duration::from_secs(24 * 3600);
+        for (name, modified) in [("old.txt", hour_ago), ("future.txt", tomorrow)] {
+            fs::File::create(root.path().join(name))
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+
+        let outcome = scan(root.path(), None);
+        let file = |name: &str| {
+            outcome
+                .files()
+                .iter()
+                .find(|file| file.name() == name)
+                .unwrap()
+        };
+        let (old, future) = (file("old.txt"), file("future.txt"));
+        let now = tai64::Tai64N::now();
+
+        assert!(old.modified_before(&now) && !old.modified_after(&now));
+        assert!(future.modified_after(&now) && !future.modified_before(&now));
+        assert!(future.newer_than(old) && !old.newer_than(future));
+        assert!(old.age().unwrap() >= Duration::from_secs(3600));
+        assert_eq!(Some(Duration::ZERO), future.age());
+    }
+
+    #[test]
+    fn scan_timing() {
+        use std::time::Duration;
+
+        let root = nested_fixture();
+        let unscanned = DirMetadata::new(root.path().to_str().unwrap()).unwrap();
+        assert_eq!(None, unscanned.scanned_at());
+        assert!(unscanned.is_stale(Duration::MAX));
+
+        let before = tai64::Tai64N::now();
+        let outcome = scan(root.path(), None);
+        let scanned_at = outcome.scanned_at().unwrap();
+
+        assert!(before <= scanned_at && scanned_at <= tai64::Tai64N::now());
+        assert!(outcome.scan_duration() > Duration::ZERO);
+        assert!(!outcome.is_stale(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn refresh_subdir() {
+        let root = nested_fixture();
+        let mut outcome = scan(root.path(), None);
+        let before = outcome.clone();
+        let c_txt = root.path().join("one").join("two").join("c.txt");
+        fs::write(&c_txt, b"changed content").unwrap();
+        fs::write(root.path().join("new.txt"), b"not refreshed").unwrap();
+
+        assert!(smol::block_on(outcome.refresh_subdir("one/two")).unwrap());
+
+        let changed = outcome
+            .files()
+            .iter()
+            .filter(|file| !before.files().contains(file))
+            .map(|file| file.path().into_owned())
+            .collect::<Vec<std::path::PathBuf>>();
+        assert_eq!(vec![c_txt.clone()], changed);
+        assert_eq!(before.files().len(), outcome.files().len());
+        assert_eq!(
+            before.size()
+                - before
+                    .files()
+                    .iter()
+                    .find(|file| file.path() == c_txt)
+                    .unwrap()
+                    .size
+                + "changed content".len() as u64,
+            outcome.size()
+        );
+
+        outcome.refresh_blocking().unwrap();
+        assert!(file_names(&outcome).contains(&"new.txt"));
+        assert_eq!(scan(root.path(), None).size(), outcome.size());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn error_counts() {
+        let root = nested_fixture();
+        std::os::unix::fs::symlink(root.path(), root.path().join("one").join("loop")).unwrap();
+
+        let mut outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .follow_symlinks(true)
+                .dir_metadata(),
+        )
+        .unwrap();
+        // The errors of a scan merged again are only counted once
+        outcome.merge(outcome.clone());
+
+        assert_eq!(3, outcome.file_count());
+        assert_eq!(outcome.directories().len(), outcome.dir_count());
+        assert_eq!(1, outcome.error_count());
+        assert_eq!(1, outcome.errors_by_kind().values().sum::<usize>());
+        assert_eq!(1, outcome.errors_for("one").len());
+        assert!(outcome.errors_for("one/two").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn root_metadata() {
+        let root = nested_fixture();
+        let outcome = scan(root.path(), None);
+        assert!(!outcome.root_is_symlink());
+        assert_eq!(
+            fs::metadata(root.path()).unwrap().modified().ok(),
+            outcome
+                .root_modified()
+                .map(|modified| modified.to_system_time())
+        );
+        #[cfg(feature = "unix")]
+        assert!(outcome
+            .root_permissions()
+            .is_some_and(|mode| mode & 0o700 == 0o700));
+
+        let link = tempfile::tempdir().unwrap();
+        let link = link.path().join("link");
+        std::os::unix::fs::symlink(root.path(), &link).unwrap();
+        let linked = scan(&link, None);
+        assert!(linked.root_is_symlink());
+        assert_eq!(outcome.root_modified(), linked.root_modified());
+        assert_eq!(file_names(&outcome), file_names(&linked));
+
+        let unscanned = DirMetadata::new(root.path().to_str().unwrap()).unwrap();
+        assert_eq!(None, unscanned.root_modified());
+    }
+
+    #[test]
+    fn directory_entries() {
+        let root = nested_fixture();
+        fs::create_dir(root.path().join("empty")).unwrap();
+
+        let outcome = scan(root.path(), None);
+        let entries = outcome.directory_entries();
+        assert_eq!(
+            outcome.directories(),
+            entries
+                .iter()
+                .map(|entry| entry.path().to_path_buf())
+                .collect::<Vec<_>>()
+        );
+
+        let entry = |name: &str| entries.iter().find(|entry| entry.name() == name).unwrap();
+        assert_eq!((0, Some(0), Some(0)), {
+            let empty = entry("empty");
+            (empty.depth(), empty.file_count(), empty.dir_count())
+        });
+        assert_eq!((0, Some(1), Some(1)), {
+            let one = entry("one");
+            (one.depth(), one.file_count(), one.dir_count())
+        });
+        assert_eq!(1, entry("two").depth());
+        #[cfg(feature = "time")]
+        assert_eq!(
+            fs::metadata(root.path().join("one"))
+                .unwrap()
+                .modified()
+                .ok(),
+            entry("one")
+                .modified()
+                .map(|modified| modified.to_system_time())
+        );
+
+        let limited = scan(root.path(), Some(1));
+        let two = limited
+            .directory_entries()
+            .iter()
+            .find(|entry| entry.name() == "two")
+            .unwrap();
+        assert_eq!(None, two.file_count());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn broken_links() {
+        let root = nested_fixture();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("kept.txt"), b"kept").unwrap();
+        fs::write(outside.path().join("moved.txt"), b"moved").unwrap();
+        std::os::unix::fs::symlink("one/missing", root.path().join("broken")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("kept.txt"), root.path().join("kept"))
+            .unwrap();
+        std::os::unix::fs::symlink(outside.path().join("moved.txt"), root.path().join("moved"))
+            .unwrap();
+        fs::remove_file(outside.path().join("moved.txt")).unwrap();
+
+        let outcome = scan(root.path(), None);
+        let mut broken = outcome
+            .broken_links()
+            .iter()
+            .map(|file| file.name())
+            .collect::<Vec<&str>>();
+        broken.sort();
+        assert_eq!(vec!["broken", "moved"], broken);
+        assert_eq!(2, outcome.stats().broken_links);
+    }
 }
@@ -0,0 +1,199 @@
+use crate::DirMetadata;
+use sha2::{Digest, Sha256};
+use smol::{io, unblock};
+use std::{
+    fmt::Write,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// The algorithm used to compute the digest of the content of files,
+/// passed to [DirMetadata::hash_files]
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum HashAlgo {
+    /// SHA-256, the digests can be checked with `sha256sum -c`
+    #[default]
+    Sha256,
+}
+
+impl HashAlgo {
+    /// The size of the chunks read from a file while hashing it
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    /// Compute the digest of the content of the file at `path` in lowercase hexadecimal.
+    /// The file is read in chunks so large files are never fully loaded into memory.
+    /// This blocks the current thread
+    pub fn digest_file(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        let mut file = File::open(path)?;
+        let mut buffer = vec![0u8; Self::CHUNK_SIZE];
+
+        let digest = match self {
+            Self::Sha256 => {
+                let mut hasher = Sha256::new();
+
+                loop {
+                    match file.read(&mut buffer) {
+                        Ok(0) => break,
+                        Ok(read) => hasher.update(&buffer[..read]),
+                        Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(error) => return Err(error),
+                    }
+                }
+
+                hasher.finalize()
+            }
+        };
+
+        Ok(digest
+            .iter()
+            .fold(String::with_capacity(digest.len() * 2), |mut hex, byte| {
+                write!(hex, "{byte:02x}").ok();
+
+                hex
+            }))
+    }
+}
+
+/// A file listed in a manifest that no longer matches it,
+/// returned by [DirMetadata::verify_manifest]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ManifestMismatch {
+    /// The content of the file changed since the manifest was written
+    Changed(PathBuf),
+    /// The file could not be read, usually because it was removed
+    Missing(PathBuf),
+}
+
+impl DirMetadata<'_> {
+    /// Write the digests computed with [DirMetadata::hash_files] to the file at `manifest_path`
+    /// in the format of `sha256sum`, with the paths relative to [DirMetadata::dir_path].
+    /// Running `sha256sum -c` on the manifest from [DirMetadata::dir_path] checks the files.
+    /// Files without a digest are not written
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{DirMetadata, HashAlgo};
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .hash_files(HashAlgo::Sha256)
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///     let manifest = std::env::temp_dir().join("dir-meta-manifest.sha256");
+    ///
+    ///     outcome.write_manifest(&manifest).await.unwrap();
+    ///     assert!(outcome.verify_manifest(&manifest).await.unwrap().is_empty());
+    /// });
+    /// ```
+    pub async fn write_manifest(&self, manifest_path: impl AsRef<Path>) -> io::Result<()> {
+        let manifest = self
+            .files()
+            .iter()
+            .filter_map(|file| {
+                let digest = file.digest()?;
+                let path = file.path();
+                let relative = path.strip_prefix(self.dir_path()).ok()?;
+
+                Some(format!("{}  {}\n", digest, relative.display()))
+            })
+            .collect::<String>();
+
+        smol::fs::write(manifest_path, manifest).await
+    }
+
+    /// Hash the files listed in the manifest at `manifest_path` again, returning the files
+    /// whose content changed or that could not be read. The paths in the manifest are
+    /// relative to [DirMetadata::dir_path] like those written by [DirMetadata::write_manifest]
+    pub async fn verify_manifest(
+        &self,
+        manifest_path: impl AsRef<Path>,
+    ) -> io::Result<Vec<ManifestMismatch>> {
+        let manifest = smol::fs::read_to_string(manifest_path).await?;
+        let hash_algo = self.hash_algo().unwrap_or_default();
+        let mut mismatches = Vec::<ManifestMismatch>::new();
+
+        for line in manifest.lines().filter(|line| !line.is_empty()) {
+            let Some((expected, relative)) = line.split_once(' ') else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid line in manifest `{line}`"),
+                ));
+            };
+            // `sha256sum` marks files read in binary mode with a `*`
+            let relative = relative
+                .strip_prefix(' ')
+                .or_else(|| relative.strip_prefix('*'))
+                .unwrap_or(relative);
+
+            let path = self.dir_path().join(relative);
+            let cloned_path = path.clone();
+
+            match unblock(move || hash_algo.digest_file(cloned_path)).await {
+                Ok(digest) if digest.eq_ignore_ascii_case(expected) => (),
+                Ok(_) => mismatches.push(ManifestMismatch::Changed(path)),
+                Err(_) => mismatches.push(ManifestMismatch::Missing(path)),
+            }
+        }
+
+        Ok(mismatches)
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+    use crate::{test_utils::nested_fixture, DirMetadata};
+    use std::fs;
+
+    #[test]
+    fn hash_files() {
+        use crate::{HashAlgo, ManifestMismatch};
+
+        let root = nested_fixture();
+        let outcome = smol::block_on(
+            DirMetadata::new(root.path().to_str().unwrap())
+                .unwrap()
+                .hash_files(HashAlgo::Sha256)
+                .concurrency(2)
+                .dir_metadata(),
+        )
+        .unwrap();
+        let a_txt = outcome.get_file_by_path(root.path().join("a.txt")).unwrap();
+        assert_eq!(
+            Some("ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"),
+            a_txt.digest()
+        );
+        assert!(outcome.files().iter().all(|file| file.digest().is_some()));
+
+        let manifest = tempfile::NamedTempFile::new().unwrap();
+        smol::block_on(outcome.write_manifest(manifest.path())).unwrap();
+        assert!(smol::block_on(outcome.verify_manifest(manifest.path()))
+            .unwrap()
+            .is_empty());
+
+        if let Ok(status) = std::process::Command::new("sha256sum")
+            .arg("--check")
+            .arg("--quiet")
+            .arg(manifest.path())
+            .current_dir(root.path())
+            .status()
+        {
+            assert!(status.success());
+        }
+
+        fs::write(root.path().join("a.txt"), b"changed").unwrap();
+        fs::remove_file(root.path().join("one").join("b.txt")).unwrap();
+        let mut mismatches = smol::block_on(outcome.verify_manifest(manifest.path())).unwrap();
+        mismatches.sort_by_key(|mismatch| format!("{mismatch:?}"));
+        assert_eq!(
+            vec![
+                ManifestMismatch::Changed(root.path().join("a.txt")),
+                ManifestMismatch::Missing(root.path().join("one").join("b.txt")),
+            ],
+            mismatches
+        );
+    }
+}
@@ -0,0 +1,467 @@
+//! The watcher backend built on inotify, used on Linux with the `watcher` feature
+
+use crate::{
+    watcher::{PendingMoves, Wake},
+    FsWatcher, WatchFor, WatcherError, WatcherEvents, WatcherOutcome,
+};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use smol::{channel::Receiver, future, io, Async};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ffi::OsStr,
+    os::fd::AsFd,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+impl WatchFor {
+    /// The inotify mask of each option
+    const MASKS: &'static [(WatchFor, WatchMask)] = &[
+        (Self::CREATE, WatchMask::CREATE),
+        (Self::DELETE, WatchMask::DELETE),
+        (Self::MODIFY, WatchMask::MODIFY),
+        (Self::MOVE, WatchMask::MOVE),
+        (Self::ATTRIB, WatchMask::ATTRIB),
+        (Self::ACCESS, WatchMask::ACCESS),
+        (Self::OPEN, WatchMask::OPEN),
+        (Self::CLOSE, WatchMask::CLOSE),
+        (Self::DELETE_SELF, WatchMask::DELETE_SELF),
+        (Self::MOVE_SELF, WatchMask::MOVE_SELF),
+    ];
+}
+
+/// #### Example
+/// ```rust
+/// use dir_meta::{inotify::WatchMask, WatchFor};
+///
+/// assert_eq!(
+///     WatchMask::CREATE | WatchMask::DELETE,
+///     WatchMask::from(WatchFor::CREATE | WatchFor::DELETE)
+/// );
+/// ```
+impl From<WatchFor> for WatchMask {
+    fn from(watch_for: WatchFor) -> Self {
+        WatchFor::MASKS
+            .iter()
+            .filter(|(option, _)| watch_for.contains(*option))
+            .fold(WatchMask::empty(), |mask, (_, option_mask)| {
+                mask | *option_mask
+            })
+    }
+}
+
+impl FsWatcher {
+    /// The time a [Self::resilient] watcher waits before the first check
+    /// for a deleted path to reappear, doubled after each check
+    const REATTACH_FIRST_DELAY: Duration = Duration::from_millis(10);
+
+    /// Watches the paths until the [WatcherHandle] owning the other half of `control`
+    /// is stopped or dropped, or until the watches of all the paths have been removed
+    pub(crate) async fn watch_until(
+        self,
+        watch_for: WatchMask,
+        control: Option<Receiver<PathBuf>>,
+    ) -> Result<(), WatcherError> {
+        let mut watches = self.add_watches(watch_for)?;
+
+        //TODO add logging here "Watching current directory for activity..."
+
+        // A duplicate of the inotify file descriptor registered with the reactor
+        // so that waiting for events yields to the executor instead of blocking it
+        let readiness = Async::new(watches.inotify.as_fd().try_clone_to_owned()?)?;
+
+        let mut pending_moves = PendingMoves::default();
+
+        let mut buffer = vec![0u8; self.buffer_size];
+
+        loop {
+            if let Some(control) = control.as_ref() {
+                while let Ok(path) = control.try_recv() {
+                    watches.unwatch(&path);
+                }
+
+                if control.is_closed() {
+                    break;
+                }
+            }
+
+            if watches.watched.is_empty() && watches.detached.is_empty() {
+                break;
+            }
+
+            for outcome in watches.reattach() {
+                self.send(outcome).await?;
+            }
+
+            self.flush()?;
+
+            for moved_from in pending_moves.expired() {
+                if self.passes(moved_from.name.as_deref(), moved_from.path.as_deref()) {
+                    self.send(moved_from).await?;
+                }
+            }
+
+            let events = match watches.inotify.read_events(&mut buffer) {
+                Ok(events) => events,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    let readable = async { readiness.readable().await.map(|_| Wake::Continue) };
+                    // A pending move expires, a detached path should be checked again
+                    // or the held outcomes should be sent again
+                    let deadline = pending_moves
+                        .deadline()
+                        .into_iter()
+                        .chain(watches.detached.iter().map(|(_, check_at, _)| *check_at))
+                        .chain(self.retry_deadline())
+                        .min();
+
+                    match future::or(readable, Wake::wait(control.as_ref(), deadline)).await? {
+                        Wake::Continue => (),
+                        Wake::Unwatch(path) => watches.unwatch(&path),
+                        Wake::Stop => break,
+                    }
+
+                    continue;
+                }
+                Err(error) => return Err(error.into()),
+            };
+
+            for event in events {
+                let descriptor = event.wd.clone();
+                let Some(outcome) = self.admit(watches.outcome(event), &mut pending_moves) else {
+                    continue;
+                };
+
+                let ignored = outcome.events.contains(&WatcherEvents::Ignored);
+                let moved_away = outcome.events.contains(&WatcherEvents::MoveSelf);
+                let overflowed = outcome.mask == WatcherEvents::QueueOverflow;
+                self.send(outcome).await?;
+
+                if overflowed {
+                    self.rescan(watches.watched.values()).await;
+                }
+
+                // The watch was removed, either explicitly or because the path is gone
+                if ignored {
+                    watches.forget(descriptor, self.resilient);
+                } else if moved_away && self.resilient {
+                    // The watch follows the moved entry, it is removed so that the path
+                    // is watched again once an entry replaces it
+                    watches.inotify.watches().remove(descriptor).ok();
+                }
+            }
+        }
+
+        self.flush().ok();
+
+        // The watched path may already be gone, which removes its watch
+        watches.watched.into_keys().for_each(|descriptor| {
+            watches.inotify.watches().remove(descriptor).ok();
+        });
+
+        Ok(())
+    }
+
+    /// The outcomes of [FsWatcher::watch_blocking], the events are read with
+    /// `Inotify::read_events_blocking` on the calling thread
+    pub(crate) fn blocking_outcomes(
+        self,
+        watch_for: WatchMask,
+    ) -> impl Iterator<Item = Result<WatcherOutcome, WatcherError>> {
+        let (mut watches, mut failed) = match self.add_watches(watch_for) {
+            Ok(watches) => (Some(watches), None),
+            Err(error) => (None, Some(error)),
+        };
+        let mut buffer = vec![0u8; self.buffer_size];
+        let mut pending_moves = PendingMoves::default();
+        let mut ready = VecDeque::<WatcherOutcome>::new();
+
+        std::iter::from_fn(move || loop {
+            if let Some(error) = failed.take() {
+                return Some(Err(error));
+            }
+
+            if let Some(outcome) = ready.pop_front() {
+                return Some(Ok(outcome));
+            }
+
+            let watches = watches.as_mut()?;
+            if watches.watched.is_empty() {
+                return None;
+            }
+
+            let events = match watches.inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => events,
+                Err(error) => {
+                    watches.watched.clear();
+
+                    return Some(Err(error.into()));
+                }
+            };
+
+            for event in events {
+                let descriptor = event.wd.clone();
+                let outcome = watches.outcome(event);
+                let ignored = outcome.events.contains(&WatcherEvents::Ignored);
+
+                if let Some(outcome) = self.admit(outcome, &mut pending_moves) {
+                    ready.push_back(outcome);
+                }
+
+                if ignored {
+                    watches.forget(descriptor, false);
+                }
+            }
+
+            // Without a timeout the renames are only paired when both halves are read together
+            ready.extend(pending_moves.drain().filter(|moved_from| {
+                self.passes(moved_from.name.as_deref(), moved_from.path.as_deref())
+            }));
+        })
+    }
+
+    /// Initializes inotify and adds a watch for each path
+    fn add_watches(&self, watch_for: WatchMask) -> Result<Watches, WatcherError> {
+        if self.paths.is_empty() {
+            return Err(WatcherError::PathNotSet);
+        }
+
+        let mut watches = Watches {
+            inotify: Inotify::init()?,
+            watch_for,
+            watched: HashMap::default(),
+            unwatched: HashSet::default(),
+            detached: Vec::default(),
+        };
+        for path in &self.paths {
+            let descriptor = watches
+                .inotify
+                .watches()
+                .add(path, watch_for)
+                .map_err(|error| match error.kind() {
+                    // `ENOSPC` is only returned when `max_user_watches` is reached
+                    io::ErrorKind::StorageFull => WatcherError::WatchLimitReached,
+                    _ => WatcherError::Io(error),
+                })?;
+            watches.watched.insert(descriptor, path.clone());
+        }
+
+        Ok(watches)
+    }
+}
+
+/// The watches of a running [FsWatcher]
+struct Watches {
+    inotify: Inotify,
+    watch_for: WatchMask,
+    /// The watched path of each watch, used to build the path of the entry of an event
+    watched: HashMap<WatchDescriptor, PathBuf>,
+    /// The watches removed by [WatcherHandle::unwatch] which are not watched again
+    unwatched: HashSet<WatchDescriptor>,
+    /// The paths of a resilient watcher waiting to reappear,
+    /// with the time of the next check and the delay before the check after it
+    detached: Vec<(PathBuf, Instant, Duration)>,
+}
+
+impl Watches {
+    /// The outcome of an event with the paths of its watch
+    fn outcome(&self, event: inotify::Event<&OsStr>) -> WatcherOutcome {
+        let watched_path = self.watched.get(&event.wd).cloned();
+        let entry_path = watched_path.as_ref().map(|watched_path| match event.name {
+            Some(name) => watched_path.join(name),
+            None => watched_path.clone(),
+        });
+        let mut outcome: WatcherOutcome = event.into();
+        outcome.path = entry_path;
+        outcome.watched_path = watched_path;
+
+        outcome
+    }
+
+    /// Removes the watches of `path`, each of them is forgotten once its
+    /// [WatcherEvents::Ignored] event has been sent
+    fn unwatch(&mut self, path: &Path) {
+        self.detached
+            .retain(|(detached_path, _, _)| detached_path != path);
+
+        self.watched
+            .iter()
+            .filter(|(_, watched_path)| watched_path.as_path() == path)
+            .for_each(|(descriptor, _)| {
+                // The watch may already be gone with the path
+                self.inotify.watches().remove(descriptor.clone()).ok();
+                self.unwatched.insert(descriptor.clone());
+            });
+    }
+
+    /// Forgets a watch that was removed, its path waits to reappear if `resilient`
+    /// unless it was removed by [Self::unwatch]
+    fn forget(&mut self, descriptor: WatchDescriptor, resilient: bool) {
+        let Some(path) = self.watched.remove(&descriptor) else {
+            return;
+        };
+
+        if !self.unwatched.remove(&descriptor) && resilient {
+            self.detached.push((
+                path,
+                Instant::now() + FsWatcher::REATTACH_FIRST_DELAY,
+                FsWatcher::REATTACH_FIRST_DELAY * 2,
+            ));
+        }
+    }
+
+    /// Watches again the detached paths that reappeared, returning a
+    /// [WatcherEvents::Reattached] outcome for each of them
+    fn reattach(&mut self) -> Vec<WatcherOutcome> {
+        let now = Instant::now();
+        let mut outcomes = Vec::<WatcherOutcome>::new();
+
+        for (path, check_at, delay) in std::mem::take(&mut self.detached) {
+            if check_at > now {
+                self.detached.push((path, check_at, delay));
+
+                continue;
+            }
+
+            match self.inotify.watches().add(&path, self.watch_for) {
+                Ok(descriptor) => {
+                    outcomes.push(WatcherOutcome {
+                        descriptor: descriptor.get_watch_descriptor_id(),
+                        mask: WatcherEvents::Reattached,
+                        events: vec![WatcherEvents::Reattached],
+                        is_dir: path.is_dir(),
+                        cookie: 0,
+                        name: None,
+                        path: Some(path.clone()),
+                        watched_path: Some(path.clone()),
+                        old_name: None,
+                        old_path: None,
+                        dropped: 0,
+                    });
+                    self.watched.insert(descriptor, path);
+                }
+                Err(_) => self.detached.push((
+                    path,
+                    now + delay,
+                    (delay * 2).min(FsWatcher::REATTACH_MAX_DELAY),
+                )),
+            }
+        }
+
+        outcomes
+    }
+}
+
+impl WatcherEvents {
+    /// The events that can be set in an [EventMask] besides [EventMask::ISDIR],
+    /// in the order the primary event of a combined mask is chosen
+    const EVENTS: &'static [(EventMask, WatcherEvents)] = &[
+        (EventMask::Q_OVERFLOW, Self::QueueOverflow),
+        (EventMask::UNMOUNT, Self::Unmount),
+        (EventMask::IGNORED, Self::Ignored),
+        (EventMask::CREATE, Self::Create),
+        (EventMask::DELETE, Self::Delete),
+        (EventMask::DELETE_SELF, Self::DeleteSelf),
+        (EventMask::MOVED_FROM, Self::MovedFrom),
+        (EventMask::MOVED_TO, Self::MovedTo),
+        (EventMask::MOVE_SELF, Self::MoveSelf),
+        (EventMask::MODIFY, Self::Modify),
+        (EventMask::ATTRIB, Self::Attrib),
+        (EventMask::CLOSE_WRITE, Self::CloseWrite),
+        (EventMask::CLOSE_NOWRITE, Self::CloseNoWrite),
+        (EventMask::OPEN, Self::Open),
+        (EventMask::ACCESS, Self::Access),
+    ];
+
+    /// Every event set in a mask that can combine several bits, like `CREATE | ISDIR`
+    /// which is split into [Self::Create] and [Self::IsDir]
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{inotify::EventMask, WatcherEvents};
+    ///
+    /// assert_eq!(
+    ///     vec![WatcherEvents::Delete, WatcherEvents::IsDir],
+    ///     WatcherEvents::split(EventMask::DELETE | EventMask::ISDIR)
+    /// );
+    /// ```
+    pub fn split(mask: EventMask) -> Vec<Self> {
+        let mut events = Self::EVENTS
+            .iter()
+            .filter(|(event_mask, _)| mask.contains(*event_mask))
+            .map(|(_, event)| *event)
+            .collect::<Vec<Self>>();
+
+        if mask.contains(EventMask::ISDIR) {
+            events.push(Self::IsDir);
+        }
+
+        events
+    }
+}
+
+/// Converts the primary event of a mask, [EventMask::ISDIR] is only reported
+/// as [WatcherEvents::IsDir] if no other event is set
+impl From<EventMask> for WatcherEvents {
+    fn from(value: EventMask) -> Self {
+        match Self::EVENTS
+            .iter()
+            .find(|(event_mask, _)| value.contains(*event_mask))
+        {
+            Some((_, event)) => *event,
+            None if value.contains(EventMask::ISDIR) => Self::IsDir,
+            None => Self::Unsupported,
+        }
+    }
+}
+
+impl From<inotify::Event<&OsStr>> for WatcherOutcome {
+    fn from(event: inotify::Event<&OsStr>) -> Self {
+        let name = event
+            .name
+            .map(|inner_name| inner_name.to_string_lossy().to_string());
+
+        Self {
+            descriptor: event.wd.get_watch_descriptor_id(),
+            mask: event.mask.into(),
+            events: WatcherEvents::split(event.mask),
+            is_dir: event.mask.contains(EventMask::ISDIR),
+            cookie: event.cookie,
+            name,
+            path: None,
+            watched_path: None,
+            old_name: None,
+            old_path: None,
+            dropped: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+
+    #[test]
+    fn combined_event_masks() {
+        use crate::{inotify::EventMask, WatcherEvents};
+
+        assert_eq!(WatcherEvents::Create, EventMask::CREATE.into());
+        assert_eq!(
+            WatcherEvents::Create,
+            (EventMask::CREATE | EventMask::ISDIR).into()
+        );
+        assert_eq!(
+            WatcherEvents::Delete,
+            (EventMask::DELETE | EventMask::ISDIR).into()
+        );
+        assert_eq!(WatcherEvents::IsDir, EventMask::ISDIR.into());
+        assert_eq!(WatcherEvents::Unsupported, EventMask::empty().into());
+
+        assert_eq!(
+            vec![WatcherEvents::MovedFrom, WatcherEvents::IsDir],
+            WatcherEvents::split(EventMask::MOVED_FROM | EventMask::ISDIR)
+        );
+        assert_eq!(
+            vec![WatcherEvents::Ignored, WatcherEvents::DeleteSelf],
+            WatcherEvents::split(EventMask::DELETE_SELF | EventMask::IGNORED)
+        );
+        assert!(WatcherEvents::split(EventMask::empty()).is_empty());
+    }
+}
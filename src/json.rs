@@ -0,0 +1,145 @@
+use crate::{DirMetadata, DirNode, FileMetadata, FsUtils};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use serde_json::Value;
+
+impl DirMetadata<'_> {
+    /// Serialize the scan to JSON with the files as a flat list. Besides the size in bytes,
+    /// the scan and each file have a `size_formatted` field in human readable format.
+    /// Returns an error if a path is not valid UTF-8, [Self::to_json_tree] replaces
+    /// the invalid characters instead
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     println!("{}", outcome.to_json().unwrap());
+    /// });
+    /// ```
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&self.to_json_value()?)
+    }
+
+    /// Same as [Self::to_json] but indented to be read by people
+    pub fn to_json_pretty(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.to_json_value()?)
+    }
+
+    /// Serialize the scan to JSON as nested directories like [Self::as_tree] arranges them,
+    /// the shape expected by treemap visualizers. Each directory has a `name`, `path`, `size`,
+    /// `size_formatted`, the `files` directly inside it and its sub-directories as `children`.
+    /// Names and paths that are not valid UTF-8 have the invalid characters replaced
+    /// with `U+FFFD REPLACEMENT CHARACTER`
+    pub fn to_json_tree(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&self.as_tree())
+    }
+
+    fn to_json_value(&self) -> serde_json::Result<Value> {
+        let mut value = serde_json::to_value(self)?;
+        value["size_formatted"] = self.size_formatted().into();
+
+        if let Some(files) = value["files"].as_array_mut() {
+            files
+                .iter_mut()
+                .zip(self.files())
+                .try_for_each(|(value, file)| {
+                    // Only the directory of a file is stored, its full path is added back
+                    value["path"] = serde_json::to_value(file.path())?;
+                    value["size_formatted"] = file.formatted_size().into();
+
+                    Ok::<_, serde_json::Error>(())
+                })?;
+        }
+
+        Ok(value)
+    }
+}
+
+impl Serialize for DirNode<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut node = serializer.serialize_struct("DirNode", 6)?;
+        node.serialize_field("name", self.name())?;
+        node.serialize_field("path", &self.path().to_string_lossy())?;
+        node.serialize_field("size", &self.size())?;
+        node.serialize_field("size_formatted", &FsUtils::size_to_bytes(self.size()))?;
+        node.serialize_field(
+            "files",
+            &self
+                .files()
+                .iter()
+                .map(|file| TreeFile(file))
+                .collect::<Vec<TreeFile>>(),
+        )?;
+        node.serialize_field("children", self.children())?;
+        node.end()
+    }
+}
+
+/// A file inside a [DirNode] serialized by [DirMetadata::to_json_tree]
+struct TreeFile<'f>(&'f FileMetadata<'f>);
+
+impl Serialize for TreeFile<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut file = serializer.serialize_struct("TreeFile", 4)?;
+        file.serialize_field("name", self.0.name())?;
+        file.serialize_field("path", &self.0.path().to_string_lossy())?;
+        file.serialize_field("size", &self.0.size())?;
+        file.serialize_field("size_formatted", &self.0.formatted_size())?;
+        file.end()
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+    use crate::test_utils::{nested_fixture, scan};
+
+    #[test]
+    fn json_export() {
+        use serde_json::Value;
+
+        let root = nested_fixture();
+        let outcome = scan(root.path(), None);
+
+        let flat = serde_json::from_str::<Value>(&outcome.to_json().unwrap()).unwrap();
+        assert_eq!(6, flat["size"]);
+        assert_eq!(outcome.size_formatted(), flat["size_formatted"]);
+        assert_eq!(3, flat["files"].as_array().unwrap().len());
+        assert_eq!(
+            outcome.files()[0].formatted_size(),
+            flat["files"][0]["size_formatted"]
+        );
+
+        let tree = serde_json::from_str::<Value>(&outcome.to_json_tree().unwrap()).unwrap();
+        assert_eq!(6, tree["size"]);
+        assert_eq!("a.txt", tree["files"][0]["name"]);
+        assert_eq!("one", tree["children"][0]["name"]);
+        assert_eq!(5, tree["children"][0]["size"]);
+        assert_eq!("two", tree["children"][0]["children"][0]["name"]);
+        assert_eq!(
+            "c.txt",
+            tree["children"][0]["children"][0]["files"][0]["name"]
+        );
+
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt};
+
+            let name = OsStr::from_bytes(b"invalid-\xff.txt");
+            fs::write(root.path().join(name), b"bytes").unwrap();
+            let outcome = scan(root.path(), Some(0));
+
+            assert!(outcome.to_json().is_err());
+            let tree = serde_json::from_str::<Value>(&outcome.to_json_tree().unwrap()).unwrap();
+            assert!(tree["files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|file| file["name"] == "invalid-\u{FFFD}.txt"));
+        }
+    }
+}
@@ -8,30 +8,113 @@ pub use utils::*;
 mod fs;
 pub use fs::*;
 
-#[cfg(feature = "time")]
+mod tree;
+pub use tree::*;
+
+mod summary;
+pub use summary::*;
+
+mod export;
+
+mod portability;
+
+mod audit;
+pub use audit::*;
+
+#[cfg(feature = "hash")]
+mod hash;
+#[cfg(feature = "hash")]
+pub use hash::*;
+
+#[cfg(feature = "serde")]
+mod serialize;
+
+#[cfg(feature = "serde")]
+mod json;
+
+#[cfg(feature = "serde")]
+mod snapshot;
+
+#[cfg(feature = "tokio")]
+mod tokio_runtime;
+
+#[cfg(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+))]
 mod watcher;
-/// This directory inherits most types from `inotify` crate
-#[cfg(feature = "time")]
+/// The watcher API shared by the inotify backend of the `watcher` feature on Linux
+/// and the `notify` backend of the `watcher-portable` feature
+#[cfg(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+))]
 pub use watcher::*;
 
-pub use async_recursion;
-pub use byte_prefix;
+#[cfg(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+))]
+mod live;
+#[cfg(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+))]
+pub use live::*;
+
+#[cfg(all(feature = "watcher", target_os = "linux"))]
+mod inotify_watcher;
+
+#[cfg(all(
+    feature = "watcher-portable",
+    not(all(feature = "watcher", target_os = "linux"))
+))]
+mod notify_watcher;
+
+#[cfg(feature = "serde")]
+pub use bincode;
 #[cfg(feature = "time")]
 pub use chrono;
 pub use file_format;
+pub use globset;
 #[cfg(feature = "time")]
 pub use humantime;
-#[cfg(feature = "watcher")]
+#[cfg(feature = "gitignore")]
+pub use ignore;
+#[cfg(all(feature = "watcher", target_os = "linux"))]
 pub use inotify;
+#[cfg(feature = "watcher-portable")]
+pub use notify;
+#[cfg(feature = "rayon")]
+pub use rayon;
+#[cfg(feature = "regex")]
+pub use regex;
+#[cfg(feature = "serde")]
+pub use serde;
+#[cfg(feature = "serde")]
+pub use serde_json;
+#[cfg(feature = "hash")]
+pub use sha2;
 pub use smol;
 pub use tai64;
+#[cfg(feature = "tokio")]
+pub use tokio;
+#[cfg(feature = "unicode")]
+pub use unicode_normalization;
+
+#[cfg(test)]
+mod test_utils;
 
 #[cfg(test)]
 mod sanity_checks {
     #[test]
     fn ineq() {
         smol::block_on(async {
-            let outcome = crate::DirMetadata::new("src").dir_metadata().await.unwrap();
+            let outcome = crate::DirMetadata::new("src")
+                .unwrap()
+                .dir_metadata()
+                .await
+                .unwrap();
 
             dbg!(&outcome);
             dbg!(outcome.size_formatted());
@@ -0,0 +1,260 @@
+//! A scan kept up to date by the outcomes of a [crate::FsWatcher]
+
+use crate::{DirMetadata, WatcherEvents, WatcherOutcome};
+use smol::{
+    channel::{self, Receiver, Sender},
+    io,
+};
+use std::path::{Path, PathBuf};
+
+/// A change applied by a [LiveDir] to its snapshot
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LiveChange {
+    /// A file or a directory with its content was added
+    Added(PathBuf),
+    /// A file or a directory with its content was removed
+    Removed(PathBuf),
+    /// A file or a directory with its content was read again, like the size
+    /// and modification time of a modified file
+    Modified(PathBuf),
+    /// A file or a directory with its content was moved from the first path to the second one
+    Moved(PathBuf, PathBuf),
+    /// The snapshot was replaced with a new scan since events were lost
+    Rescanned,
+}
+
+/// A [DirMetadata] snapshot kept up to date by applying the outcomes of a [crate::FsWatcher].
+/// New and modified files are read again and new directories are scanned with the options
+/// of the initial scan, deleted entries are removed and the sizes are updated along.
+/// A [WatcherEvents::QueueOverflow] or [WatcherEvents::Dropped] outcome scans everything again.
+///
+/// The watcher has to watch the directories of the snapshot by the same paths, see
+/// [Self::watched_paths]. A directory created later is scanned but not watched
+/// #### Example
+/// ```rust
+/// use dir_meta::{smol::channel, DirMetadata, FsWatcher, LiveDir, WatchFor};
+///
+/// smol::block_on(async {
+///     let scan = DirMetadata::new("src")
+///         .unwrap()
+///         .dir_metadata()
+///         .await
+///         .unwrap();
+///     let (sender, receiver) = channel::unbounded();
+///     let mut live = LiveDir::new(scan, receiver);
+///
+///     let (handle, watch) = FsWatcher::new(sender)
+///         .paths(live.watched_paths())
+///         .watch_with_handle(WatchFor::FILE_CHANGES | WatchFor::ATTRIB);
+///     smol::spawn(watch).detach();
+///     handle.stop();
+///
+///     while live.update().await.unwrap() {
+///         dbg!(live.snapshot().size());
+///     }
+/// });
+/// ```
+#[derive(Debug)]
+pub struct LiveDir<'a> {
+    snapshot: DirMetadata<'a>,
+    events: Receiver<WatcherOutcome>,
+    changes: Option<Sender<LiveChange>>,
+}
+
+impl<'a> LiveDir<'a> {
+    /// Create a [LiveDir] from an initial scan and the receiver of the channel
+    /// passed to [crate::FsWatcher::new]
+    pub fn new(snapshot: DirMetadata<'a>, events: Receiver<WatcherOutcome>) -> Self {
+        Self {
+            snapshot,
+            events,
+            changes: None,
+        }
+    }
+
+    /// The paths to watch to keep the snapshot up to date, the roots of the scan
+    /// followed by all its directories
+    pub fn watched_paths(&self) -> Vec<PathBuf> {
+        self.snapshot
+            .roots()
+            .iter()
+            .chain(self.snapshot.directories())
+            .cloned()
+            .collect()
+    }
+
+    /// The snapshot with all the outcomes received so far applied
+    pub fn snapshot(&self) -> &DirMetadata<'a> {
+        &self.snapshot
+    }
+
+    /// Stop applying outcomes and get the snapshot
+    pub fn into_snapshot(self) -> DirMetadata<'a> {
+        self.snapshot
+    }
+
+    /// Get a receiver of each [LiveChange] applied from now on.
+    /// Calling this again replaces the previous channel
+    pub fn changes(&mut self) -> Receiver<LiveChange> {
+        let (sender, receiver) = channel::unbounded();
+        self.changes.replace(sender);
+
+        receiver
+    }
+
+    /// Wait for the next outcome of the watcher and apply it. Returns `Ok(false)`
+    /// once the watcher has stopped and all its outcomes have been applied
+    pub async fn update(&mut self) -> io::Result<bool> {
+        match self.events.recv().await {
+            Ok(outcome) => {
+                self.apply(&outcome).await?;
+
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Apply a single outcome of the watcher to the snapshot.
+    /// Returns an error if a directory could not be scanned
+    pub async fn apply(&mut self, outcome: &WatcherOutcome) -> io::Result<()> {
+        match outcome.mask {
+            WatcherEvents::QueueOverflow | WatcherEvents::Dropped | WatcherEvents::Reattached => {
+                self.snapshot.refresh().await?;
+                self.notify(LiveChange::Rescanned);
+            }
+            WatcherEvents::Renamed => {
+                let (Some(old_path), Some(path)) = (&outcome.old_path, &outcome.path) else {
+                    return Ok(());
+                };
+
+                let moved = self.knows(old_path);
+                self.snapshot.refresh_path(old_path).await?;
+                let added = self.snapshot.refresh_path(path).await?;
+
+                match (moved, added) {
+                    (true, true) => self.notify(LiveChange::Moved(old_path.clone(), path.clone())),
+                    (true, false) => self.notify(LiveChange::Removed(old_path.clone())),
+                    (false, true) => self.notify(LiveChange::Added(path.clone())),
+                    (false, false) => (),
+                }
+            }
+            WatcherEvents::Create
+            | WatcherEvents::Delete
+            | WatcherEvents::Modify
+            | WatcherEvents::Attrib
+            | WatcherEvents::CloseWrite
+            | WatcherEvents::MovedFrom
+            | WatcherEvents::MovedTo => {
+                let Some(path) = &outcome.path else {
+                    return Ok(());
+                };
+
+                let known = self.knows(path);
+                let present = self.snapshot.refresh_path(path).await?;
+
+                match (known, present) {
+                    (true, true) => self.notify(LiveChange::Modified(path.clone())),
+                    (true, false) => self.notify(LiveChange::Removed(path.clone())),
+                    (false, true) => self.notify(LiveChange::Added(path.clone())),
+                    (false, false) => (),
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Check if the snapshot has a file or a directory at `path`
+    fn knows(&self, path: &Path) -> bool {
+        self.snapshot.contains_path(path)
+            || self
+                .snapshot
+                .directories()
+                .iter()
+                .any(|directory| directory == path)
+    }
+
+    fn notify(&mut self, change: LiveChange) {
+        let closed = self
+            .changes
+            .as_ref()
+            .is_some_and(|changes| changes.try_send(change).is_err());
+
+        if closed {
+            self.changes = None;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "watcher", target_os = "linux"))]
+mod sanity_checks {
+    use crate::test_utils::{file_names, nested_fixture, scan};
+    use std::fs;
+
+    #[test]
+    fn live_dir() {
+        use crate::{FsWatcher, LiveChange, LiveDir, WatchFor};
+        use smol::{channel, future, Timer};
+        use std::time::Duration;
+
+        let root = nested_fixture();
+        let outside = tempfile::tempdir().unwrap();
+        let (sender, receiver) = channel::unbounded();
+        let mut live = LiveDir::new(scan(root.path(), None), receiver);
+        let changes = live.changes();
+        let (_handle, watch) = FsWatcher::new(sender)
+            .paths(live.watched_paths())
+            .watch_with_handle(WatchFor::FILE_CHANGES);
+        smol::spawn(watch).detach();
+        // Lets the watch add its watches before the files are changed
+        smol::block_on(Timer::after(Duration::from_millis(50)));
+
+        fs::write(root.path().join("one").join("d.txt"), b"dddd").unwrap();
+        fs::write(root.path().join("a.txt"), b"aaaaa").unwrap();
+        fs::remove_file(root.path().join("one").join("two").join("c.txt")).unwrap();
+        fs::create_dir(outside.path().join("three")).unwrap();
+        fs::write(outside.path().join("three").join("e.txt"), b"ee").unwrap();
+        fs::rename(outside.path().join("three"), root.path().join("three")).unwrap();
+
+        let expected = scan(root.path(), None);
+        smol::block_on(future::or(
+            async {
+                while live.snapshot().size() != expected.size()
+                    || file_names(live.snapshot()) != file_names(&expected)
+                {
+                    assert!(live.update().await.unwrap());
+                }
+            },
+            async {
+                Timer::after(Duration::from_secs(5)).await;
+                panic!("The snapshot was not updated");
+            },
+        ));
+
+        assert_eq!(
+            vec!["a.txt", "b.txt", "d.txt", "e.txt"],
+            file_names(live.snapshot())
+        );
+        assert_eq!(
+            expected.dir_size(root.path()),
+            live.snapshot().dir_size(root.path())
+        );
+        assert_eq!(Some(2), live.snapshot().dir_size(root.path().join("three")));
+        assert_eq!(
+            Some(5),
+            live.snapshot()
+                .get_file_by_path(root.path().join("a.txt"))
+                .map(|file| file.size())
+        );
+
+        let changes = std::iter::from_fn(|| changes.try_recv().ok()).collect::<Vec<LiveChange>>();
+        assert!(changes.contains(&LiveChange::Added(root.path().join("one").join("d.txt"))));
+        assert!(changes.contains(&LiveChange::Modified(root.path().join("a.txt"))));
+        assert!(changes.contains(&LiveChange::Removed(
+            root.path().join("one").join("two").join("c.txt")
+        )));
+        assert!(changes.contains(&LiveChange::Added(root.path().join("three"))));
+    }
+}
@@ -0,0 +1,382 @@
+//! The watcher backend built on the `notify` crate, used with the `watcher-portable` feature
+//! on the platforms without inotify
+
+use crate::{
+    watcher::{PendingMoves, Wake},
+    FsWatcher, WatchFor, WatcherError, WatcherEvents, WatcherOutcome,
+};
+use notify::{
+    event::{AccessKind, AccessMode, CreateKind, ModifyKind, RemoveKind, RenameMode},
+    Event, EventHandler, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use smol::{channel, channel::Receiver, future, io};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+impl WatchFor {
+    /// The option an event is reported for, the events without an option are always reported
+    fn of(event: WatcherEvents) -> Option<Self> {
+        match event {
+            WatcherEvents::Create => Some(Self::CREATE),
+            WatcherEvents::Delete => Some(Self::DELETE),
+            WatcherEvents::Modify => Some(Self::MODIFY),
+            WatcherEvents::MovedFrom | WatcherEvents::MovedTo | WatcherEvents::Renamed => {
+                Some(Self::MOVE)
+            }
+            WatcherEvents::Attrib => Some(Self::ATTRIB),
+            WatcherEvents::Access => Some(Self::ACCESS),
+            WatcherEvents::Open => Some(Self::OPEN),
+            WatcherEvents::CloseWrite | WatcherEvents::CloseNoWrite => Some(Self::CLOSE),
+            WatcherEvents::DeleteSelf => Some(Self::DELETE_SELF),
+            WatcherEvents::MoveSelf => Some(Self::MOVE_SELF),
+            _ => None,
+        }
+    }
+}
+
+/// What woke the watch loop of the `notify` backend
+enum Next {
+    Event(notify::Result<Event>),
+    Wake(Wake),
+}
+
+impl FsWatcher {
+    /// Watches the paths until the [crate::WatcherHandle] owning the other half of `control`
+    /// is stopped or dropped, or until all the paths have been removed
+    pub(crate) async fn watch_until(
+        self,
+        watch_for: WatchFor,
+        control: Option<Receiver<PathBuf>>,
+    ) -> Result<(), WatcherError> {
+        // The events are sent from the thread of the `notify` watcher
+        let (event_sender, event_receiver) = channel::unbounded::<notify::Result<Event>>();
+        let (mut watcher, mut watched) = self.add_watches(move |event| {
+            event_sender.send_blocking(event).ok();
+        })?;
+
+        let mut pending_moves = PendingMoves::default();
+
+        loop {
+            if let Some(control) = control.as_ref() {
+                while let Ok(path) = control.try_recv() {
+                    if let Some(ignored) = Self::remove_watch(&mut watcher, &mut watched, &path) {
+                        self.send(ignored).await?;
+                    }
+                }
+
+                if control.is_closed() {
+                    break;
+                }
+            }
+
+            if watched.is_empty() {
+                break;
+            }
+
+            self.flush()?;
+
+            for moved_from in pending_moves.expired() {
+                if self.passes(moved_from.name.as_deref(), moved_from.path.as_deref()) {
+                    self.send(moved_from).await?;
+                }
+            }
+
+            let received = async {
+                match event_receiver.recv().await {
+                    Ok(event) => Ok(Next::Event(event)),
+                    // The sender lives as long as the `notify` watcher
+                    Err(_) => future::pending().await,
+                }
+            };
+            // A pending move expires or the held outcomes should be sent again
+            let deadline = pending_moves
+                .deadline()
+                .into_iter()
+                .chain(self.retry_deadline())
+                .min();
+            let woken = async { Wake::wait(control.as_ref(), deadline).await.map(Next::Wake) };
+
+            let event = match future::or(received, woken).await? {
+                Next::Event(event) => event.map_err(Self::watcher_error)?,
+                Next::Wake(Wake::Continue) => continue,
+                Next::Wake(Wake::Unwatch(path)) => {
+                    if let Some(ignored) = Self::remove_watch(&mut watcher, &mut watched, &path) {
+                        self.send(ignored).await?;
+                    }
+
+                    continue;
+                }
+                Next::Wake(Wake::Stop) => break,
+            };
+
+            for outcome in Self::outcomes(&event, &watched, watch_for) {
+                let deleted = match outcome.mask {
+                    WatcherEvents::DeleteSelf => outcome.path.clone(),
+                    _ => None,
+                };
+
+                if let Some(outcome) = self.admit(outcome, &mut pending_moves) {
+                    self.send(outcome).await?;
+                }
+
+                // The watch of a deleted path is removed along with it
+                if let Some(path) = deleted {
+                    if let Some(ignored) = Self::remove_watch(&mut watcher, &mut watched, &path) {
+                        self.send(ignored).await?;
+                    }
+                }
+            }
+
+            if event.need_rescan() {
+                self.rescan(watched.iter().map(|(_, path)| path)).await;
+            }
+        }
+
+        self.flush().ok();
+
+        watched.into_iter().for_each(|(_, path)| {
+            watcher.unwatch(&path).ok();
+        });
+
+        Ok(())
+    }
+
+    /// The outcomes of [FsWatcher::watch_blocking], the events are received
+    /// from the thread of the `notify` watcher on the calling thread
+    pub(crate) fn blocking_outcomes(
+        self,
+        watch_for: WatchFor,
+    ) -> impl Iterator<Item = Result<WatcherOutcome, WatcherError>> {
+        let (event_sender, event_receiver) = mpsc::channel::<notify::Result<Event>>();
+        let (mut watches, mut failed) = match self.add_watches(event_sender) {
+            Ok(watches) => (Some(watches), None),
+            Err(error) => (None, Some(error)),
+        };
+        let mut pending_moves = PendingMoves::default();
+        let mut ready = VecDeque::<WatcherOutcome>::new();
+
+        std::iter::from_fn(move || loop {
+            if let Some(error) = failed.take() {
+                return Some(Err(error));
+            }
+
+            if let Some(outcome) = ready.pop_front() {
+                return Some(Ok(outcome));
+            }
+
+            let (watcher, watched) = watches.as_mut()?;
+            if watched.is_empty() {
+                return None;
+            }
+
+            let event = match event_receiver.recv() {
+                Ok(Ok(event)) => event,
+                Ok(Err(error)) => {
+                    watched.clear();
+
+                    return Some(Err(Self::watcher_error(error)));
+                }
+                Err(_) => return None,
+            };
+
+            for outcome in Self::outcomes(&event, watched, watch_for) {
+                let deleted = match outcome.mask {
+                    WatcherEvents::DeleteSelf => outcome.path.clone(),
+                    _ => None,
+                };
+
+                if let Some(outcome) = self.admit(outcome, &mut pending_moves) {
+                    ready.push_back(outcome);
+                }
+
+                if let Some(path) = deleted {
+                    ready.extend(Self::remove_watch(watcher, watched, &path));
+                }
+            }
+
+            // Without a timeout the renames are only paired when both halves are received together
+            if event_receiver.try_recv().is_err() {
+                ready.extend(pending_moves.drain().filter(|moved_from| {
+                    self.passes(moved_from.name.as_deref(), moved_from.path.as_deref())
+                }));
+            }
+        })
+    }
+
+    /// Creates the `notify` watcher sending its events to `handler` and watches each path,
+    /// the index of each path in `self.paths` stands in for the watch descriptor
+    fn add_watches(
+        &self,
+        handler: impl EventHandler,
+    ) -> Result<(RecommendedWatcher, Vec<(i32, PathBuf)>), WatcherError> {
+        if self.paths.is_empty() {
+            return Err(WatcherError::PathNotSet);
+        }
+
+        let mut watcher = notify::recommended_watcher(handler).map_err(Self::watcher_error)?;
+        let mut watched = Vec::<(i32, PathBuf)>::new();
+        for (descriptor, path) in self.paths.iter().enumerate() {
+            watcher
+                .watch(path, RecursiveMode::NonRecursive)
+                .map_err(Self::watcher_error)?;
+            watched.push((descriptor as i32, path.clone()));
+        }
+
+        Ok((watcher, watched))
+    }
+
+    /// The outcomes of a `notify` event for each of its paths that is watched for
+    fn outcomes(
+        event: &Event,
+        watched: &[(i32, PathBuf)],
+        watch_for: WatchFor,
+    ) -> Vec<WatcherOutcome> {
+        if event.need_rescan() {
+            return vec![Self::outcome(
+                WatcherEvents::QueueOverflow,
+                false,
+                None,
+                None,
+                watched,
+            )];
+        }
+
+        let cookie = event.tracker().unwrap_or_default() as u32;
+        let mut outcomes = Vec::<WatcherOutcome>::new();
+
+        for path in &event.paths {
+            let watched_path = watched
+                .iter()
+                .find(|(_, watched_path)| {
+                    watched_path == path || Some(watched_path.as_path()) == path.parent()
+                })
+                .map(|(_, watched_path)| watched_path.clone());
+            let is_self = watched_path.as_ref() == Some(path);
+
+            let Some((mask, is_dir)) = Self::event_of(&event.kind, path, is_self) else {
+                continue;
+            };
+            if WatchFor::of(mask).is_some_and(|option| !watch_for.contains(option)) {
+                continue;
+            }
+
+            let mut outcome =
+                Self::outcome(mask, is_dir, Some(path.clone()), watched_path, watched);
+            outcome.cookie = cookie;
+            if !is_self {
+                outcome.name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string());
+            }
+
+            outcomes.push(outcome);
+        }
+
+        outcomes
+    }
+
+    /// Stops watching `path`, returning the [WatcherEvents::Ignored] outcome for it
+    /// unless it was not watched
+    fn remove_watch(
+        watcher: &mut impl Watcher,
+        watched: &mut Vec<(i32, PathBuf)>,
+        path: &Path,
+    ) -> Option<WatcherOutcome> {
+        let index = watched
+            .iter()
+            .position(|(_, watched_path)| watched_path == path)?;
+
+        // The watch may already be gone with the path
+        watcher.unwatch(path).ok();
+        let outcome = Self::outcome(
+            WatcherEvents::Ignored,
+            false,
+            Some(path.to_path_buf()),
+            Some(path.to_path_buf()),
+            watched,
+        );
+        watched.remove(index);
+
+        Some(outcome)
+    }
+
+    /// The event of a `notify` event kind for `path` and whether `path` is a directory,
+    /// `is_self` is set if `path` is one of the watched paths
+    fn event_of(kind: &EventKind, path: &Path, is_self: bool) -> Option<(WatcherEvents, bool)> {
+        let event = match kind {
+            EventKind::Create(CreateKind::Folder) => return Some((WatcherEvents::Create, true)),
+            EventKind::Create(_) => WatcherEvents::Create,
+            EventKind::Remove(RemoveKind::Folder) if is_self => {
+                return Some((WatcherEvents::DeleteSelf, true))
+            }
+            EventKind::Remove(RemoveKind::Folder) => return Some((WatcherEvents::Delete, true)),
+            EventKind::Remove(_) if is_self => WatcherEvents::DeleteSelf,
+            EventKind::Remove(_) => WatcherEvents::Delete,
+            EventKind::Modify(ModifyKind::Metadata(_)) => WatcherEvents::Attrib,
+            // Pairs of renames are made from the halves sharing the same tracker
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => return None,
+            EventKind::Modify(ModifyKind::Name(_)) if is_self => WatcherEvents::MoveSelf,
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => WatcherEvents::MovedFrom,
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => WatcherEvents::MovedTo,
+            // Some platforms do not tell which half of a rename an event is
+            EventKind::Modify(ModifyKind::Name(_)) if path.exists() => WatcherEvents::MovedTo,
+            EventKind::Modify(ModifyKind::Name(_)) => WatcherEvents::MovedFrom,
+            EventKind::Modify(_) => WatcherEvents::Modify,
+            EventKind::Access(AccessKind::Open(_)) => WatcherEvents::Open,
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => WatcherEvents::CloseWrite,
+            EventKind::Access(AccessKind::Close(_)) => WatcherEvents::CloseNoWrite,
+            EventKind::Access(_) => WatcherEvents::Access,
+            EventKind::Any | EventKind::Other => return None,
+        };
+
+        Some((event, path.is_dir()))
+    }
+
+    /// An outcome of a single event, the descriptor is the one of `watched_path`
+    fn outcome(
+        mask: WatcherEvents,
+        is_dir: bool,
+        path: Option<PathBuf>,
+        watched_path: Option<PathBuf>,
+        watched: &[(i32, PathBuf)],
+    ) -> WatcherOutcome {
+        let descriptor = watched
+            .iter()
+            .find(|(_, path)| Some(path) == watched_path.as_ref())
+            .map(|(descriptor, _)| *descriptor)
+            .unwrap_or(-1);
+        let mut events = vec![mask];
+        if is_dir {
+            events.push(WatcherEvents::IsDir);
+        }
+
+        WatcherOutcome {
+            descriptor,
+            mask,
+            events,
+            is_dir,
+            cookie: 0,
+            name: None,
+            path,
+            watched_path,
+            old_name: None,
+            old_path: None,
+            dropped: 0,
+        }
+    }
+
+    fn watcher_error(error: notify::Error) -> WatcherError {
+        match error.kind {
+            notify::ErrorKind::Io(error) => WatcherError::Io(error),
+            notify::ErrorKind::PathNotFound => WatcherError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                "The path was not found",
+            )),
+            notify::ErrorKind::MaxFilesWatch => WatcherError::WatchLimitReached,
+            _ => WatcherError::Io(io::Error::other(error)),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{DirMetadata, FileMetadata, FsUtils};
+use std::collections::BTreeMap;
+
+impl FsUtils {
+    /// The names reserved by Windows for devices, with or without an extension
+    const WINDOWS_RESERVED_NAMES: &'static [&'static str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    /// The characters that cannot appear in a file name on Windows besides control characters
+    const WINDOWS_FORBIDDEN_CHARS: &'static [char] =
+        &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+    /// Check if `name` can be used as a file name on Windows: it is not a reserved name like `CON`
+    /// or `nul.txt`, has no character like `:` or `?` and does not end with a space or a `.`
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::FsUtils;
+    ///
+    /// assert!(FsUtils::is_valid_windows_name("notes.txt"));
+    /// assert!(!FsUtils::is_valid_windows_name("Con.txt"));
+    /// assert!(!FsUtils::is_valid_windows_name("what?.txt"));
+    /// assert!(!FsUtils::is_valid_windows_name("draft."));
+    /// ```
+    pub fn is_valid_windows_name(name: &str) -> bool {
+        let stem = name.split('.').next().unwrap_or_default().trim_end();
+
+        !name.is_empty()
+            && !name.ends_with([' ', '.'])
+            && !name
+                .chars()
+                .any(|char| char.is_control() || Self::WINDOWS_FORBIDDEN_CHARS.contains(&char))
+            && !Self::WINDOWS_RESERVED_NAMES
+                .iter()
+                .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    }
+}
+
+impl<'a> DirMetadata<'a> {
+    /// Get the groups of files whose paths only differ by case, which cannot be
+    /// checked out side by side on case-insensitive filesystems like those of Windows and macOS.
+    /// The groups are sorted by path and so are the files in each group
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     for collision in outcome.case_collisions() {
+    ///         dbg!(collision.iter().map(|file| file.path()).collect::<Vec<_>>());
+    ///     }
+    /// });
+    /// ```
+    pub fn case_collisions(&self) -> Vec<Vec<&FileMetadata<'a>>> {
+        let mut by_path = BTreeMap::<String, Vec<&FileMetadata<'a>>>::new();
+        self.files().iter().for_each(|file| {
+            by_path
+                .entry(file.path().to_string_lossy().to_lowercase())
+                .or_default()
+                .push(file);
+        });
+
+        by_path
+            .into_values()
+            .filter(|files| files.len() > 1)
+            .map(|mut files| {
+                files.sort_by(|first, second| first.cmp_path(second));
+
+                files
+            })
+            .collect()
+    }
+
+    /// Get the files whose name cannot be used on Windows, see [FsUtils::is_valid_windows_name]
+    pub fn invalid_windows_names(&self) -> Vec<&FileMetadata<'a>> {
+        self.files()
+            .iter()
+            .filter(|file| !FsUtils::is_valid_windows_name(file.name()))
+            .collect()
+    }
+}
+
+// Only Linux filesystems accept these names and tell them apart by case
+#[cfg(all(test, target_os = "linux"))]
+mod sanity_checks {
+    use crate::test_utils::{nested_fixture, scan};
+    use std::fs;
+
+    #[test]
+    fn portable_names() {
+        let root = nested_fixture();
+        for name in [
+            "A.txt",
+            "nul.txt",
+            "what?.txt",
+            "trailing.",
+            "CON",
+            "console.txt",
+        ] {
+            fs::write(root.path().join(name), b"").unwrap();
+        }
+        fs::write(root.path().join("one").join("B.TXT"), b"").unwrap();
+
+        let outcome = scan(root.path(), None);
+        let collisions = outcome
+            .case_collisions()
+            .iter()
+            .map(|files| files.iter().map(|file| file.name()).collect::<Vec<&str>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![vec!["A.txt", "a.txt"], vec!["B.TXT", "b.txt"]],
+            collisions
+        );
+
+        let mut invalid = outcome
+            .invalid_windows_names()
+            .iter()
+            .map(|file| file.name())
+            .collect::<Vec<&str>>();
+        invalid.sort();
+        assert_eq!(vec!["CON", "nul.txt", "trailing.", "what?.txt"], invalid);
+    }
+}
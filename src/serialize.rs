@@ -0,0 +1,161 @@
+//! The representations used by `serde` for the types of other crates stored in a scan
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Write;
+use tai64::Tai64N;
+
+/// Serializes a [Tai64N] as its canonical 12 bytes in lowercase hexadecimal
+pub(crate) mod tai64n {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        time: &Tai64N,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        time.to_bytes()
+            .iter()
+            .fold(
+                String::with_capacity(Tai64N::BYTE_SIZE * 2),
+                |mut hex, byte| {
+                    write!(hex, "{byte:02x}").ok();
+
+                    hex
+                },
+            )
+            .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Tai64N, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+
+        if hex.len() != Tai64N::BYTE_SIZE * 2 || !hex.is_ascii() {
+            return Err(D::Error::custom(format!(
+                "Invalid TAI64N timestamp `{hex}`"
+            )));
+        }
+
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(D::Error::custom)?;
+
+        Tai64N::from_slice(&bytes).map_err(D::Error::custom)
+    }
+}
+
+/// Serializes an optional [Tai64N] like [tai64n]
+pub(crate) mod tai64n_option {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "super::tai64n")] Tai64N);
+
+    pub(crate) fn serialize<S: Serializer>(
+        time: &Option<Tai64N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        time.map(Wrapper).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Tai64N>, D::Error> {
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(time)| time))
+    }
+}
+
+/// Serializes the file format of a file if it was detected, as [Option::None] otherwise
+pub(crate) mod file_format_cell {
+    use super::*;
+    use file_format::FileFormat;
+    use std::sync::OnceLock;
+
+    pub(crate) fn serialize<S: Serializer>(
+        file_format: &OnceLock<FileFormat>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        file_format.get().serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OnceLock<FileFormat>, D::Error> {
+        let cell = OnceLock::new();
+        if let Some(file_format) = Option::<FileFormat>::deserialize(deserializer)? {
+            cell.set(file_format).ok();
+        }
+
+        Ok(cell)
+    }
+}
+
+/// Serializes an [std::io::ErrorKind] as the name of the variant like `NotFound`
+pub(crate) mod error_kind {
+    use super::*;
+    use std::io::ErrorKind;
+
+    /// The kinds that can be read back, any other kind is read back as [ErrorKind::Other]
+    const ERROR_KINDS: &[ErrorKind] = &[
+        ErrorKind::NotFound,
+        ErrorKind::PermissionDenied,
+        ErrorKind::ConnectionRefused,
+        ErrorKind::ConnectionReset,
+        ErrorKind::HostUnreachable,
+        ErrorKind::NetworkUnreachable,
+        ErrorKind::ConnectionAborted,
+        ErrorKind::NotConnected,
+        ErrorKind::AddrInUse,
+        ErrorKind::AddrNotAvailable,
+        ErrorKind::NetworkDown,
+        ErrorKind::BrokenPipe,
+        ErrorKind::AlreadyExists,
+        ErrorKind::WouldBlock,
+        ErrorKind::NotADirectory,
+        ErrorKind::IsADirectory,
+        ErrorKind::DirectoryNotEmpty,
+        ErrorKind::ReadOnlyFilesystem,
+        ErrorKind::StaleNetworkFileHandle,
+        ErrorKind::InvalidInput,
+        ErrorKind::InvalidData,
+        ErrorKind::TimedOut,
+        ErrorKind::WriteZero,
+        ErrorKind::StorageFull,
+        ErrorKind::NotSeekable,
+        ErrorKind::QuotaExceeded,
+        ErrorKind::FileTooLarge,
+        ErrorKind::ResourceBusy,
+        ErrorKind::ExecutableFileBusy,
+        ErrorKind::Deadlock,
+        ErrorKind::CrossesDevices,
+        ErrorKind::TooManyLinks,
+        ErrorKind::InvalidFilename,
+        ErrorKind::ArgumentListTooLong,
+        ErrorKind::Interrupted,
+        ErrorKind::Unsupported,
+        ErrorKind::UnexpectedEof,
+        ErrorKind::OutOfMemory,
+        ErrorKind::Other,
+    ];
+
+    pub(crate) fn serialize<S: Serializer>(
+        error_kind: &ErrorKind,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        format!("{error_kind:?}").serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ErrorKind, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        Ok(ERROR_KINDS
+            .iter()
+            .find(|error_kind| format!("{error_kind:?}") == name)
+            .copied()
+            .unwrap_or(ErrorKind::Other))
+    }
+}
@@ -0,0 +1,118 @@
+use crate::DirMetadata;
+use smol::io::{self, ErrorKind};
+use std::path::Path;
+
+/// The version of the layout of snapshots, written as the first byte of a snapshot.
+/// It must be incremented whenever a serialized field of the scan is added or removed
+const SNAPSHOT_VERSION: u8 = 8;
+
+/// The features that add fields to the scan, written as the second byte of a snapshot
+/// since a snapshot can only be read back with the same fields
+const SNAPSHOT_FEATURES: u8 = cfg!(feature = "time") as u8
+    | (cfg!(feature = "gitignore") as u8) << 1
+    | (cfg!(feature = "unix") as u8) << 2
+    | (cfg!(feature = "hash") as u8) << 3;
+
+impl DirMetadata<'_> {
+    /// Save the scan to the file at `path` in a compact binary format,
+    /// much smaller and faster to write than JSON. The snapshot can only be
+    /// read back by [DirMetadata::load_snapshot] built with the same version of this crate
+    /// and the same `time`, `gitignore`, `unix` and `hash` features
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///     let snapshot = std::env::temp_dir().join("dir-meta-snapshot.bin");
+    ///
+    ///     outcome.save_snapshot(&snapshot).await.unwrap();
+    ///     assert_eq!(
+    ///         outcome,
+    ///         DirMetadata::load_snapshot(&snapshot).await.unwrap()
+    ///     );
+    /// });
+    /// ```
+    pub async fn save_snapshot(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut snapshot = vec![SNAPSHOT_VERSION, SNAPSHOT_FEATURES];
+        bincode::serialize_into(&mut snapshot, self)
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+
+        smol::fs::write(path, snapshot).await
+    }
+
+    /// Load a scan saved by [DirMetadata::save_snapshot]. Returns an error of kind
+    /// [ErrorKind::InvalidData] if the snapshot was written by another version of this crate
+    /// or with other features instead of reading back a wrong scan
+    pub async fn load_snapshot(path: impl AsRef<Path>) -> io::Result<DirMetadata<'static>> {
+        let snapshot = smol::fs::read(path).await?;
+
+        match snapshot.as_slice() {
+            [] | [SNAPSHOT_VERSION] => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The snapshot is incomplete",
+            )),
+            [SNAPSHOT_VERSION, SNAPSHOT_FEATURES, scan @ ..] => bincode::deserialize(scan)
+                .map_err(|error| io::Error::new(ErrorKind::InvalidData, error)),
+            [SNAPSHOT_VERSION, features, ..] => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "The snapshot was saved with the features `{features:#06b}` but these are `{SNAPSHOT_FEATURES:#06b}`"
+                ),
+            )),
+            [version, ..] => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Unsupported snapshot version `{version}`, only version `{SNAPSHOT_VERSION}` can be loaded"
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+    use crate::{
+        test_utils::{nested_fixture, scan},
+        DirMetadata,
+    };
+    use smol::io::ErrorKind;
+    use std::fs;
+
+    #[test]
+    fn snapshot() {
+        let root = nested_fixture();
+        let outcome = scan(root.path(), None);
+        let snapshot = root.path().join("snapshot.bin");
+
+        smol::block_on(async {
+            outcome.save_snapshot(&snapshot).await.unwrap();
+            let loaded = DirMetadata::load_snapshot(&snapshot).await.unwrap();
+            assert_eq!(outcome, loaded);
+            assert!(loaded.contains_path(root.path().join("one").join("b.txt")));
+            assert_eq!(Some(5), loaded.dir_size("one"));
+
+            let saved = fs::read(&snapshot).unwrap();
+            let mut newer = saved.clone();
+            newer[0] += 1;
+            let mut other_features = saved.clone();
+            other_features[1] ^= 1;
+
+            for bytes in [
+                newer,
+                other_features,
+                saved[..saved.len() / 2].to_vec(),
+                saved[..1].to_vec(),
+                Vec::new(),
+            ] {
+                fs::write(&snapshot, bytes).unwrap();
+                let error = DirMetadata::load_snapshot(&snapshot).await.unwrap_err();
+                assert_eq!(ErrorKind::InvalidData, error.kind());
+            }
+        });
+    }
+}
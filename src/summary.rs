@@ -0,0 +1,309 @@
+use crate::{DirMetadata, FsUtils};
+use file_format::FileFormat;
+use std::{collections::HashMap, path::PathBuf};
+use tai64::Tai64N;
+
+/// The number and total size of the files sharing an extension,
+/// created using [DirMetadata::extension_summary]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExtensionStats {
+    /// The extension in lowercase without the leading `.`,
+    /// [Option::None] for the files without an extension
+    pub extension: Option<String>,
+    /// The number of files with the extension
+    pub count: usize,
+    /// The size in bytes of all the files with the extension
+    pub size: u64,
+}
+
+impl ExtensionStats {
+    /// Get [Self::size] in human readable format
+    pub fn size_formatted(&self) -> String {
+        FsUtils::size_to_bytes(self.size)
+    }
+}
+
+/// The number and total size of the files sharing a file format,
+/// created using [DirMetadata::format_summary]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FormatStats {
+    /// The format of the files
+    pub format: FileFormat,
+    /// The number of files with the format
+    pub count: usize,
+    /// The size in bytes of all the files with the format
+    pub size: u64,
+}
+
+impl FormatStats {
+    /// Get [Self::size] in human readable format
+    pub fn size_formatted(&self) -> String {
+        FsUtils::size_to_bytes(self.size)
+    }
+}
+
+/// Statistics about a whole scan, created using [DirMetadata::stats]
+#[derive(Debug, PartialEq, Clone)]
+pub struct DirStats {
+    /// The number of files found
+    pub files: usize,
+    /// The number of sub-directories found
+    pub directories: usize,
+    /// The size in bytes of all the files, the same as [DirMetadata::size]
+    pub size: u64,
+    /// The average size in bytes of the files found, [Option::None] if no file was found
+    pub average_file_size: Option<f64>,
+    /// The median size in bytes of the files found, the average of the two middle sizes
+    /// for an even number of files. [Option::None] if no file was found
+    pub median_file_size: Option<u64>,
+    /// The path of the largest file, [Option::None] if no file was found
+    pub largest_file: Option<PathBuf>,
+    /// The oldest modification time of the files,
+    /// [Option::None] if the modification time of no file could be read
+    pub oldest_modified: Option<Tai64N>,
+    /// The newest modification time of the files,
+    /// [Option::None] if the modification time of no file could be read
+    pub newest_modified: Option<Tai64N>,
+    /// The number of errors encountered
+    pub errors: usize,
+    /// The number of symbolic links whose target did not exist, see [DirMetadata::broken_links]
+    pub broken_links: usize,
+}
+
+impl DirMetadata<'_> {
+    /// Compute statistics about the files and directories found
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let stats = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap()
+    ///         .stats();
+    ///
+    ///     dbg!(stats.median_file_size, stats.largest_file);
+    /// });
+    /// ```
+    pub fn stats(&self) -> DirStats {
+        let files = self.files();
+
+        let mut sizes = files.iter().map(|file| file.size()).collect::<Vec<u64>>();
+        let median_file_size = match sizes.len() {
+            0 => None,
+            count => {
+                let (lower, upper, _) = sizes.select_nth_unstable(count / 2);
+                let upper = *upper;
+
+                if count % 2 == 0 {
+                    let lower = lower.iter().copied().max().unwrap_or(upper);
+                    Some(lower + (upper - lower) / 2)
+                } else {
+                    Some(upper)
+                }
+            }
+        };
+
+        let modified = files.iter().filter_map(|file| file.modified());
+
+        DirStats {
+            files: files.len(),
+            directories: self.directories().len(),
+            size: self.size(),
+            average_file_size: (!files.is_empty()).then(|| {
+                files.iter().map(|file| file.size() as f64).sum::<f64>() / files.len() as f64
+            }),
+            median_file_size,
+            largest_file: self
+                .largest_files(1)
+                .first()
+                .map(|file| file.path().into_owned()),
+            oldest_modified: modified.clone().min(),
+            newest_modified: modified.max(),
+            errors: self.errors().len(),
+            broken_links: self.broken_links().len(),
+        }
+    }
+
+    /// Count the files and add up their sizes for each extension, ignoring ASCII case.
+    /// The largest extensions come first
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     for stats in outcome.extension_summary() {
+    ///         dbg!(&stats.extension, stats.count, stats.size_formatted());
+    ///     }
+    /// });
+    /// ```
+    pub fn extension_summary(&self) -> Vec<ExtensionStats> {
+        let mut summary = HashMap::<Option<String>, (usize, u64)>::new();
+
+        self.files().iter().for_each(|file| {
+            let extension = file
+                .extension()
+                .map(|extension| extension.to_ascii_lowercase());
+            let (count, size) = summary.entry(extension).or_default();
+            *count += 1;
+            *size += file.size();
+        });
+
+        let mut summary = summary
+            .into_iter()
+            .map(|(extension, (count, size))| ExtensionStats {
+                extension,
+                count,
+                size,
+            })
+            .collect::<Vec<ExtensionStats>>();
+        summary.sort_by(|first, second| {
+            second
+                .size
+                .cmp(&first.size)
+                .then_with(|| first.extension.cmp(&second.extension))
+        });
+
+        summary
+    }
+
+    /// Get the total size in bytes and the number of files for each extension like
+    /// [Self::extension_summary] as `(extension, size, count)` tuples, the largest extensions first.
+    /// The files without an extension are grouped under an empty extension
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     for (extension, size, count) in outcome.size_by_extension_formatted() {
+    ///         println!("{size}\t{count}\t.{extension}");
+    ///     }
+    /// });
+    /// ```
+    pub fn size_by_extension(&self) -> Vec<(String, u64, usize)> {
+        self.extension_summary()
+            .into_iter()
+            .map(|stats| (stats.extension.unwrap_or_default(), stats.size, stats.count))
+            .collect()
+    }
+
+    /// Get [Self::size_by_extension] with the sizes in human readable format
+    pub fn size_by_extension_formatted(&self) -> Vec<(String, String, usize)> {
+        self.size_by_extension()
+            .into_iter()
+            .map(|(extension, size, count)| (extension, FsUtils::size_to_bytes(size), count))
+            .collect()
+    }
+
+    /// Count the files and add up their sizes for each [FileFormat].
+    /// The largest formats come first.
+    /// If the scan used [DirMetadata::defer_format_detection] this detects
+    /// the format of every file, blocking the current thread
+    pub fn format_summary(&self) -> Vec<FormatStats> {
+        // [FileFormat] is not hashable and a scan only finds a few distinct formats
+        let mut summary = Vec::<FormatStats>::new();
+
+        self.files().iter().for_each(|file| {
+            let format = *file.file_format();
+
+            match summary.iter_mut().find(|stats| stats.format == format) {
+                Some(stats) => {
+                    stats.count += 1;
+                    stats.size += file.size();
+                }
+                None => summary.push(FormatStats {
+                    format,
+                    count: 1,
+                    size: file.size(),
+                }),
+            }
+        });
+
+        summary.sort_by(|first, second| {
+            second
+                .size
+                .cmp(&first.size)
+                .then_with(|| first.format.name().cmp(second.format.name()))
+        });
+
+        summary
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+    use crate::{
+        test_utils::{nested_fixture, scan},
+        DirMetadata,
+    };
+    use file_format::FileFormat;
+    use std::fs;
+
+    #[test]
+    fn summaries() {
+        let root = nested_fixture();
+        fs::write(root.path().join("UPPER.TXT"), b"upper").unwrap();
+        fs::write(root.path().join("README"), b"readme").unwrap();
+        fs::write(root.path().join("image.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+
+        let outcome = scan(root.path(), None);
+        let extensions = outcome.extension_summary();
+        assert_eq!(3, extensions.len());
+        assert_eq!(Some("txt".to_owned()), extensions[0].extension);
+        assert_eq!(4, extensions[0].count);
+        assert_eq!(11, extensions[0].size);
+        assert_eq!(
+            vec![Some("png".to_owned()), None],
+            extensions[1..]
+                .iter()
+                .map(|stats| stats.extension.clone())
+                .collect::<Vec<_>>()
+        );
+
+        let formats = outcome.format_summary();
+        assert_eq!(FileFormat::PlainText, formats[0].format);
+        assert_eq!(5, formats[0].count);
+        assert_eq!(17, formats[0].size);
+        assert_eq!(FileFormat::PortableNetworkGraphics, formats[1].format);
+    }
+
+    #[test]
+    fn stats() {
+        let root = nested_fixture();
+        fs::write(root.path().join("d.txt"), b"dddddd").unwrap();
+
+        let mut outcome = scan(root.path(), None);
+        let stats = outcome.stats();
+        assert_eq!(4, stats.files);
+        assert_eq!(2, stats.directories);
+        assert_eq!(12, stats.size);
+        assert_eq!(Some(3.0), stats.average_file_size);
+        assert_eq!(Some(2), stats.median_file_size);
+        assert_eq!(Some(root.path().join("d.txt")), stats.largest_file);
+        assert!(stats.oldest_modified <= stats.newest_modified);
+        assert!(stats.oldest_modified.is_some());
+        assert_eq!(0, stats.errors);
+
+        outcome.retain_files(|file| file.name() != "d.txt");
+        assert_eq!(Some(2), outcome.stats().median_file_size);
+
+        let empty = DirMetadata::default().stats();
+        assert_eq!(None, empty.average_file_size);
+        assert_eq!(None, empty.median_file_size);
+        assert_eq!(None, empty.largest_file);
+    }
+}
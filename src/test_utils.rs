@@ -0,0 +1,97 @@
+//! Fixtures shared by the tests of every module
+
+use crate::DirMetadata;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Creates `root/a.txt`, `root/one/b.txt` and `root/one/two/c.txt`
+pub(crate) fn nested_fixture() -> tempfile::TempDir {
+    let root = tempfile::tempdir().unwrap();
+    let two = root.path().join("one").join("two");
+    fs::create_dir_all(&two).unwrap();
+    fs::write(root.path().join("a.txt"), b"a").unwrap();
+    fs::write(root.path().join("one").join("b.txt"), b"bb").unwrap();
+    fs::write(two.join("c.txt"), b"ccc").unwrap();
+
+    root
+}
+
+pub(crate) fn scan(path: &Path, max_depth: Option<usize>) -> DirMetadata<'_> {
+    let dir = DirMetadata::new(path.to_str().unwrap()).unwrap();
+    let dir = match max_depth {
+        Some(depth) => dir.max_depth(depth),
+        None => dir,
+    };
+
+    smol::block_on(dir.dir_metadata()).unwrap()
+}
+
+pub(crate) fn file_names<'a>(outcome: &'a DirMetadata) -> Vec<&'a str> {
+    let mut names = outcome
+        .files()
+        .iter()
+        .map(|file| file.name())
+        .collect::<Vec<&str>>();
+    names.sort();
+
+    names
+}
+
+/// The directories whose entry lookups are counted by [LookupProbe]
+static PROBED_DIRS: Mutex<Vec<(PathBuf, Arc<LookupCounts>)>> = Mutex::new(Vec::new());
+
+/// The number of entry lookups of a directory in flight and the highest number seen
+#[derive(Debug, Default)]
+pub(crate) struct LookupCounts {
+    in_flight: AtomicUsize,
+    pub(crate) max_in_flight: AtomicUsize,
+}
+
+/// Counts the entry lookups of the directories passed to [LookupProbe::track]
+/// and slows them down so that concurrent lookups overlap.
+/// Tracking a directory again starts new counts for it
+pub(crate) struct LookupProbe(Option<Arc<LookupCounts>>);
+
+impl LookupProbe {
+    pub(crate) fn track(dir: &Path) -> Arc<LookupCounts> {
+        let counts = Arc::<LookupCounts>::default();
+        PROBED_DIRS
+            .lock()
+            .unwrap()
+            .push((dir.to_owned(), counts.clone()));
+
+        counts
+    }
+
+    pub(crate) fn enter(path: &Path) -> Self {
+        let counts = PROBED_DIRS
+            .lock()
+            .unwrap()
+            .iter()
+            .rfind(|(dir, _)| path.parent() == Some(dir.as_path()))
+            .map(|(_, counts)| counts.clone());
+
+        if let Some(counts) = counts.as_ref() {
+            let in_flight = counts.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            counts.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        Self(counts)
+    }
+}
+
+impl Drop for LookupProbe {
+    fn drop(&mut self) {
+        if let Some(counts) = self.0.as_ref() {
+            counts.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Entry points for applications running on the tokio runtime. They bridge to the smol
+//! based scan and watcher, which are still compiled in and run on their own threads
+
+use crate::DirMetadata;
+use smol::io;
+
+impl DirMetadata<'static> {
+    /// Same as [DirMetadata::dir_metadata] for applications running on tokio.
+    /// This is a bridge rather than a traversal built on `tokio::fs`: the same scan is
+    /// driven by `smol::block_on` on a thread of the tokio blocking pool, so it never stalls
+    /// the workers of the runtime and no executor needs to be started by the application.
+    /// The `smol`, `async-io` and `blocking` crates are still used, the file formats are
+    /// detected on the thread pool of `blocking` instead of with `tokio::task::spawn_blocking`
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread()
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// runtime.block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .tokio_dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     dbg!(outcome.size_formatted());
+    /// });
+    /// ```
+    pub async fn tokio_dir_metadata(self) -> io::Result<Self> {
+        tokio::task::spawn_blocking(move || smol::block_on(self.dir_metadata()))
+            .await
+            .unwrap_or_else(|error| Err(io::Error::other(error)))
+    }
+}
+
+#[cfg(any(
+    all(feature = "watcher", target_os = "linux"),
+    feature = "watcher-portable"
+))]
+impl crate::FsWatcher {
+    /// Same as [crate::FsWatcher::watch] for applications running on tokio.
+    /// Like [DirMetadata::tokio_dir_metadata] this is a bridge: the smol based watch
+    /// runs on a thread of the tokio blocking pool and the outcomes are sent
+    /// through the same [crate::FsSender]
+    pub async fn tokio_watch(
+        self,
+        watch_for: impl Into<crate::BackendMask>,
+    ) -> Result<(), crate::WatcherError> {
+        let watch_for = watch_for.into();
+
+        tokio::task::spawn_blocking(move || smol::block_on(self.watch(watch_for)))
+            .await
+            .unwrap_or_else(|error| Err(io::Error::other(error).into()))
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+    use crate::{test_utils::file_names, DirMetadata};
+
+    #[test]
+    fn tokio_dir_metadata() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let on_tokio = runtime
+            .block_on(DirMetadata::new("src").unwrap().tokio_dir_metadata())
+            .unwrap();
+        let on_smol = smol::block_on(DirMetadata::new("src").unwrap().dir_metadata()).unwrap();
+        assert_eq!(on_smol.size(), on_tokio.size());
+        assert_eq!(file_names(&on_smol), file_names(&on_tokio));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{DirMetadata, FileMetadata, FsUtils};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// A directory of a scan with its files and sub-directories nested inside it,
+/// created using [DirMetadata::as_tree]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DirNode<'d> {
+    name: String,
+    path: PathBuf,
+    files: Vec<&'d FileMetadata<'d>>,
+    children: Vec<DirNode<'d>>,
+    size: u64,
+}
+
+impl<'d> DirNode<'d> {
+    fn new(
+        path: &Path,
+        mut files: Vec<&'d FileMetadata<'d>>,
+        mut children: Vec<DirNode<'d>>,
+    ) -> Self {
+        files.sort_by(|first, second| first.name().cmp(second.name()));
+        children.sort_by(|first, second| first.name.cmp(&second.name));
+
+        let size = files.iter().map(|file| file.size()).sum::<u64>()
+            + children.iter().map(|child| child.size).sum::<u64>();
+
+        Self {
+            name: path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            path: path.to_path_buf(),
+            files,
+            children,
+            size,
+        }
+    }
+
+    /// Get the name of the directory
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Get the path of the directory
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Get the files directly inside the directory sorted by name
+    pub fn files(&self) -> &[&'d FileMetadata<'d>] {
+        self.files.as_ref()
+    }
+
+    /// Get the sub-directories directly inside the directory sorted by name
+    pub fn children(&self) -> &[DirNode<'d>] {
+        self.children.as_ref()
+    }
+
+    /// Get the size of the files in the directory and all its sub-directories
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl DirMetadata<'_> {
+    /// Arrange the files and directories of the scan as a tree rooted at [Self::dir_path].
+    /// Directories without files are part of the tree. A scan of several [Self::roots]
+    /// created using [Self::new_multi] is rooted at a node with an empty name and path
+    /// whose children are the roots
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::DirMetadata;
+    ///
+    /// smol::block_on(async {
+    ///     let outcome = DirMetadata::new("src")
+    ///         .unwrap()
+    ///         .dir_metadata()
+    ///         .await
+    ///         .unwrap();
+    ///     let tree = outcome.as_tree();
+    ///
+    ///     assert_eq!(outcome.size(), tree.size());
+    /// });
+    /// ```
+    pub fn as_tree(&self) -> DirNode<'_> {
+        let mut files = HashMap::<&Path, Vec<&FileMetadata>>::new();
+        self.files().iter().for_each(|file| {
+            files.entry(file.parent()).or_default().push(file);
+        });
+
+        // The deepest directories are built first so that each directory
+        // is built after all of its sub-directories
+        let mut directories = self
+            .directories()
+            .iter()
+            .map(|directory| directory.as_path())
+            .collect::<Vec<&Path>>();
+        directories.sort_by_key(|directory| Reverse(directory.components().count()));
+
+        let mut built = HashMap::<&Path, Vec<DirNode>>::new();
+        for directory in directories {
+            let node = DirNode::new(
+                directory,
+                files.remove(directory).unwrap_or_default(),
+                built.remove(directory).unwrap_or_default(),
+            );
+
+            if let Some(parent) = directory.parent() {
+                built.entry(parent).or_default().push(node);
+            }
+        }
+
+        let mut root_nodes = self
+            .roots()
+            .iter()
+            .map(|root| {
+                DirNode::new(
+                    root,
+                    files.remove(root.as_path()).unwrap_or_default(),
+                    built.remove(root.as_path()).unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<DirNode>>();
+
+        if root_nodes.len() > 1 {
+            let mut top_node = DirNode::new(Path::new(""), Vec::new(), root_nodes);
+            top_node.name.clear();
+
+            return top_node;
+        }
+
+        let mut root_node = root_nodes
+            .pop()
+            .unwrap_or_else(|| DirNode::new(self.dir_path(), Vec::new(), Vec::new()));
+        root_node.name = self.dir_name().to_owned();
+
+        root_node
+    }
+}
+
+/// An indented tree of the directories with the size of everything inside each of them,
+/// followed by the files directly inside it formatted like [FileMetadata] displays them
+/// with the sizes aligned across the whole tree
+impl fmt::Display for DirMetadata<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .files()
+            .iter()
+            .map(|file| file.formatted_size().chars().count())
+            .max()
+            .unwrap_or_default();
+
+        let tree = self.as_tree();
+        // The roots of a scan of several directories are listed one after the other
+        let mut nodes = match tree.path().as_os_str().is_empty() {
+            true => tree
+                .children()
+                .iter()
+                .rev()
+                .map(|child| (child, 0usize))
+                .collect(),
+            false => vec![(&tree, 0usize)],
+        };
+        while let Some((node, indent)) = nodes.pop() {
+            writeln!(
+                f,
+                "{:indent$}{}/ ({})",
+                "",
+                node.name(),
+                FsUtils::size_to_bytes(node.size())
+            )?;
+
+            for file in node.files() {
+                writeln!(f, "{:indent$}{file:width$}", "", indent = indent + 2)?;
+            }
+
+            nodes.extend(
+                node.children()
+                    .iter()
+                    .rev()
+                    .map(|child| (child, indent + 2)),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+    use crate::{
+        test_utils::{nested_fixture, scan},
+        DirMetadata,
+    };
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn as_tree() {
+        let root = nested_fixture();
+        fs::create_dir(root.path().join("one").join("empty")).unwrap();
+
+        let outcome = scan(root.path(), None);
+        let tree = outcome.as_tree();
+        assert_eq!(root.path(), tree.path());
+        assert_eq!(outcome.dir_name(), tree.name());
+        assert_eq!(6, tree.size());
+        assert_eq!("a.txt", tree.files()[0].name());
+
+        let one = &tree.children()[0];
+        assert_eq!(1, tree.children().len());
+        assert_eq!("one", one.name());
+        assert_eq!(5, one.size());
+        assert_eq!(
+            vec!["empty", "two"],
+            one.children()
+                .iter()
+                .map(|child| child.name())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(0, one.children()[0].size());
+        assert!(one.children()[0].files().is_empty());
+        assert_eq!("c.txt", one.children()[1].files()[0].name());
+        assert_eq!(3, one.children()[1].size());
+    }
+
+    #[test]
+    fn as_tree_of_several_roots() {
+        let first = nested_fixture();
+        let second = tempfile::tempdir().unwrap();
+        fs::create_dir(second.path().join("three")).unwrap();
+        fs::write(second.path().join("d.txt"), b"dd").unwrap();
+        fs::write(second.path().join("three").join("e.txt"), b"e").unwrap();
+
+        let outcome = smol::block_on(
+            DirMetadata::new_multi([first.path().to_owned(), second.path().to_owned()])
+                .dir_metadata(),
+        )
+        .unwrap();
+        let tree = outcome.as_tree();
+        assert_eq!(9, outcome.size());
+        assert_eq!(outcome.size(), tree.size());
+        assert_eq!("", tree.name());
+        assert!(tree.files().is_empty());
+
+        let mut roots = tree
+            .children()
+            .iter()
+            .map(|root| (root.path().to_owned(), root.size()))
+            .collect::<Vec<(PathBuf, u64)>>();
+        roots.sort();
+        let mut expected = vec![
+            (fs::canonicalize(first.path()).unwrap(), 6),
+            (fs::canonicalize(second.path()).unwrap(), 3),
+        ];
+        expected.sort();
+        assert_eq!(expected, roots);
+
+        let displayed = outcome.to_string();
+        assert_eq!(
+            2,
+            displayed
+                .lines()
+                .filter(|line| !line.starts_with(' '))
+                .count()
+        );
+        assert!(displayed.lines().any(|line| line == "  three/ (1.00B)"));
+    }
+
+    #[test]
+    fn display() {
+        let root = nested_fixture();
+        fs::write(root.path().join("large.txt"), [0u8; 2048]).unwrap();
+        let outcome = scan(root.path(), None);
+
+        let displayed = outcome.to_string();
+        let lines = displayed.lines().collect::<Vec<&str>>();
+        assert_eq!(7, lines.len());
+        assert!(lines[0].ends_with("/ (2.01KiB)"));
+        assert!(
+            lines[1].starts_with("  -")
+                && lines[1].contains(" 1.00B ")
+                && lines[1].ends_with(" a.txt")
+        );
+        assert!(lines[2].contains(" 2.00KiB ") && lines[2].ends_with(" large.txt"));
+        assert_eq!("  one/ (5.00B)", lines[3]);
+        assert!(
+            lines[4].starts_with("    -")
+                && lines[4].contains(" 2.00B ")
+                && lines[4].ends_with(" b.txt")
+        );
+        assert_eq!("    two/ (3.00B)", lines[5]);
+        assert!(lines[6].starts_with("      -") && lines[6].ends_with(" c.txt"));
+
+        let size_end = |line: &&str| line.trim_start().find("B ");
+        assert!([lines[1], lines[2], lines[4], lines[6]]
+            .iter()
+            .all(|line| size_end(line) == size_end(&lines[2])));
+
+        let file = outcome.get_file("a.txt").unwrap();
+        assert!(file.to_string().contains(" 1.00B "));
+        assert!(format!("{file:8}").contains("    1.00B "));
+    }
+}
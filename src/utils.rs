@@ -1,10 +1,21 @@
 #[cfg(feature = "time")]
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, SecondsFormat, TimeZone, Utc};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
 #[cfg(feature = "time")]
-use std::time::Duration;
-use std::{borrow::Cow, time::SystemTime};
+use std::{
+    fmt::{self, Write},
+    time::Duration,
+};
 use tai64::Tai64N;
 
+/// The error that a channel has been closed
+pub const SENDER_CHANNEL_ERROR: &str = "SENDER_CHANNEL_CLOSED";
+
 /// Reusable Clone-on-Write str with lifetime of `'a`
 pub type CowStr<'a> = Cow<'a, str>;
 
@@ -12,44 +23,221 @@ pub type CowStr<'a> = Cow<'a, str>;
 pub struct FsUtils;
 
 impl FsUtils {
+    /// The format of the date used by [Self::tai64_to_local_hrs] and [Self::tai64_to_local_am_pm]
+    #[cfg(feature = "time")]
+    const DATE_FORMAT: &'static str = "%A, %-d %B, %C%y";
+
     /// Returns [Option::None] if time query is not supported
     pub fn maybe_time(time_result: Option<SystemTime>) -> Option<Tai64N> {
         time_result.map(|time| Tai64N::from_system_time(&time))
     }
 
+    /// Compare two TAI64N timestamps, the earlier one is [Ordering::Less]
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::FsUtils;
+    /// use std::cmp::Ordering;
+    /// use tai64::Tai64N;
+    ///
+    /// assert_eq!(
+    ///     Ordering::Less,
+    ///     FsUtils::tai64_cmp(&Tai64N::UNIX_EPOCH, &Tai64N::now())
+    /// );
+    /// ```
+    pub fn tai64_cmp(a: &Tai64N, b: &Tai64N) -> Ordering {
+        a.cmp(b)
+    }
+
+    /// Convert a TAI64N timestamp to a [SystemTime]
+    pub fn tai64_to_system_time(time: &Tai64N) -> SystemTime {
+        time.to_system_time()
+    }
+
+    /// Get the whole seconds since UNIX EPOCH of a TAI64N timestamp,
+    /// [Option::None] if it is earlier than UNIX EPOCH
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::FsUtils;
+    /// use tai64::Tai64N;
+    ///
+    /// let time =
+    ///     Tai64N::from_system_time(&(std::time::UNIX_EPOCH + std::time::Duration::from_millis(1500)));
+    /// assert_eq!(Some(1), FsUtils::tai64_to_unix_secs(&time));
+    /// assert_eq!(Some(1500), FsUtils::tai64_to_unix_millis(&time));
+    /// ```
+    pub fn tai64_to_unix_secs(time: &Tai64N) -> Option<u64> {
+        Self::tai64_to_system_time(time)
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|duration| duration.as_secs())
+    }
+
+    /// Get the whole milliseconds since UNIX EPOCH of a TAI64N timestamp,
+    /// [Option::None] if it is earlier than UNIX EPOCH or too far in the future
+    pub fn tai64_to_unix_millis(time: &Tai64N) -> Option<u64> {
+        Self::tai64_to_system_time(time)
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|duration| duration.as_millis().try_into().ok())
+    }
+
+    /// Check if the extension of a path matches `extension` ignoring ASCII case.
+    /// A leading `.` in `extension` is ignored and paths without an extension never match
+    pub fn path_has_extension(path: &Path, extension: &str) -> bool {
+        let extension = extension.strip_prefix('.').unwrap_or(extension);
+
+        path.extension()
+            .is_some_and(|path_extension| path_extension.eq_ignore_ascii_case(extension))
+    }
+
     /// Calculate the size in bytes
-    pub fn size_to_bytes(bytes: usize) -> String {
-        byte_prefix::calc_bytes(bytes as f32)
+    pub fn size_to_bytes(bytes: u64) -> String {
+        Self::format_size(bytes, SizeStyle::default())
+    }
+
+    /// Format a size in bytes in the units of `style` with two decimal places eg `1.50KiB`
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{FsUtils, SizeStyle};
+    ///
+    /// assert_eq!("1.50KiB", FsUtils::format_size(1536, SizeStyle::Binary));
+    /// assert_eq!("1.54kB", FsUtils::format_size(1536, SizeStyle::Si));
+    /// assert_eq!("1536B", FsUtils::format_size(1536, SizeStyle::Bytes));
+    /// ```
+    pub fn format_size(bytes: u64, style: SizeStyle) -> String {
+        Self::format_size_with_decimals(bytes, style, 2)
+    }
+
+    /// Same as [Self::format_size] with `decimals` decimal places instead of two
+    pub fn format_size_with_decimals(bytes: u64, style: SizeStyle, decimals: usize) -> String {
+        let (base, units) = match style {
+            SizeStyle::Binary => (1024, ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+            SizeStyle::Si => (1000, ["B", "kB", "MB", "GB", "TB", "PB", "EB"]),
+            SizeStyle::Bytes => return format!("{bytes}B"),
+        };
+
+        let mut divisor = 1u64;
+        let mut unit = 0;
+        while unit + 1 < units.len() && bytes / divisor >= base {
+            divisor *= base;
+            unit += 1;
+        }
+
+        format!(
+            "{:.decimals$}{}",
+            bytes as f64 / divisor as f64,
+            units[unit]
+        )
     }
 
     /// Convert TAI64N to local time in 24 hour format
     #[cfg(feature = "time")]
     pub fn tai64_to_local_hrs<'a>(time: &Tai64N) -> DateTimeString<'a> {
-        let date_time: DateTime<Utc> = time.to_system_time().into();
-        let date = date_time
-            .date_naive()
-            .format("%A, %-d %B, %C%y")
-            .to_string();
-        let date = CowStr::Owned(date);
-        let time = date_time.format("%H:%M:%S").to_string();
-        let time = CowStr::Owned(time);
-
-        DateTimeString { date, time }
+        // The formats are valid so formatting never fails
+        Self::tai64_to_formatted(time, Self::DATE_FORMAT, "%H:%M:%S").unwrap_or_default()
     }
 
     /// Convert TAI64N to local time in 12 hour format
     #[cfg(feature = "time")]
     pub fn tai64_to_local_am_pm<'a>(time: &Tai64N) -> DateTimeString<'a> {
+        // The formats are valid so formatting never fails
+        Self::tai64_to_formatted(time, Self::DATE_FORMAT, "%-I:%M %p").unwrap_or_default()
+    }
+
+    /// Convert TAI64N to UTC in 24 hour format
+    #[cfg(feature = "time")]
+    pub fn tai64_to_utc_hrs<'a>(time: &Tai64N) -> DateTimeString<'a> {
+        // The formats are valid so formatting never fails
+        Self::tai64_to_formatted_utc(time, Self::DATE_FORMAT, "%H:%M:%S").unwrap_or_default()
+    }
+
+    /// Convert TAI64N to UTC in 12 hour format
+    #[cfg(feature = "time")]
+    pub fn tai64_to_utc_am_pm<'a>(time: &Tai64N) -> DateTimeString<'a> {
+        // The formats are valid so formatting never fails
+        Self::tai64_to_formatted_utc(time, Self::DATE_FORMAT, "%-I:%M %p").unwrap_or_default()
+    }
+
+    /// Convert TAI64N to local time with the date formatted with `date_format` and the time
+    /// formatted with `time_format`, both using the `strftime` specifiers supported by
+    /// [chrono::format::strftime]. The local time zone is read from the `TZ` environment
+    /// variable or the system settings. Returns an error if a format contains an invalid specifier
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::FsUtils;
+    /// use tai64::Tai64N;
+    ///
+    /// assert!(FsUtils::tai64_to_formatted(&Tai64N::now(), "%d/%m/%Y", "%Hh%M").is_ok());
+    /// assert!(FsUtils::tai64_to_formatted(&Tai64N::now(), "%Q", "%H").is_err());
+    /// ```
+    #[cfg(feature = "time")]
+    pub fn tai64_to_formatted<'a>(
+        time: &Tai64N,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<DateTimeString<'a>, fmt::Error> {
+        Self::tai64_to_formatted_in(time, &Local, date_format, time_format)
+    }
+
+    /// Same as [Self::tai64_to_formatted] but in the time zone `time_zone`
+    /// instead of local time, eg a [chrono::FixedOffset] chosen by the caller
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{chrono::FixedOffset, FsUtils};
+    /// use tai64::Tai64N;
+    ///
+    /// let plus_three = FixedOffset::east_opt(3 * 3600).unwrap();
+    /// let formatted =
+    ///     FsUtils::tai64_to_formatted_in(&Tai64N::UNIX_EPOCH, &plus_three, "%d/%m/%Y", "%Hh%M")
+    ///         .unwrap();
+    /// assert_eq!("01/01/1970", formatted.date);
+    /// assert_eq!("03h00", formatted.time);
+    /// assert_eq!("+03:00", formatted.offset);
+    /// ```
+    #[cfg(feature = "time")]
+    pub fn tai64_to_formatted_in<'a, Tz: TimeZone>(
+        time: &Tai64N,
+        time_zone: &Tz,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<DateTimeString<'a>, fmt::Error>
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let date_time = DateTime::<Utc>::from(time.to_system_time()).with_timezone(time_zone);
+
+        DateTimeString::format(date_time, date_format, time_format)
+    }
+
+    /// Same as [Self::tai64_to_formatted] but in UTC instead of local time
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::FsUtils;
+    /// use tai64::Tai64N;
+    ///
+    /// let formatted =
+    ///     FsUtils::tai64_to_formatted_utc(&Tai64N::UNIX_EPOCH, "%d/%m/%Y", "%Hh%M").unwrap();
+    /// assert_eq!("01/01/1970", formatted.date);
+    /// assert_eq!("00h00", formatted.time);
+    /// assert_eq!("+00:00", formatted.offset);
+    /// ```
+    #[cfg(feature = "time")]
+    pub fn tai64_to_formatted_utc<'a>(
+        time: &Tai64N,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<DateTimeString<'a>, fmt::Error> {
+        Self::tai64_to_formatted_in(time, &Utc, date_format, time_format)
+    }
+
+    /// Convert TAI64N to an RFC 3339 timestamp in UTC with nanosecond precision
+    /// like `2024-01-31T08:15:00.500000000Z`. The leap seconds between TAI and UTC
+    /// are removed the same way as the other converters do it
+    #[cfg(feature = "time")]
+    pub fn tai64_to_rfc3339(time: &Tai64N) -> String {
         let date_time: DateTime<Utc> = time.to_system_time().into();
-        let date = date_time
-            .date_naive()
-            .format("%A, %-d %B, %C%y")
-            .to_string();
-        let date = CowStr::Owned(date);
-        let time = date_time.format("%-I:%M %p").to_string();
-        let time = CowStr::Owned(time);
 
-        DateTimeString { date, time }
+        date_time.to_rfc3339_opts(SecondsFormat::Nanos, true)
     }
 
     /// Convert duration since UNIX EPOCH to humantime
@@ -73,40 +261,132 @@ impl FsUtils {
             .map(|duration| humantime::format_duration(duration).to_string())
     }
 
+    /// Describe how long before `current_time` the timestamp `time` is, rounded down to
+    /// the largest unit eg `2 days ago` or `3 hours ago`, and `just now` for less than a minute.
+    /// Timestamps after `current_time`, for example because of clock skew, are described
+    /// like `in 5 minutes`
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::FsUtils;
+    /// use std::time::Duration;
+    /// use tai64::Tai64N;
+    ///
+    /// let now = Tai64N::now();
+    ///
+    /// assert_eq!("just now", FsUtils::tai64_to_coarse_humantime(&now, &now));
+    /// assert_eq!(
+    ///     "2 days ago",
+    ///     FsUtils::tai64_to_coarse_humantime(&(now - Duration::from_secs(50 * 3600)), &now)
+    /// );
+    /// assert_eq!(
+    ///     "in 5 minutes",
+    ///     FsUtils::tai64_to_coarse_humantime(&(now + Duration::from_secs(300)), &now)
+    /// );
+    /// ```
+    #[cfg(feature = "time")]
+    pub fn tai64_to_coarse_humantime(time: &Tai64N, current_time: &Tai64N) -> String {
+        const UNITS: [(u64, &str); 6] = [
+            (365 * 24 * 3600, "year"),
+            (30 * 24 * 3600, "month"),
+            (7 * 24 * 3600, "week"),
+            (24 * 3600, "day"),
+            (3600, "hour"),
+            (60, "minute"),
+        ];
+
+        let (duration, in_future) = match current_time.duration_since(time) {
+            Ok(duration) => (duration, false),
+            Err(duration) => (duration, true),
+        };
+
+        let Some((count, unit)) = UNITS.iter().find_map(|(unit_secs, unit)| {
+            let count = duration.as_secs() / unit_secs;
+
+            (count > 0).then_some((count, unit))
+        }) else {
+            return "just now".to_owned();
+        };
+        let plural = if count == 1 { "" } else { "s" };
+
+        if in_future {
+            format!("in {count} {unit}{plural}")
+        } else {
+            format!("{count} {unit}{plural} ago")
+        }
+    }
+
+    /// Same as [Self::tai64_to_coarse_humantime] relative to the current time
+    #[cfg(feature = "time")]
+    pub fn tai64_now_to_coarse_humantime(time: &Tai64N) -> String {
+        Self::tai64_to_coarse_humantime(time, &Tai64N::now())
+    }
+
     /// Get the duration between two TAI64N timestamps
     #[cfg(feature = "time")]
     pub fn tai64_duration(earlier_time: &Tai64N, current_time: &Tai64N) -> Option<Duration> {
-        match earlier_time.duration_since(current_time) {
-            Ok(valid_time) => Some(valid_time),
-            Err(_) => Option::None,
-        }
+        earlier_time.duration_since(current_time).ok()
     }
 
     /// Get the duration since UNIX EPOCH
     #[cfg(feature = "time")]
     pub fn tai64_duration_since_epoch(time: &Tai64N) -> Option<Duration> {
-        match time.duration_since(&Tai64N::UNIX_EPOCH) {
-            Ok(valid_time) => Some(valid_time),
-            Err(_) => Option::None,
-        }
+        time.duration_since(&Tai64N::UNIX_EPOCH).ok()
     }
 
     /// Get the duration since UNIX EPOCH
     #[cfg(feature = "time")]
     pub fn tai64_duration_from_now(earlier_time: &Tai64N) -> Option<Duration> {
-        match Tai64N::now().duration_since(earlier_time) {
-            Ok(valid_time) => Some(valid_time),
-            Err(_) => Option::None,
-        }
+        Tai64N::now().duration_since(earlier_time).ok()
     }
 }
 
+/// The units used to format sizes by [FsUtils::format_size]
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Hash)]
+pub enum SizeStyle {
+    /// Multiples of 1024 like `KiB` and `MiB`
+    #[default]
+    Binary,
+    /// Multiples of 1000 like `kB` and `MB`
+    Si,
+    /// The exact number of bytes without a larger unit
+    Bytes,
+}
+
 /// The data and time in human readable [String]
 #[cfg(feature = "time")]
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DateTimeString<'a> {
     /// The data without a timestamp
     pub date: CowStr<'a>,
     /// A timestamp without a date
     pub time: CowStr<'a>,
+    /// The offset of the time zone of [Self::date] and [Self::time] from UTC eg `+02:00`
+    pub offset: CowStr<'a>,
+}
+
+#[cfg(feature = "time")]
+impl DateTimeString<'_> {
+    fn format<Tz: TimeZone>(
+        date_time: DateTime<Tz>,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<Self, fmt::Error>
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let format = |format: &str| {
+            let mut formatted = String::new();
+            // Formatting with `to_string` panics on invalid specifiers
+            write!(formatted, "{}", date_time.format(format))?;
+
+            Ok::<_, fmt::Error>(CowStr::Owned(formatted))
+        };
+
+        Ok(DateTimeString {
+            date: format(date_format)?,
+            time: format(time_format)?,
+            offset: format("%:z")?,
+        })
+    }
 }
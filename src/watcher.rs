@@ -1,39 +1,123 @@
-use inotify::{EventMask, Inotify, WatchMask};
-use smol::{channel::Sender, io};
+use crate::{
+    fs::{GlobFilter, SharedHook},
+    DirMetadata, SENDER_CHANNEL_ERROR,
+};
+use smol::{
+    channel::{self, Receiver, Sender, TrySendError},
+    future::{self, Future},
+    io, Timer,
+};
 use std::{
-    ffi::OsStr,
+    collections::{HashMap, VecDeque},
+    fmt,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
-/// The error that a channel has been closed
-pub const SENDER_CHANNEL_ERROR: &str = "SENDER_CHANNEL_CLOSED";
-
 /// The sender type for a channel as a type for reusability
 pub type FsSender = Sender<WatcherOutcome>;
 
-/// Create a watcher for a certain path that can be a file or directory
-///
-/// #### Structure
+/// The mask accepted by [FsWatcher::watch], an `inotify::WatchMask` on Linux
+/// with the `watcher` feature and a [WatchFor] with the `watcher-portable` backend.
+/// A [WatchFor] converts to both
+#[cfg(all(feature = "watcher", target_os = "linux"))]
+pub type BackendMask = inotify::WatchMask;
+
+/// The mask accepted by [FsWatcher::watch], an `inotify::WatchMask` on Linux
+/// with the `watcher` feature and a [WatchFor] with the `watcher-portable` backend.
+/// A [WatchFor] converts to both
+#[cfg(not(all(feature = "watcher", target_os = "linux")))]
+pub type BackendMask = WatchFor;
+
+/// The changes to watch for, converted to the mask of the backend of the watcher.
+/// Options are combined with `|`
+/// #### Example
 /// ```rust
-/// use dir_meta::FsSender;
-/// use std::path::PathBuf;
+/// use dir_meta::WatchFor;
 ///
-/// #[derive(Debug)]
-/// pub struct FsWatcher {
-///     path: Option<PathBuf>,
-///     sender: FsSender,
-/// }
+/// let watch_for = WatchFor::CREATE | WatchFor::DELETE;
+/// assert!(watch_for.contains(WatchFor::CREATE));
+/// assert!(!watch_for.contains(WatchFor::MODIFY));
 /// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct WatchFor(u32);
+
+impl WatchFor {
+    /// A file or directory was created inside a watched directory
+    pub const CREATE: Self = Self(1);
+    /// A file or directory was deleted from a watched directory
+    pub const DELETE: Self = Self(1 << 1);
+    /// A file was modified
+    pub const MODIFY: Self = Self(1 << 2);
+    /// A file or directory was moved into or out of a watched directory
+    pub const MOVE: Self = Self(1 << 3);
+    /// The metadata like permissions or timestamps changed
+    pub const ATTRIB: Self = Self(1 << 4);
+    /// A file was read
+    pub const ACCESS: Self = Self(1 << 5);
+    /// A file or directory was opened
+    pub const OPEN: Self = Self(1 << 6);
+    /// A file or directory was closed
+    pub const CLOSE: Self = Self(1 << 7);
+    /// The watched path itself was deleted
+    pub const DELETE_SELF: Self = Self(1 << 8);
+    /// The watched path itself was moved
+    pub const MOVE_SELF: Self = Self(1 << 9);
+    /// The changes to the content of a directory, [Self::MODIFY], [Self::CREATE],
+    /// [Self::DELETE] and [Self::MOVE]
+    pub const FILE_CHANGES: Self =
+        Self(Self::MODIFY.0 | Self::CREATE.0 | Self::DELETE.0 | Self::MOVE.0);
+    /// Every change
+    pub const ALL: Self = Self((1 << 10) - 1);
+
+    /// Check if all the options of `other` are set
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for WatchFor {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for WatchFor {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// What a watcher does with an outcome when the bounded channel of its [FsSender] is full
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum WhenFull {
+    /// Wait for the receiver to make room, no outcome is lost but the events
+    /// pile up in the kernel meanwhile and may overflow its queue
+    #[default]
+    Block,
+    /// Hold the outcomes that don't fit, up to as many as the capacity of the channel,
+    /// and drop the oldest held outcome to make room for a new one
+    DropOldest,
+    /// Drop the outcomes that don't fit
+    DropNewest,
+}
+
+/// The closure passed to [FsWatcher::on_overflow]
+type OverflowHook = dyn FnMut(&Path, io::Result<DirMetadata<'static>>) + Send;
+
+/// Create a watcher for a certain path that can be a file or directory
 ///
 /// #### Example
 /// ```rust
-/// use dir_meta::{inotify::WatchMask, smol::channel, FsWatcher, WatcherOutcome};
+/// use dir_meta::{smol::channel, FsWatcher, WatchFor, WatcherOutcome};
 ///
 /// smol::block_on(async {
 ///     let (sender, receiver) = channel::unbounded::<WatcherOutcome>();
 ///
-///     let watch_options =
-///         WatchMask::MODIFY | WatchMask::CREATE | WatchMask::DELETE | WatchMask::DELETE_SELF;
+///     let watch_options = WatchFor::FILE_CHANGES | WatchFor::DELETE_SELF;
 ///
 ///     smol::spawn(FsWatcher::new(sender).path("Foo").watch(watch_options)).detach();
 ///
@@ -44,58 +128,626 @@ pub type FsSender = Sender<WatcherOutcome>;
 /// ```
 #[derive(Debug)]
 pub struct FsWatcher {
-    path: Option<PathBuf>, //Option is used here to make it easier to return ErrorKind::NotFound in io::Result when calling watcher
-    sender: FsSender,
+    pub(crate) paths: Vec<PathBuf>,
+    pub(crate) sender: FsSender,
+    pub(crate) pair_renames: bool,
+    pub(crate) resilient: bool,
+    pub(crate) buffer_size: usize,
+    pub(crate) overflow_hook: Option<SharedHook<OverflowHook>>,
+    pub(crate) ignore_globs: GlobFilter,
+    pub(crate) only_globs: GlobFilter,
+    pub(crate) when_full: WhenFull,
+    pub(crate) outbox: Mutex<Outbox>,
 }
 
 impl FsWatcher {
+    /// How long a [WatcherEvents::MovedFrom] is held waiting for its [WatcherEvents::MovedTo]
+    /// when renames are paired
+    pub const RENAME_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// The longest time a [Self::resilient] watcher waits between two checks
+    /// for a deleted path to reappear
+    pub const REATTACH_MAX_DELAY: Duration = Duration::from_secs(2);
+
+    /// How often the outcomes held by a [WhenFull] policy are sent again while the channel is full
+    const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// The size in bytes of the buffer the events are read into unless [Self::buffer_size] is set
+    pub const DEFAULT_BUFFER_SIZE: usize = 4096;
+
+    /// The size of the largest single event, the header of an event followed by
+    /// a name of up to 255 bytes and its terminating nul byte
+    pub(crate) const MIN_BUFFER_SIZE: usize = 16 + 255 + 1;
+
     /// Create a new [FsWatcher] by passing an async-channel::channel::Sender with type specified by [FsSender]
     pub fn new(sender: FsSender) -> Self {
         Self {
             sender,
-            path: Option::default(),
+            paths: Vec::default(),
+            pair_renames: false,
+            resilient: false,
+            buffer_size: Self::DEFAULT_BUFFER_SIZE,
+            overflow_hook: None,
+            ignore_globs: GlobFilter::default(),
+            only_globs: GlobFilter::default(),
+            when_full: WhenFull::default(),
+            outbox: Mutex::default(),
         }
     }
 
-    /// Add the path to listen to
+    /// Create a new [FsWatcher] for [Self::watch_blocking] watching `paths`, which returns
+    /// the outcomes instead of sending them so no channel is needed. The other ways to watch
+    /// fail with [WatcherError::ChannelClosed] on the first outcome
+    pub fn blocking(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self::new(channel::bounded(1).0).paths(paths)
+    }
+
+    /// Add a path to listen to, can be called several times to watch several paths
+    /// with the same watcher
     pub fn path(mut self, path: impl AsRef<Path>) -> Self {
-        self.path.replace(path.as_ref().to_path_buf());
+        self.paths.push(path.as_ref().to_path_buf());
+
+        self
+    }
+
+    /// Add several paths to listen to
+    pub fn paths(mut self, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.paths.extend(paths);
 
         self
     }
 
-    /// Watch the path using the parameters from `inotify::WatchMask`
-    /// which can be concatenated `WatchMask::MODIFY | WatchMask::CREATE | WatchMask::DELETE`
-    pub async fn watch(self, watch_for: WatchMask) -> io::Result<()> {
-        if let Some(path) = self.path {
-            let mut inotify = Inotify::init()?;
+    /// Drop the events of entries whose name or full path matches the glob pattern,
+    /// like `*.swp` or `*~`, before they are sent. Can be called multiple times.
+    /// A rename is dropped only if both its names are filtered out.
+    /// Returns an error if the pattern is invalid
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{smol::channel, FsWatcher};
+    ///
+    /// let (sender, _receiver) = channel::unbounded();
+    /// let watcher = FsWatcher::new(sender)
+    ///     .path("src")
+    ///     .ignore_glob("*.swp")
+    ///     .unwrap()
+    ///     .ignore_glob("*~")
+    ///     .unwrap();
+    /// ```
+    pub fn ignore_glob(mut self, pattern: &str) -> Result<Self, globset::Error> {
+        self.ignore_globs.add(pattern)?;
+
+        Ok(self)
+    }
+
+    /// Only send the events of entries whose name or full path matches the glob pattern.
+    /// Can be called multiple times, an event is sent if it matches any of the patterns.
+    /// The events of the watched paths themselves and [WatcherEvents::QueueOverflow] are
+    /// always sent. Returns an error if the pattern is invalid
+    pub fn only_glob(mut self, pattern: &str) -> Result<Self, globset::Error> {
+        self.only_globs.add(pattern)?;
+
+        Ok(self)
+    }
+
+    /// Report a [WatcherEvents::MovedFrom] followed by the [WatcherEvents::MovedTo] with the
+    /// same cookie as a single [WatcherEvents::Renamed] outcome carrying both names.
+    /// A [WatcherEvents::MovedFrom] without its other half within [Self::RENAME_TIMEOUT]
+    /// is reported as is since the entry was moved out of the watched path.
+    /// The watch mask needs [WatchFor::MOVE], or both `WatchMask::MOVED_FROM` and `WatchMask::MOVED_TO`
+    pub fn pair_renames(mut self, pair_renames: bool) -> Self {
+        self.pair_renames = pair_renames;
+
+        self
+    }
+
+    /// Keep watching a path that is deleted or moved away, like a file replaced by an editor
+    /// which renames a new file over it. The watcher checks for the path to reappear,
+    /// waiting longer between each check up to [Self::REATTACH_MAX_DELAY], then watches it
+    /// again and sends a [WatcherEvents::Reattached] outcome since the events in between were missed.
+    /// Only supported by the inotify backend
+    pub fn resilient(mut self, resilient: bool) -> Self {
+        self.resilient = resilient;
+
+        self
+    }
+
+    /// The size in bytes of the buffer the events are read into, a larger buffer reads
+    /// more events at once which helps keeping up with busy directories.
+    /// Sizes too small to hold the largest event are raised to that size.
+    /// Only used by the inotify backend
+    pub fn buffer_size(mut self, bytes: usize) -> Self {
+        self.buffer_size = bytes.max(Self::MIN_BUFFER_SIZE);
+
+        self
+    }
+
+    /// Choose what happens to an outcome when the channel passed to [Self::new] is a bounded
+    /// channel that is full, which keeps the memory used by a slow receiver bounded.
+    /// The number of outcomes dropped by [WhenFull::DropOldest] and [WhenFull::DropNewest]
+    /// is sent in a [WatcherEvents::Dropped] outcome as soon as the channel has room again.
+    /// Outcomes are never dropped with an unbounded channel
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{smol::channel, FsWatcher, WhenFull};
+    ///
+    /// let (sender, _receiver) = channel::bounded(64);
+    /// let watcher = FsWatcher::new(sender)
+    ///     .path("src")
+    ///     .when_full(WhenFull::DropOldest);
+    /// ```
+    pub fn when_full(mut self, when_full: WhenFull) -> Self {
+        self.when_full = when_full;
+
+        self
+    }
+
+    /// Call `on_overflow` with a fresh scan of each watched directory after a
+    /// [WatcherEvents::QueueOverflow], the events lost in the overflow can be recovered
+    /// by comparing the scan with the state known before. The scan of a directory
+    /// that can no longer be read is passed as the error
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{smol::channel, FsWatcher};
+    ///
+    /// let (sender, _receiver) = channel::unbounded();
+    /// let watcher = FsWatcher::new(sender)
+    ///     .path("src")
+    ///     .buffer_size(64 * 1024)
+    ///     .on_overflow(|path, rescan| match rescan {
+    ///         Ok(outcome) => println!("`{}` has {} files", path.display(), outcome.files().len()),
+    ///         Err(error) => eprintln!("Unable to rescan `{}`: {error}", path.display()),
+    ///     });
+    /// ```
+    pub fn on_overflow<F>(mut self, on_overflow: F) -> Self
+    where
+        F: FnMut(&Path, io::Result<DirMetadata<'static>>) + Send + 'static,
+    {
+        self.overflow_hook
+            .replace(SharedHook(Arc::new(Mutex::new(on_overflow))));
+
+        self
+    }
+
+    /// Watch the paths for the changes in `watch_for`, either a [WatchFor] like
+    /// `WatchFor::MODIFY | WatchFor::CREATE` or any other [BackendMask].
+    /// The future waits for events without blocking the executor so it can run
+    /// on the same thread as the task receiving the events. It completes with `Ok(())`
+    /// once the watches of all the paths are removed, for example because they were deleted,
+    /// and with [WatcherError::ChannelClosed] once the receiver of the channel is dropped
+    pub async fn watch(self, watch_for: impl Into<BackendMask>) -> Result<(), WatcherError> {
+        self.watch_until(watch_for.into(), None).await
+    }
+
+    /// Same as [Self::watch] but returns a [WatcherHandle] alongside the future
+    /// that watches the paths. Calling [WatcherHandle::stop] or dropping the handle
+    /// removes the watches and completes the future with `Ok(())` without waiting
+    /// for another event, [WatcherHandle::unwatch] stops watching a single path
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{smol::channel, FsWatcher, WatchFor};
+    ///
+    /// smol::block_on(async {
+    ///     let (sender, _receiver) = channel::unbounded();
+    ///     let (handle, watch) = FsWatcher::new(sender)
+    ///         .path("src")
+    ///         .watch_with_handle(WatchFor::CREATE);
+    ///     let watch = smol::spawn(watch);
+    ///
+    ///     handle.stop();
+    ///     assert!(watch.await.is_ok());
+    /// });
+    /// ```
+    pub fn watch_with_handle(
+        self,
+        watch_for: impl Into<BackendMask>,
+    ) -> (
+        WatcherHandle,
+        impl Future<Output = Result<(), WatcherError>> + Send + 'static,
+    ) {
+        let (control_sender, control_receiver) = channel::unbounded::<PathBuf>();
+
+        (
+            WatcherHandle(control_sender),
+            self.watch_until(watch_for.into(), Some(control_receiver)),
+        )
+    }
+
+    /// Watch the paths on the current thread without an executor, returning the outcomes
+    /// instead of sending them through the channel. The watches are added before this returns
+    /// and the iterator blocks until the next event, it ends once the watches of all the paths
+    /// are removed or after an error reading the events. Renames are only paired when both
+    /// halves are read at once, [Self::resilient], [Self::when_full] and [Self::on_overflow]
+    /// do not apply. [FsWatcher::blocking] creates a watcher without a channel for this
+    /// #### Example
+    /// ```rust
+    /// use dir_meta::{FsWatcher, WatchFor};
+    ///
+    /// let dir = std::env::temp_dir().join("dir-meta-watch-blocking");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    ///
+    /// let outcomes =
+    ///     FsWatcher::blocking([dir.clone()]).watch_blocking(WatchFor::CREATE | WatchFor::DELETE_SELF);
+    /// std::fs::write(dir.join("a.txt"), b"a").unwrap();
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    ///
+    /// for outcome in outcomes {
+    ///     dbg!(outcome.unwrap().mask);
+    /// }
+    /// ```
+    pub fn watch_blocking(
+        self,
+        watch_for: impl Into<BackendMask>,
+    ) -> impl Iterator<Item = Result<WatcherOutcome, WatcherError>> {
+        self.blocking_outcomes(watch_for.into())
+    }
+
+    /// Passes a fresh scan of each watched directory to the [Self::on_overflow] hook
+    pub(crate) async fn rescan<'p>(&self, watched: impl IntoIterator<Item = &'p PathBuf>) {
+        let Some(overflow_hook) = self.overflow_hook.as_ref() else {
+            return;
+        };
+
+        let mut directories = watched
+            .into_iter()
+            .filter(|path| path.is_dir())
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        directories.sort();
+        directories.dedup();
+
+        for path in directories {
+            let rescan = DirMetadata::new_multi([path.clone()]).dir_metadata().await;
+            (overflow_hook.lock())(&path, rescan);
+        }
+    }
+
+    /// Pairs the renames and applies the glob filters, returns the outcome to send if any.
+    /// Both halves of a rename are either sent or dropped: a [WatcherEvents::Renamed] passes
+    /// if either of its names passes and a [WatcherEvents::MovedTo] follows the decision
+    /// made for the [WatcherEvents::MovedFrom] with the same cookie
+    pub(crate) fn admit(
+        &self,
+        outcome: WatcherOutcome,
+        pending_moves: &mut PendingMoves,
+    ) -> Option<WatcherOutcome> {
+        let outcome = match self.pair_renames {
+            true => pending_moves.pair(outcome)?,
+            false => outcome,
+        };
 
-            inotify.watches().add(&path, watch_for)?;
+        if self.ignore_globs.globs.is_empty() && self.only_globs.globs.is_empty() {
+            return Some(outcome);
+        }
 
-            //TODO add logging here "Watching current directory for activity..."
+        let passes = match outcome.mask {
+            WatcherEvents::Renamed => {
+                self.passes(outcome.old_name.as_deref(), outcome.old_path.as_deref())
+                    || self.passes(outcome.name.as_deref(), outcome.path.as_deref())
+            }
+            WatcherEvents::MovedFrom | WatcherEvents::MovedTo if outcome.cookie != 0 => {
+                pending_moves.decide(
+                    outcome.cookie,
+                    self.passes(outcome.name.as_deref(), outcome.path.as_deref()),
+                )
+            }
+            _ => self.passes(outcome.name.as_deref(), outcome.path.as_deref()),
+        };
 
-            let mut buffer = [0u8; 4096];
+        passes.then_some(outcome)
+    }
 
-            loop {
-                let events = inotify.read_events_blocking(&mut buffer)?;
+    /// Checks the name and path of an entry against [Self::ignore_glob] and [Self::only_glob],
+    /// the events without a name concern a watched path and always pass
+    pub(crate) fn passes(&self, name: Option<&str>, path: Option<&Path>) -> bool {
+        let Some(name) = name else {
+            return true;
+        };
+        let matches = |filter: &GlobFilter| {
+            filter.set.is_match(name) || path.is_some_and(|path| filter.set.is_match(path))
+        };
 
-                for event in events {
-                    let outcome: WatcherOutcome = event.into();
+        !matches(&self.ignore_globs)
+            && (self.only_globs.globs.is_empty() || matches(&self.only_globs))
+    }
 
-                    if self.sender.clone().send(outcome).await.is_err() {
-                        return Err(io::Error::new(io::ErrorKind::Other, SENDER_CHANNEL_ERROR));
+    /// Sends an outcome, or applies the [Self::when_full] policy to it if the channel is full
+    pub(crate) async fn send(&self, outcome: WatcherOutcome) -> Result<(), WatcherError> {
+        if self.when_full == WhenFull::Block {
+            return self
+                .sender
+                .send(outcome)
+                .await
+                .map_err(|_| WatcherError::ChannelClosed);
+        }
+
+        let mut outbox = self
+            .outbox
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        outbox.held.push_back(outcome);
+        outbox.flush(&self.sender)?;
+
+        let capacity = match self.when_full {
+            WhenFull::DropOldest => self.sender.capacity().unwrap_or(usize::MAX),
+            _ => 0,
+        };
+        while outbox.held.len() > capacity {
+            match self.when_full {
+                WhenFull::DropOldest => outbox.held.pop_front(),
+                _ => outbox.held.pop_back(),
+            };
+            outbox.dropped += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the outcomes held by the [Self::when_full] policy that fit in the channel
+    pub(crate) fn flush(&self) -> Result<(), WatcherError> {
+        self.outbox
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .flush(&self.sender)
+    }
+
+    /// When the outcomes held by the [Self::when_full] policy should be sent again
+    pub(crate) fn retry_deadline(&self) -> Option<Instant> {
+        let outbox = self
+            .outbox
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        (!outbox.held.is_empty() || outbox.dropped > 0)
+            .then(|| Instant::now() + Self::RETRY_INTERVAL)
+    }
+}
+
+/// The error returned when watching the paths fails
+#[derive(Debug)]
+pub enum WatcherError {
+    /// The receiver of the channel passed to [FsWatcher::new] was dropped
+    ChannelClosed,
+    /// No path was added with [FsWatcher::path] or [FsWatcher::paths]
+    PathNotSet,
+    /// An error reported by the operating system, like a watched path that does not exist
+    Io(io::Error),
+    /// The limit of watches for the user was reached, on Linux this is set by
+    /// `/proc/sys/fs/inotify/max_user_watches`
+    WatchLimitReached,
+}
+
+impl fmt::Display for WatcherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChannelClosed => write!(f, "{SENDER_CHANNEL_ERROR}"),
+            Self::PathNotSet => write!(f, "The path was not found, maybe you didn't specify it"),
+            Self::Io(error) => write!(f, "{error}"),
+            Self::WatchLimitReached => write!(
+                f,
+                "The limit of watches was reached, on Linux it can be raised with \
+                `sysctl fs.inotify.max_user_watches=<limit>`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WatcherError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WatcherError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Converts to the errors returned before [WatcherError] was introduced,
+/// a closed channel is an [io::ErrorKind::Other] error with the message [SENDER_CHANNEL_ERROR]
+/// #### Example
+/// ```rust
+/// use dir_meta::{smol::io, WatcherError, SENDER_CHANNEL_ERROR};
+///
+/// let error = io::Error::from(WatcherError::ChannelClosed);
+/// assert_eq!(SENDER_CHANNEL_ERROR, error.to_string());
+/// ```
+impl From<WatcherError> for io::Error {
+    fn from(error: WatcherError) -> Self {
+        match error {
+            WatcherError::ChannelClosed => io::Error::other(SENDER_CHANNEL_ERROR),
+            WatcherError::PathNotSet => io::Error::new(io::ErrorKind::NotFound, error.to_string()),
+            WatcherError::Io(error) => error,
+            WatcherError::WatchLimitReached => {
+                io::Error::new(io::ErrorKind::StorageFull, error.to_string())
+            }
+        }
+    }
+}
+
+/// The outcomes held back by a [WhenFull] policy until the channel has room
+#[derive(Debug, Default)]
+pub(crate) struct Outbox {
+    held: VecDeque<WatcherOutcome>,
+    /// The outcomes dropped since the last [WatcherEvents::Dropped] outcome was sent
+    dropped: usize,
+}
+
+impl Outbox {
+    /// Sends the count of dropped outcomes first, since they were dropped
+    /// before the held outcomes were sent, then the held outcomes
+    fn flush(&mut self, sender: &FsSender) -> Result<(), WatcherError> {
+        loop {
+            let report = self.dropped > 0;
+            let outcome = match report {
+                true => WatcherOutcome::dropped(self.dropped),
+                false => match self.held.pop_front() {
+                    Some(outcome) => outcome,
+                    None => return Ok(()),
+                },
+            };
+
+            match sender.try_send(outcome) {
+                Ok(()) if report => self.dropped = 0,
+                Ok(()) => (),
+                Err(TrySendError::Full(outcome)) => {
+                    if !report {
+                        self.held.push_front(outcome);
                     }
+
+                    return Ok(());
                 }
+                Err(TrySendError::Closed(_)) => return Err(WatcherError::ChannelClosed),
             }
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "The path was not found, maybe you didn't specify it",
-            ))
         }
     }
 }
 
+/// The moves waiting for their other half when renames are paired, oldest first
+#[derive(Default)]
+pub(crate) struct PendingMoves {
+    moves: VecDeque<(Instant, WatcherOutcome)>,
+    /// Whether the first half of a rename with the cookie passed the glob filters
+    /// when renames are not paired, so that the other half is treated the same
+    decisions: HashMap<u32, (Instant, bool)>,
+}
+
+impl PendingMoves {
+    /// Holds a [WatcherEvents::MovedFrom] and turns a [WatcherEvents::MovedTo] into
+    /// a [WatcherEvents::Renamed] if its other half is held, returns the outcome to send if any
+    pub(crate) fn pair(&mut self, mut outcome: WatcherOutcome) -> Option<WatcherOutcome> {
+        match outcome.mask {
+            WatcherEvents::MovedFrom => {
+                self.moves.push_back((Instant::now(), outcome));
+
+                return None;
+            }
+            WatcherEvents::MovedTo => {
+                let moved_from = self
+                    .moves
+                    .iter()
+                    .position(|(_, moved_from)| moved_from.cookie == outcome.cookie)
+                    .and_then(|index| self.moves.remove(index));
+
+                if let Some((_, moved_from)) = moved_from {
+                    outcome.rename_from(moved_from);
+                }
+            }
+            _ => (),
+        }
+
+        Some(outcome)
+    }
+
+    /// Removes the moves held for longer than [FsWatcher::RENAME_TIMEOUT]
+    pub(crate) fn expired(&mut self) -> Vec<WatcherOutcome> {
+        let mut expired = Vec::<WatcherOutcome>::new();
+
+        while let Some((moved_at, _)) = self.moves.front() {
+            if moved_at.elapsed() < FsWatcher::RENAME_TIMEOUT {
+                break;
+            }
+
+            if let Some((_, moved_from)) = self.moves.pop_front() {
+                expired.push(moved_from);
+            }
+        }
+
+        expired
+    }
+
+    /// Returns the decision made for the other half of the rename with `cookie`,
+    /// or records `passes` as the decision for both halves
+    pub(crate) fn decide(&mut self, cookie: u32, passes: bool) -> bool {
+        if let Some((_, decision)) = self.decisions.remove(&cookie) {
+            return decision;
+        }
+
+        // The other half of a rename from or to outside the watched paths never arrives
+        self.decisions
+            .retain(|_, (decided_at, _)| decided_at.elapsed() < FsWatcher::RENAME_TIMEOUT);
+        self.decisions.insert(cookie, (Instant::now(), passes));
+
+        passes
+    }
+
+    /// Removes all the moves, oldest first
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = WatcherOutcome> + '_ {
+        self.moves.drain(..).map(|(_, moved_from)| moved_from)
+    }
+
+    /// When the oldest move expires
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.moves
+            .front()
+            .map(|(moved_at, _)| *moved_at + FsWatcher::RENAME_TIMEOUT)
+    }
+}
+
+/// Why the watch loop stopped waiting for events
+pub(crate) enum Wake {
+    Continue,
+    Unwatch(PathBuf),
+    Stop,
+}
+
+impl Wake {
+    /// Waits for a path to unwatch or for the [WatcherHandle] to stop, or until `deadline`
+    pub(crate) async fn wait(
+        control: Option<&Receiver<PathBuf>>,
+        deadline: Option<Instant>,
+    ) -> io::Result<Self> {
+        let controlled = async {
+            match control {
+                Some(control) => match control.recv().await {
+                    Ok(path) => Ok(Wake::Unwatch(path)),
+                    Err(_) => Ok(Wake::Stop),
+                },
+                None => future::pending().await,
+            }
+        };
+        let deadline_reached = async {
+            match deadline {
+                Some(deadline) => {
+                    Timer::at(deadline).await;
+                }
+                None => future::pending().await,
+            }
+
+            Ok(Wake::Continue)
+        };
+
+        future::or(controlled, deadline_reached).await
+    }
+}
+
+/// Controls the watch started by [FsWatcher::watch_with_handle], which stops
+/// when [Self::stop] is called or when the handle is dropped
+#[derive(Debug)]
+pub struct WatcherHandle(Sender<PathBuf>);
+
+impl WatcherHandle {
+    /// Remove the watches and complete the watch with `Ok(())`
+    pub fn stop(&self) {
+        self.0.close();
+    }
+
+    /// Check if [Self::stop] has been called
+    pub fn is_stopped(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    /// Stop watching one of the paths added with [FsWatcher::path] or [FsWatcher::paths],
+    /// the other paths are still watched. A [WatcherEvents::Ignored] event is sent
+    /// for the path and the watch completes with `Ok(())` once no path is left
+    pub fn unwatch(&self, path: impl AsRef<Path>) {
+        self.0.try_send(path.as_ref().to_path_buf()).ok();
+    }
+}
+
 /// Events triggered from watching a directory or file
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub enum WatcherEvents {
@@ -174,34 +826,20 @@ pub enum WatcherEvents {
     /// unmounted. An event with [`WatchMask::IGNORED`] will subsequently be
     /// generated for the same watch descriptor.
     Unmount,
+    /// File or directory was renamed inside the watched directory, reported instead of
+    /// [Self::MovedFrom] and [Self::MovedTo] when [FsWatcher::pair_renames] is enabled
+    Renamed,
+    /// The watched path was deleted or moved away and a new entry at the same path
+    /// is now watched, only sent by a [FsWatcher::resilient] watcher.
+    /// The events between the removal and this event were missed
+    Reattached,
+    /// Outcomes were dropped because the channel was full, their number is
+    /// [WatcherOutcome::dropped]. Only sent with a [FsWatcher::when_full] policy that drops outcomes
+    Dropped,
     /// Current event is unsupported
     Unsupported,
 }
 
-impl From<EventMask> for WatcherEvents {
-    fn from(value: EventMask) -> Self {
-        match value {
-            EventMask::ACCESS => Self::Access,
-            EventMask::ATTRIB => Self::Attrib,
-            EventMask::CLOSE_WRITE => Self::CloseWrite,
-            EventMask::CLOSE_NOWRITE => Self::CloseNoWrite,
-            EventMask::CREATE => Self::Create,
-            EventMask::DELETE => Self::Delete,
-            EventMask::DELETE_SELF => Self::DeleteSelf,
-            EventMask::MODIFY => Self::Modify,
-            EventMask::MOVE_SELF => Self::MoveSelf,
-            EventMask::MOVED_FROM => Self::MovedFrom,
-            EventMask::MOVED_TO => Self::MovedTo,
-            EventMask::OPEN => Self::Open,
-            EventMask::IGNORED => Self::Ignored,
-            EventMask::ISDIR => Self::IsDir,
-            EventMask::Q_OVERFLOW => Self::QueueOverflow,
-            EventMask::UNMOUNT => Self::Unmount,
-            _ => Self::Unsupported,
-        }
-    }
-}
-
 /// The outcome of a watched file or directory
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct WatcherOutcome {
@@ -209,8 +847,12 @@ pub struct WatcherOutcome {
     /// This WatchDescriptor is equal to the one that Watches::add returned when interest for this event was registered. The WatchDescriptor can be used to remove the watch using Watches::remove,
     /// thereby preventing future events of this type from being created.
     pub descriptor: i32,
-    /// Indicates what kind of event this is
+    /// Indicates what kind of event this is, the primary event if several are combined
     pub mask: WatcherEvents,
+    /// All the events combined in the mask of this event, see [WatcherEvents::split]
+    pub events: Vec<WatcherEvents>,
+    /// The subject of the event is a directory
+    pub is_dir: bool,
     /// Connects related events to each other
     /// When a file is renamed, this results two events: MOVED_FROM and MOVED_TO. The cookie field will be the same for both of them, thereby making is possible to connect the event pair.
     pub cookie: u32,
@@ -218,19 +860,539 @@ pub struct WatcherOutcome {
     /// This field is set only if the subject of the event is a file or directory in a watched directory.
     /// If the event concerns a file or directory that is watched directly, name will be None.
     pub name: Option<String>,
+    /// The full path of the file or directory the event originates from, the watched path
+    /// joined with [Self::name] or the watched path itself if the event concerns it
+    pub path: Option<PathBuf>,
+    /// The path added with [FsWatcher::path] or [FsWatcher::paths] this event belongs to
+    pub watched_path: Option<PathBuf>,
+    /// The name before a [WatcherEvents::Renamed] event, [Self::name] is the new name
+    pub old_name: Option<String>,
+    /// The full path before a [WatcherEvents::Renamed] event, [Self::path] is the new path
+    pub old_path: Option<PathBuf>,
+    /// The number of outcomes dropped before a [WatcherEvents::Dropped] event
+    pub dropped: usize,
 }
 
-impl From<inotify::Event<&OsStr>> for WatcherOutcome {
-    fn from(event: inotify::Event<&OsStr>) -> Self {
-        let name = event
-            .name
-            .map(|inner_name| inner_name.to_string_lossy().to_string());
-
+impl WatcherOutcome {
+    /// The outcome reporting `count` dropped outcomes
+    fn dropped(count: usize) -> Self {
         Self {
-            descriptor: event.wd.get_watch_descriptor_id(),
-            mask: event.mask.into(),
-            cookie: event.cookie,
-            name,
+            descriptor: -1,
+            mask: WatcherEvents::Dropped,
+            events: vec![WatcherEvents::Dropped],
+            is_dir: false,
+            cookie: 0,
+            name: None,
+            path: None,
+            watched_path: None,
+            old_name: None,
+            old_path: None,
+            dropped: count,
+        }
+    }
+
+    /// Turns this [WatcherEvents::MovedTo] into a [WatcherEvents::Renamed] from `moved_from`
+    fn rename_from(&mut self, moved_from: WatcherOutcome) {
+        self.mask = WatcherEvents::Renamed;
+        self.events.iter_mut().for_each(|event| {
+            if *event == WatcherEvents::MovedTo {
+                *event = WatcherEvents::Renamed;
+            }
+        });
+        self.old_name = moved_from.name;
+        self.old_path = moved_from.path;
+    }
+}
+
+#[cfg(test)]
+mod sanity_checks {
+    use std::fs;
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn watcher_yields_to_the_executor() {
+        use crate::{FsWatcher, WatchFor, WatcherEvents};
+        use smol::{channel, future, Timer};
+        use std::time::Duration;
+
+        let root = tempfile::tempdir().unwrap();
+        let (sender, receiver) = channel::unbounded();
+        let watch = FsWatcher::new(sender)
+            .path(root.path())
+            .watch(WatchFor::CREATE);
+
+        // The watcher is polled first so its watch is added before the file is created
+        let outcome = smol::block_on(future::or(
+            future::or(
+                async {
+                    watch.await.unwrap();
+
+                    None
+                },
+                async {
+                    fs::write(root.path().join("a.txt"), b"a").unwrap();
+
+                    receiver.recv().await.ok()
+                },
+            ),
+            async {
+                Timer::after(Duration::from_secs(5)).await;
+
+                None
+            },
+        ))
+        .unwrap();
+        assert_eq!(WatcherEvents::Create, outcome.mask);
+        assert!(!outcome.is_dir);
+        assert_eq!(Some("a.txt".to_owned()), outcome.name);
+        assert_eq!(Some(root.path().join("a.txt")), outcome.path);
+    }
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn watcher_handle() {
+        use crate::{inotify::WatchMask, FsWatcher};
+        use smol::channel;
+
+        let root = tempfile::tempdir().unwrap();
+        let (sender, _receiver) = channel::unbounded();
+
+        let (handle, watch) = FsWatcher::new(sender.clone())
+            .path(root.path())
+            .watch_with_handle(WatchMask::CREATE);
+        let watch = smol::spawn(watch);
+        // Lets the watch start waiting for events before it is stopped
+        smol::block_on(smol::Timer::after(std::time::Duration::from_millis(50)));
+        handle.stop();
+        assert!(handle.is_stopped());
+        assert!(smol::block_on(watch).is_ok());
+
+        let (handle, watch) = FsWatcher::new(sender)
+            .path(root.path())
+            .watch_with_handle(WatchMask::CREATE);
+        let watch = smol::spawn(watch);
+        drop(handle);
+        assert!(smol::block_on(watch).is_ok());
+    }
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn watcher_pairs_renames() {
+        use crate::{inotify::WatchMask, test_utils::nested_fixture, FsWatcher, WatcherEvents};
+        use smol::channel;
+
+        let root = nested_fixture();
+        let outside = tempfile::tempdir().unwrap();
+        let (sender, receiver) = channel::unbounded();
+        let (_handle, watch) = FsWatcher::new(sender)
+            .path(root.path())
+            .pair_renames(true)
+            .watch_with_handle(WatchMask::MOVED_FROM | WatchMask::MOVED_TO);
+        smol::spawn(watch).detach();
+        // Lets the watch add its watches before the files are moved
+        smol::block_on(smol::Timer::after(std::time::Duration::from_millis(50)));
+
+        fs::rename(root.path().join("a.txt"), root.path().join("z.txt")).unwrap();
+        let renamed = smol::block_on(receiver.recv()).unwrap();
+        assert_eq!(WatcherEvents::Renamed, renamed.mask);
+        assert_eq!(Some("a.txt".to_owned()), renamed.old_name);
+        assert_eq!(Some(root.path().join("z.txt")), renamed.path);
+
+        fs::rename(root.path().join("z.txt"), outside.path().join("z.txt")).unwrap();
+        let moved_out = smol::block_on(receiver.recv()).unwrap();
+        assert_eq!(WatcherEvents::MovedFrom, moved_out.mask);
+        assert_eq!(Some("z.txt".to_owned()), moved_out.name);
+        assert!(receiver.is_empty());
+    }
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn watcher_multiple_paths() {
+        use crate::{inotify::WatchMask, FsWatcher, WatcherEvents};
+        use smol::channel;
+
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let (sender, receiver) = channel::unbounded();
+        let (handle, watch) = FsWatcher::new(sender)
+            .paths([first.path().to_owned(), second.path().to_owned()])
+            .watch_with_handle(WatchMask::CREATE);
+        let watch = smol::spawn(watch);
+        // Lets the watch add its watches before the files are created
+        smol::block_on(smol::Timer::after(std::time::Duration::from_millis(50)));
+
+        fs::write(second.path().join("a.txt"), b"a").unwrap();
+        let outcome = smol::block_on(receiver.recv()).unwrap();
+        assert_eq!(Some(second.path().to_owned()), outcome.watched_path);
+        assert_eq!(Some(second.path().join("a.txt")), outcome.path);
+
+        handle.unwatch(second.path());
+        smol::block_on(smol::Timer::after(std::time::Duration::from_millis(50)));
+        fs::write(second.path().join("b.txt"), b"b").unwrap();
+        fs::write(first.path().join("c.txt"), b"c").unwrap();
+        let ignored = smol::block_on(receiver.recv()).unwrap();
+        assert_eq!(WatcherEvents::Ignored, ignored.mask);
+        assert_eq!(Some(second.path().to_owned()), ignored.watched_path);
+        let outcome = smol::block_on(receiver.recv()).unwrap();
+        assert_eq!(Some(first.path().join("c.txt")), outcome.path);
+
+        handle.unwatch(first.path());
+        assert!(smol::block_on(watch).is_ok());
+        let ignored = smol::block_on(receiver.recv()).unwrap();
+        assert_eq!(Some(first.path().to_owned()), ignored.watched_path);
+        assert!(receiver.is_empty());
+    }
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn resilient_watcher() {
+        use crate::{inotify::WatchMask, FsWatcher, WatcherEvents};
+        use smol::{channel, future, Timer};
+        use std::time::Duration;
+
+        let root = tempfile::tempdir().unwrap();
+        let config = root.path().join("config.toml");
+        fs::write(&config, b"a").unwrap();
+
+        let (sender, receiver) = channel::unbounded();
+        let (_handle, watch) = FsWatcher::new(sender)
+            .path(&config)
+            .resilient(true)
+            .watch_with_handle(WatchMask::MODIFY);
+        smol::spawn(watch).detach();
+        // Lets the watch add its watches before the file is replaced
+        smol::block_on(Timer::after(Duration::from_millis(50)));
+
+        // Replaces the file like an editor does, the watched file is deleted
+        let replacement = root.path().join("config.toml.swp");
+        fs::write(&replacement, b"b").unwrap();
+        fs::rename(&replacement, &config).unwrap();
+
+        let next_event = |event: WatcherEvents| {
+            smol::block_on(future::or(
+                async {
+                    while let Ok(outcome) = receiver.recv().await {
+                        if outcome.mask == event {
+                            return Some(outcome);
+                        }
+                    }
+
+                    None
+                },
+                async {
+                    Timer::after(Duration::from_secs(5)).await;
+
+                    None
+                },
+            ))
+        };
+
+        let reattached = next_event(WatcherEvents::Reattached).unwrap();
+        assert_eq!(Some(config.clone()), reattached.path);
+
+        fs::write(&config, b"c").unwrap();
+        let modified = next_event(WatcherEvents::Modify).unwrap();
+        assert_eq!(Some(config.clone()), modified.path);
+    }
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn watcher_rescans_on_overflow() {
+        use crate::{inotify::WatchMask, FsWatcher, WatcherEvents};
+        use smol::{channel, Timer};
+        use std::time::Duration;
+
+        let root = tempfile::tempdir().unwrap();
+        let max_queued_events = fs::read_to_string("/proc/sys/fs/inotify/max_queued_events")
+            .ok()
+            .and_then(|max| max.trim().parse::<usize>().ok())
+            .unwrap_or(16384);
+
+        // The watcher waits to send its first event so the events pile up in the kernel
+        let (sender, receiver) = channel::bounded(1);
+        let (rescan_sender, rescans) = std::sync::mpsc::channel();
+        let (_handle, watch) = FsWatcher::new(sender)
+            .path(root.path())
+            .buffer_size(0)
+            .on_overflow(move |_, rescan| {
+                rescan_sender.send(rescan.unwrap().files().len()).unwrap();
+            })
+            .watch_with_handle(WatchMask::CREATE);
+        smol::spawn(watch).detach();
+        // Lets the watch add its watches before the files are created
+        smol::block_on(Timer::after(Duration::from_millis(50)));
+
+        let file_count = max_queued_events + 16;
+        (0..file_count).for_each(|index| {
+            fs::File::create(root.path().join(index.to_string())).unwrap();
+        });
+
+        smol::block_on(async {
+            while let Ok(outcome) = receiver.recv().await {
+                if outcome.mask == WatcherEvents::QueueOverflow {
+                    break;
+                }
+            }
+        });
+        receiver.close();
+        assert_eq!(
+            file_count,
+            rescans.recv_timeout(Duration::from_secs(60)).unwrap()
+        );
+    }
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn watch_for_masks() {
+        use crate::{inotify::WatchMask, WatchFor};
+
+        assert_eq!(
+            WatchMask::MODIFY | WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVE,
+            WatchFor::FILE_CHANGES.into()
+        );
+        assert_eq!(WatchMask::ALL_EVENTS, WatchFor::ALL.into());
+        assert_eq!(WatchMask::empty(), WatchFor::default().into());
+
+        let mut watch_for = WatchFor::ATTRIB;
+        watch_for |= WatchFor::MOVE_SELF;
+        assert!(watch_for.contains(WatchFor::MOVE_SELF));
+        assert!(!watch_for.contains(WatchFor::FILE_CHANGES));
+    }
+
+    #[test]
+    fn watcher_reports_file_changes() {
+        use crate::{FsWatcher, WatchFor, WatcherEvents};
+        use smol::{channel, future, Timer};
+        use std::time::Duration;
+
+        let root = tempfile::tempdir().unwrap();
+        let file = root.path().join("a.txt");
+        let (sender, receiver) = channel::unbounded();
+        let (handle, watch) = FsWatcher::new(sender)
+            .path(root.path())
+            .watch_with_handle(WatchFor::CREATE | WatchFor::MODIFY | WatchFor::DELETE);
+
+        let events = smol::block_on(async {
+            let watch = smol::spawn(watch);
+            Timer::after(Duration::from_millis(100)).await;
+
+            fs::write(&file, b"a").unwrap();
+            Timer::after(Duration::from_millis(50)).await;
+            fs::write(&file, b"ab").unwrap();
+            Timer::after(Duration::from_millis(50)).await;
+            fs::remove_file(&file).unwrap();
+
+            let mut events = Vec::<WatcherEvents>::new();
+            while !events.contains(&WatcherEvents::Delete) {
+                let outcome = future::or(async { receiver.recv().await.ok() }, async {
+                    Timer::after(Duration::from_secs(5)).await;
+
+                    None
+                })
+                .await
+                .unwrap();
+                assert_eq!(Some(file.clone()), outcome.path);
+                assert_eq!(Some(root.path().to_path_buf()), outcome.watched_path);
+
+                if !events.contains(&outcome.mask) {
+                    events.push(outcome.mask);
+                }
+            }
+
+            handle.stop();
+            watch.await.unwrap();
+
+            events
+        });
+        assert_eq!(
+            vec![
+                WatcherEvents::Create,
+                WatcherEvents::Modify,
+                WatcherEvents::Delete
+            ],
+            events
+        );
+    }
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn watcher_glob_filters() {
+        use crate::{FsWatcher, WatchFor, WatcherEvents};
+        use smol::channel;
+
+        let root = tempfile::tempdir().unwrap();
+        let (sender, receiver) = channel::unbounded();
+        let (_handle, watch) = FsWatcher::new(sender)
+            .path(root.path())
+            .ignore_glob("*.swp")
+            .unwrap()
+            .ignore_glob("*~")
+            .unwrap()
+            .watch_with_handle(WatchFor::CREATE | WatchFor::MOVE);
+        smol::spawn(watch).detach();
+        // Lets the watch add its watches before the files are created
+        smol::block_on(smol::Timer::after(std::time::Duration::from_millis(50)));
+
+        fs::write(root.path().join("a.txt.swp"), b"a").unwrap();
+        fs::write(root.path().join("a.txt~"), b"a").unwrap();
+        fs::write(root.path().join("b.txt"), b"b").unwrap();
+        // Both halves follow the first one, which passes here and is filtered out next
+        fs::rename(root.path().join("b.txt"), root.path().join("b.swp")).unwrap();
+        fs::rename(root.path().join("a.txt.swp"), root.path().join("a.txt")).unwrap();
+        fs::write(root.path().join("c.txt"), b"c").unwrap();
+
+        let mut outcomes = Vec::<(WatcherEvents, String)>::new();
+        while outcomes.len() < 4 {
+            let outcome = smol::block_on(receiver.recv()).unwrap();
+            outcomes.push((outcome.mask, outcome.name.unwrap()));
         }
+        assert_eq!(
+            vec![
+                (WatcherEvents::Create, "b.txt".to_owned()),
+                (WatcherEvents::MovedFrom, "b.txt".to_owned()),
+                (WatcherEvents::MovedTo, "b.swp".to_owned()),
+                (WatcherEvents::Create, "c.txt".to_owned()),
+            ],
+            outcomes
+        );
+
+        let (sender, receiver) = channel::unbounded();
+        let (_handle, watch) = FsWatcher::new(sender)
+            .path(root.path())
+            .only_glob("*.txt")
+            .unwrap()
+            .pair_renames(true)
+            .watch_with_handle(WatchFor::CREATE | WatchFor::MOVE);
+        smol::spawn(watch).detach();
+        smol::block_on(smol::Timer::after(std::time::Duration::from_millis(50)));
+
+        fs::write(root.path().join("d.log"), b"d").unwrap();
+        fs::rename(root.path().join("d.log"), root.path().join("d.txt")).unwrap();
+        let renamed = smol::block_on(receiver.recv()).unwrap();
+        assert_eq!(WatcherEvents::Renamed, renamed.mask);
+        assert_eq!(Some("d.log".to_owned()), renamed.old_name);
+        assert!(FsWatcher::new(channel::unbounded().0)
+            .ignore_glob("a[")
+            .is_err());
+    }
+
+    #[cfg(all(feature = "watcher", target_os = "linux"))]
+    #[test]
+    fn watcher_when_full() {
+        use crate::{FsWatcher, WatchFor, WatcherEvents, WhenFull};
+        use smol::channel;
+        use std::time::Duration;
+
+        let received = |when_full: WhenFull| {
+            let root = tempfile::tempdir().unwrap();
+            let (sender, receiver) = channel::bounded(2);
+            let (_handle, watch) = FsWatcher::new(sender)
+                .path(root.path())
+                .when_full(when_full)
+                .watch_with_handle(WatchFor::CREATE);
+            smol::spawn(watch).detach();
+            // Lets the watch add its watches before the files are created
+            smol::block_on(smol::Timer::after(Duration::from_millis(50)));
+
+            ["a", "b", "c", "d", "e", "f"]
+                .iter()
+                .for_each(|name| fs::write(root.path().join(name), b"").unwrap());
+            smol::block_on(smol::Timer::after(Duration::from_millis(200)));
+
+            let mut received = Vec::<String>::new();
+            while received.len() < 5 {
+                let outcome = smol::block_on(async {
+                    smol::future::or(async { receiver.recv().await.ok() }, async {
+                        smol::Timer::after(Duration::from_secs(2)).await;
+
+                        None
+                    })
+                    .await
+                });
+                let Some(outcome) = outcome else {
+                    break;
+                };
+
+                match outcome.mask {
+                    WatcherEvents::Dropped => received.push(format!("{} dropped", outcome.dropped)),
+                    _ => received.push(outcome.name.unwrap()),
+                }
+            }
+
+            received
+        };
+
+        assert_eq!(
+            vec!["a", "b", "2 dropped", "e", "f"],
+            received(WhenFull::DropOldest)
+        );
+        assert_eq!(vec!["a", "b", "4 dropped"], received(WhenFull::DropNewest));
+    }
+
+    #[test]
+    fn watcher_watch_blocking() {
+        use crate::{FsWatcher, WatchFor, WatcherEvents};
+        use std::{thread, time::Duration};
+
+        let root = tempfile::tempdir().unwrap();
+        let watched = root.path().join("watched");
+        fs::create_dir(&watched).unwrap();
+        let file = watched.join("a.txt");
+
+        let outcomes = FsWatcher::blocking([watched.clone()])
+            .watch_blocking(WatchFor::CREATE | WatchFor::DELETE_SELF);
+        let changes = {
+            let (watched, file) = (watched.clone(), file.clone());
+
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                fs::write(&file, b"a").unwrap();
+                thread::sleep(Duration::from_millis(50));
+                fs::remove_dir_all(&watched).unwrap();
+            })
+        };
+
+        let outcomes = outcomes.map(|outcome| outcome.unwrap()).collect::<Vec<_>>();
+        changes.join().unwrap();
+
+        assert_eq!(WatcherEvents::Create, outcomes[0].mask);
+        assert_eq!(Some(file), outcomes[0].path);
+        assert!(outcomes
+            .iter()
+            .any(|outcome| outcome.mask == WatcherEvents::DeleteSelf));
+        assert_eq!(
+            Some(WatcherEvents::Ignored),
+            outcomes.last().map(|outcome| outcome.mask)
+        );
+    }
+
+    #[test]
+    fn watcher_errors() {
+        use crate::{FsWatcher, WatchFor, WatcherError};
+        use smol::channel;
+
+        let root = tempfile::tempdir().unwrap();
+
+        let (sender, _receiver) = channel::unbounded();
+        let watched = smol::block_on(FsWatcher::new(sender).watch(WatchFor::CREATE));
+        assert!(matches!(watched, Err(WatcherError::PathNotSet)));
+
+        let (sender, receiver) = channel::unbounded();
+        let watch = FsWatcher::new(sender)
+            .path(root.path())
+            .watch(WatchFor::CREATE);
+        drop(receiver);
+        let watched = smol::block_on(async {
+            let watch = smol::spawn(watch);
+            smol::Timer::after(std::time::Duration::from_millis(100)).await;
+            fs::write(root.path().join("a.txt"), b"a").unwrap();
+
+            watch.await
+        });
+        assert!(matches!(watched, Err(WatcherError::ChannelClosed)));
+        assert_eq!(
+            crate::SENDER_CHANNEL_ERROR,
+            smol::io::Error::from(watched.unwrap_err()).to_string()
+        );
     }
 }